@@ -0,0 +1,107 @@
+//! The content of a document as the renderers lay it out, before it goes into the PDF.
+//!
+//! Renderers push their genpdf elements to [`Blocks`] instead of straight into the
+//! `genpdf::Document`, which cannot be looked into once an element is pushed.  Each element is
+//! kept with a [`Block`] saying what it is, so that tests can check what a renderer produced,
+//! such as its headings, tables and page breaks, without rendering a PDF.
+
+use std::any::Any;
+
+use genpdf::fonts::{FontCache, FontData, FontFamily};
+use genpdf::{elements, Alignment, Element};
+
+/// What an element pushed by a renderer is.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Block {
+    /// A heading of level `level`, 1 being the top.
+    Heading { level: usize, text: String },
+    /// A paragraph of body text.
+    Paragraph(String),
+    /// A line of code or other monospaced text.
+    Code(String),
+    /// A table with `rows` rows, counting the header, and the alignment of each column.
+    Table { rows: usize, alignments: Vec<Alignment> },
+    Image,
+    PageBreak,
+    /// Vertical space between blocks.
+    Space,
+    /// Anything else, such as a title or a caption.
+    Other,
+}
+
+impl Block {
+    /// What `element` is, as far as its type tells.
+    fn of<E: Element + 'static>(element: &E) -> Block {
+        let element = element as &dyn Any;
+        if element.is::<elements::PageBreak>() {
+            Block::PageBreak
+        } else if element.is::<elements::Break>() {
+            Block::Space
+        } else if element.is::<elements::Image>() {
+            Block::Image
+        } else {
+            Block::Other
+        }
+    }
+}
+
+/// The elements of a document in order, each with the [`Block`] it is, and the document they
+/// go into; renderers register the fonts they need on it.
+pub struct Blocks {
+    doc: genpdf::Document,
+    elements: Vec<(Block, Box<dyn Element>)>,
+}
+
+impl Blocks {
+    pub fn new(doc: genpdf::Document) -> Self {
+        Self { doc, elements: Vec::new() }
+    }
+
+    /// Adds `element`, telling what it is from its type.
+    pub fn push<E: Element + 'static>(&mut self, element: E) {
+        let block = Block::of(&element);
+        self.push_block(block, element);
+    }
+
+    /// Adds `element`, which is `block`.
+    pub fn push_block<E: Element + 'static>(&mut self, block: Block, element: E) {
+        self.elements.push((block, Box::new(element)));
+    }
+
+    /// What the elements pushed so far are, in order.
+    #[cfg(test)]
+    pub fn blocks(&self) -> impl Iterator<Item = &Block> {
+        self.elements.iter().map(|(block, _)| block)
+    }
+
+    pub fn add_font_family(&mut self, family: FontFamily<FontData>) -> FontFamily<genpdf::fonts::Font> {
+        self.doc.add_font_family(family)
+    }
+
+    pub fn font_cache(&self) -> &FontCache {
+        self.doc.font_cache()
+    }
+
+    /// The document with all elements pushed to it.
+    pub fn into_document(self) -> genpdf::Document {
+        let mut doc = self.doc;
+        for (_, element) in self.elements {
+            doc.push(Boxed(element));
+        }
+        doc
+    }
+}
+
+/// An element of any type, as genpdf only takes elements of a known size.
+struct Boxed(Box<dyn Element>);
+
+impl Element for Boxed {
+    fn render(
+        &mut self,
+        context: &genpdf::Context,
+        area: genpdf::render::Area<'_>,
+        style: genpdf::style::Style,
+    ) -> Result<genpdf::RenderResult, genpdf::error::Error> {
+        self.0.render(context, area, style)
+    }
+}
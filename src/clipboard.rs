@@ -0,0 +1,57 @@
+//! Saving clipboard contents to a file so they can go through the normal conversion path, and
+//! putting text such as output paths on the clipboard.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+
+/// Writes the clipboard's text or image to a file in `dir` and returns its path.
+///
+/// Text is saved as `.md` when it looks like Markdown and as `.txt` otherwise; images are saved
+/// as PNG.  Text takes precedence when the clipboard holds both.
+pub fn save_clipboard(dir: &Path) -> Result<PathBuf> {
+    let mut clipboard = arboard::Clipboard::new().context("Failed to access the clipboard")?;
+    let stem = format!("topdf_clipboard_{}", std::process::id());
+
+    if let Ok(text) = clipboard.get_text()
+        && !text.trim().is_empty()
+    {
+        let extension = if looks_like_markdown(&text) { "md" } else { "txt" };
+        let path = dir.join(format!("{}.{}", stem, extension));
+        std::fs::write(&path, text).context("Failed to save clipboard text")?;
+        log::info!("Saved clipboard text to {:?}", path);
+        return Ok(path);
+    }
+
+    if let Ok(image) = clipboard.get_image() {
+        let buffer = image::RgbaImage::from_raw(image.width as u32, image.height as u32, image.bytes.into_owned())
+            .context("Clipboard image has an unexpected size")?;
+        let path = dir.join(format!("{}.png", stem));
+        // genpdf does not accept images with an alpha channel.
+        image::DynamicImage::ImageRgba8(buffer).to_rgb8().save(&path).context("Failed to save clipboard image")?;
+        log::info!("Saved clipboard image to {:?}", path);
+        return Ok(path);
+    }
+
+    Err(anyhow!("Clipboard contains no text or image"))
+}
+
+/// Replaces the clipboard's contents with `text`.
+pub fn copy_text(text: &str) -> Result<()> {
+    let mut clipboard = arboard::Clipboard::new().context("Failed to access the clipboard")?;
+    clipboard.set_text(text).context("Failed to copy to the clipboard")?;
+    Ok(())
+}
+
+/// A rough check for Markdown syntax at the start of lines (headings, lists, fences, quotes).
+fn looks_like_markdown(text: &str) -> bool {
+    text.lines().any(|line| {
+        let line = line.trim_start();
+        line.starts_with("# ")
+            || line.starts_with("## ")
+            || line.starts_with("```")
+            || line.starts_with("> ")
+            || line.starts_with("- ")
+            || line.starts_with("* ")
+    })
+}
@@ -0,0 +1,277 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::converter::{ConversionOptions, Density, ImageCompression, PageMargins, TextAlign};
+use crate::styles::StyleSheet;
+
+/// Location of the user configuration, next to the `logs/` directory.
+pub const CONFIG_FILE: &str = "topdf_config.toml";
+
+/// Persistent application settings loaded from [`CONFIG_FILE`].
+///
+/// Every field has a default, so a partial or missing file is fine.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    pub fonts: FontConfig,
+    /// User presets, listed after the built-in ones. A preset with a built-in name replaces it.
+    pub presets: Vec<Preset>,
+    pub redaction: RedactionConfig,
+    pub performance: PerformanceMode,
+    pub accent: AccentColor,
+    pub theme: UiTheme,
+    pub styles: StyleSheet,
+    /// Folder for intermediate files such as extracted ZIP entries; the system's temporary
+    /// folder if unset.
+    pub temp_dir: Option<PathBuf>,
+}
+
+/// Overall look of the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UiTheme {
+    #[default]
+    Dark,
+    /// Black background, white text and lighter highlights that meet the WCAG AA contrast
+    /// ratio for all text, with larger secondary text.
+    HighContrast,
+}
+
+impl UiTheme {
+    pub const ALL: [UiTheme; 2] = [UiTheme::Dark, UiTheme::HighContrast];
+
+    fn key(self) -> &'static str {
+        match self {
+            UiTheme::Dark => "dark",
+            UiTheme::HighContrast => "high_contrast",
+        }
+    }
+}
+
+/// Highlight color of the window: buttons, progress bar and headings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccentColor {
+    #[default]
+    Blue,
+    Teal,
+    Green,
+    Purple,
+    Orange,
+    Pink,
+}
+
+impl AccentColor {
+    pub const ALL: [AccentColor; 6] = [
+        AccentColor::Blue,
+        AccentColor::Teal,
+        AccentColor::Green,
+        AccentColor::Purple,
+        AccentColor::Orange,
+        AccentColor::Pink,
+    ];
+
+    /// Red, green and blue between 0 and 1.
+    pub fn rgb(self) -> [f32; 3] {
+        match self {
+            AccentColor::Blue => [0.2, 0.6, 1.0],
+            AccentColor::Teal => [0.1, 0.75, 0.75],
+            AccentColor::Green => [0.3, 0.75, 0.35],
+            AccentColor::Purple => [0.65, 0.45, 1.0],
+            AccentColor::Orange => [1.0, 0.6, 0.2],
+            AccentColor::Pink => [1.0, 0.4, 0.65],
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            AccentColor::Blue => "blue",
+            AccentColor::Teal => "teal",
+            AccentColor::Green => "green",
+            AccentColor::Purple => "purple",
+            AccentColor::Orange => "orange",
+            AccentColor::Pink => "pink",
+        }
+    }
+}
+
+/// How much of the machine a batch may use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PerformanceMode {
+    /// One file at a time, with a pause after each, to keep a laptop cool and responsive.
+    PowerSaving,
+    /// Half of the CPU cores.
+    #[default]
+    Balanced,
+    /// Every CPU core.
+    Performance,
+}
+
+impl PerformanceMode {
+    pub const ALL: [PerformanceMode; 3] = [PerformanceMode::PowerSaving, PerformanceMode::Balanced, PerformanceMode::Performance];
+
+    /// Files loaded and post-processed at the same time.
+    pub fn workers(self) -> usize {
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        match self {
+            PerformanceMode::PowerSaving => 1,
+            PerformanceMode::Balanced => (cores / 2).max(1),
+            PerformanceMode::Performance => cores,
+        }
+    }
+
+    /// Idle time of a worker after each file.
+    pub fn pause(self) -> Duration {
+        match self {
+            PerformanceMode::PowerSaving => Duration::from_millis(500),
+            PerformanceMode::Balanced | PerformanceMode::Performance => Duration::ZERO,
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            PerformanceMode::PowerSaving => "power_saving",
+            PerformanceMode::Balanced => "balanced",
+            PerformanceMode::Performance => "performance",
+        }
+    }
+}
+
+/// User-defined redaction rules, offered next to the built-in email and phone patterns.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionConfig {
+    /// Regular expressions whose matches are masked when custom redaction is enabled.
+    pub patterns: Vec<String>,
+}
+
+/// A named set of conversion options.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preset {
+    pub name: String,
+    pub options: ConversionOptions,
+}
+
+/// Font overrides for the PDF output and the interface. Unset entries use the built-in selection.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FontConfig {
+    /// Font for prose: text, Markdown, DOCX, HTML and tables. Should cover CJK.
+    pub text: Option<PathBuf>,
+    /// Monospace font for source code and structured data (JSON, XML, YAML, TOML).
+    pub mono: Option<PathBuf>,
+    /// Font for characters the primary font lacks (symbols, other scripts).
+    pub fallback: Option<PathBuf>,
+    /// Font of the interface itself. Must cover Chinese; read once at startup.
+    pub ui: Option<PathBuf>,
+}
+
+impl AppConfig {
+    /// The built-in presets followed by the ones from the configuration file.
+    pub fn presets(&self) -> Vec<Preset> {
+        let mut presets = builtin_presets();
+        for preset in &self.presets {
+            match presets.iter_mut().find(|p| p.name == preset.name) {
+                Some(existing) => *existing = preset.clone(),
+                None => presets.push(preset.clone()),
+            }
+        }
+        presets
+    }
+
+    /// Loads the configuration, falling back to defaults if the file is missing or invalid.
+    pub fn load() -> Self {
+        Self::load_from(Path::new(CONFIG_FILE))
+    }
+
+    /// Writes `mode` to the configuration file.
+    pub fn save_performance(mode: PerformanceMode) -> anyhow::Result<()> {
+        Self::save_setting("performance", mode.key())
+    }
+
+    /// Writes `accent` to the configuration file.
+    pub fn save_accent(accent: AccentColor) -> anyhow::Result<()> {
+        Self::save_setting("accent", accent.key())
+    }
+
+    /// Writes `theme` to the configuration file.
+    pub fn save_theme(theme: UiTheme) -> anyhow::Result<()> {
+        Self::save_setting("theme", theme.key())
+    }
+
+    /// Sets the top-level `key` of the configuration file, keeping the rest of the file
+    /// (comments included) as the user wrote it.
+    fn save_setting(key: &str, value: &str) -> anyhow::Result<()> {
+        let path = Path::new(CONFIG_FILE);
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut document: toml_edit::DocumentMut = content.parse()?;
+        document[key] = toml_edit::value(value);
+        std::fs::write(path, document.to_string())?;
+        log::info!("Saved {} = {:?} to {:?}", key, value, path);
+        Ok(())
+    }
+
+    fn load_from(path: &Path) -> Self {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(_) => {
+                log::info!("No configuration file at {:?}, using defaults", path);
+                return Self::default();
+            }
+        };
+        match toml::from_str(&content) {
+            Ok(config) => {
+                log::info!("Loaded configuration from {:?}", path);
+                config
+            }
+            Err(e) => {
+                log::warn!("Invalid configuration file {:?}, using defaults: {}", path, e);
+                Self::default()
+            }
+        }
+    }
+}
+
+fn builtin_presets() -> Vec<Preset> {
+    let defaults = ConversionOptions::default();
+    vec![
+        Preset {
+            name: "紧凑".to_string(),
+            options: ConversionOptions {
+                font_size: 10,
+                margins: PageMargins::uniform(8),
+                markdown_density: Density::Compact,
+                ..defaults.clone()
+            },
+        },
+        Preset {
+            name: "打印友好".to_string(),
+            options: ConversionOptions {
+                font_size: 11,
+                margins: PageMargins { top: 20, right: 15, bottom: 20, left: 25 },
+                page_numbers: true,
+                text_align: TextAlign::Justify,
+                ..defaults.clone()
+            },
+        },
+        Preset {
+            name: "演示".to_string(),
+            // Presentation mode doubles the font size of Markdown and Org files by itself.
+            options: ConversionOptions {
+                presentation: true,
+                margins: PageMargins::uniform(15),
+                markdown_density: Density::Loose,
+                image_compression: ImageCompression { enabled: true, ..ImageCompression::default() },
+                ..defaults
+            },
+        },
+    ]
+}
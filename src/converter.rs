@@ -1,28 +1,59 @@
 use genpdf::fonts::FontData;
+use std::borrow::Cow;
+use std::cell::{OnceCell, RefCell};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use anyhow::{Context, Result};
 use genpdf::{elements, style, Element};
-use pulldown_cmark::{Parser, Event, Tag, TagEnd, HeadingLevel};
+use pulldown_cmark::{CodeBlockKind, HeadingLevel, Parser, Event, Options, Tag, TagEnd};
 use serde_json::Value;
 use std::io::Read;
 use zip::ZipArchive;
 use std::sync::Arc;
-use calamine::{Reader, open_workbook, Xlsx, Xls};
+use calamine::Reader;
+use unicode_bidi::BidiInfo;
+use serde::{Deserialize, Serialize};
+use crate::blocks::{Block, Blocks};
+use crate::error::ConversionError;
+use crate::layout::{BidiParagraph, JustifiedParagraph};
+use crate::page_template::{PageTemplate, PageValues, TemplateDecorator};
+use crate::ranges::PartRange;
+use crate::stats::DocumentStats;
+use crate::styles::{ElementStyle, StyleSheet, Weight};
+use crate::{export, graphviz, html, links, markdown, merge, mermaid, org, pdfa, pipeline, redact, rst, scratch, shading, stats, subtitle, tree};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FileType {
     Markdown,
     Json,
     Xml,
     Txt,
+    Code,
     Docx,
     Html,
     Csv,
+    /// Tab-separated values, converted like CSV files.
+    Tsv,
     Image,
     Yaml,
     Toml,
     Excel,
+    /// OpenDocument spreadsheets, converted like Excel workbooks.
+    Ods,
+    Eml,
+    Rst,
+    /// Emacs Org mode notes, laid out like Markdown.
+    Org,
+    Subtitle,
+    /// Application and server logs, with lines colored by log level.
+    Log,
+    /// Graphviz graphs, rendered with the system's `dot` if it is installed.
+    Dot,
+    Sqlite,
+    Zip,
+    /// Existing PDFs, which can only be appended to another PDF as they are.
+    Pdf,
     Unknown,
 }
 
@@ -32,295 +63,3896 @@ impl FileType {
             Some("md") | Some("markdown") => FileType::Markdown,
             Some("json") => FileType::Json,
             Some("xml") => FileType::Xml,
-            Some("txt") | Some("rs") | Some("py") | Some("js") | Some("c") | Some("cpp") => FileType::Txt,
+            Some("txt") => FileType::Txt,
+            Some("rs") | Some("py") | Some("js") | Some("c") | Some("cpp") => FileType::Code,
             Some("docx") => FileType::Docx,
             Some("html") | Some("htm") => FileType::Html,
             Some("csv") => FileType::Csv,
+            Some("tsv") | Some("tab") => FileType::Tsv,
             Some("png") | Some("jpg") | Some("jpeg") | Some("bmp") => FileType::Image,
             Some("yaml") | Some("yml") => FileType::Yaml,
             Some("toml") => FileType::Toml,
             Some("xlsx") | Some("xls") => FileType::Excel,
+            Some("ods") => FileType::Ods,
+            Some("eml") => FileType::Eml,
+            Some("rst") => FileType::Rst,
+            Some("org") => FileType::Org,
+            Some("srt") | Some("vtt") => FileType::Subtitle,
+            Some("log") => FileType::Log,
+            Some("dot") | Some("gv") => FileType::Dot,
+            Some("sqlite") | Some("sqlite3") | Some("db") => FileType::Sqlite,
+            Some("pdf") => FileType::Pdf,
+            Some("zip") => FileType::Zip,
             _ => FileType::Unknown,
         }
     }
+
+    /// The type the first bytes of a file give away, for formats with a distinctive signature.
+    /// DOCX, XLSX and ODS files are ZIP archives inside and read as `Zip`.
+    pub fn sniff(head: &[u8]) -> Option<Self> {
+        if head.starts_with(b"%PDF-") {
+            Some(FileType::Pdf)
+        } else if head.starts_with(b"\x89PNG\r\n\x1a\n") || head.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(FileType::Image)
+        } else if head.starts_with(b"SQLite format 3\0") {
+            Some(FileType::Sqlite)
+        } else if head.starts_with(b"PK\x03\x04") || head.starts_with(b"PK\x05\x06") {
+            Some(FileType::Zip)
+        } else {
+            None
+        }
+    }
+
+    /// Whether content that [`Self::sniff`] reads as `sniffed` fits this type.
+    pub fn fits_content(self, sniffed: FileType) -> bool {
+        self == sniffed
+            || (sniffed == FileType::Zip && matches!(self, FileType::Docx | FileType::Excel | FileType::Ods))
+    }
+
+    /// Whether the file is a workbook of worksheets: Excel or OpenDocument.
+    pub fn is_spreadsheet(self) -> bool {
+        matches!(self, FileType::Excel | FileType::Ods)
+    }
+
+    /// Whether the document has top-level headings to split it at with
+    /// [`ConversionOptions::split_by_heading`].
+    pub fn splits_at_headings(self) -> bool {
+        matches!(self, FileType::Markdown | FileType::Org | FileType::Docx)
+    }
+
+    /// Whether the content is best shown in a monospace font.
+    pub fn prefers_monospace(self) -> bool {
+        matches!(self, FileType::Code | FileType::Json | FileType::Xml | FileType::Yaml | FileType::Toml | FileType::Dot | FileType::Log)
+    }
+}
+
+/// Horizontal alignment of body paragraphs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+    Justify,
+}
+
+impl TextAlign {
+    pub const ALL: [TextAlign; 4] = [TextAlign::Left, TextAlign::Center, TextAlign::Right, TextAlign::Justify];
+}
+
+/// Spacing between Markdown blocks (paragraphs, headings, code blocks).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Density {
+    Compact,
+    #[default]
+    Normal,
+    Loose,
+}
+
+impl Density {
+    pub const ALL: [Density; 3] = [Density::Compact, Density::Normal, Density::Loose];
+
+    /// Height of the break after a block, in lines.
+    fn block_spacing(self) -> f64 {
+        match self {
+            Density::Compact => 0.2,
+            Density::Normal => 0.5,
+            Density::Loose => 1.0,
+        }
+    }
+}
+
+/// How JSON documents are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonLayout {
+    /// An indented tree with a line per value.
+    #[default]
+    Expanded,
+    /// A line per item of a top-level array or entry of a top-level object, for record-style
+    /// files such as logs.
+    Compact,
+}
+
+impl JsonLayout {
+    pub const ALL: [JsonLayout; 2] = [JsonLayout::Expanded, JsonLayout::Compact];
+}
+
+/// Paper size of the output pages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PageSize {
+    #[default]
+    A4,
+    A5,
+    Letter,
+    Legal,
+}
+
+impl PageSize {
+    pub const ALL: [PageSize; 4] = [PageSize::A4, PageSize::A5, PageSize::Letter, PageSize::Legal];
+
+    /// Width and height in millimeters, portrait.
+    fn dimensions_mm(self) -> (f64, f64) {
+        match self {
+            PageSize::A4 => (210.0, 297.0),
+            PageSize::A5 => (148.0, 210.0),
+            PageSize::Letter => (216.0, 279.0),
+            PageSize::Legal => (216.0, 356.0),
+        }
+    }
+}
+
+/// Which version of a DOCX document with tracked changes to render.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackedChanges {
+    /// All changes accepted: insertions kept, deletions dropped.
+    #[default]
+    Final,
+    /// All changes rejected: the text before revising.
+    Original,
+}
+
+impl TrackedChanges {
+    pub const ALL: [TrackedChanges; 2] = [TrackedChanges::Final, TrackedChanges::Original];
+}
+
+/// Character encoding of text inputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextEncoding {
+    /// A byte order mark if there is one, otherwise UTF-8, then the first legacy encoding
+    /// that decodes the whole file.
+    #[default]
+    Auto,
+    Utf8,
+    Gbk,
+    Gb18030,
+    Big5,
+    ShiftJis,
+    Latin1,
+}
+
+impl TextEncoding {
+    pub const ALL: [TextEncoding; 7] = [
+        TextEncoding::Auto,
+        TextEncoding::Utf8,
+        TextEncoding::Gbk,
+        TextEncoding::Gb18030,
+        TextEncoding::Big5,
+        TextEncoding::ShiftJis,
+        TextEncoding::Latin1,
+    ];
+
+    fn encoding(self) -> Option<&'static encoding_rs::Encoding> {
+        match self {
+            TextEncoding::Auto => None,
+            TextEncoding::Utf8 => Some(encoding_rs::UTF_8),
+            TextEncoding::Gbk => Some(encoding_rs::GBK),
+            TextEncoding::Gb18030 => Some(encoding_rs::GB18030),
+            TextEncoding::Big5 => Some(encoding_rs::BIG5),
+            TextEncoding::ShiftJis => Some(encoding_rs::SHIFT_JIS),
+            // What browsers, and encoding_rs, mean by Latin-1.
+            TextEncoding::Latin1 => Some(encoding_rs::WINDOWS_1252),
+        }
+    }
+}
+
+/// Legacy encodings tried in order when a file is not UTF-8.  Latin-1 comes last since it
+/// accepts any bytes.
+const FALLBACK_ENCODINGS: [&encoding_rs::Encoding; 3] = [encoding_rs::GB18030, encoding_rs::BIG5, encoding_rs::SHIFT_JIS];
+
+/// The kind of file a conversion writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    #[default]
+    Pdf,
+    /// The extracted plain text.
+    Txt,
+    /// The plain text rendered into an image, or an image input re-encoded.
+    Png,
+    /// Headings, lists and tables of DOCX and HTML documents as Markdown; see [`crate::markdown`].
+    Markdown,
+}
+
+impl OutputFormat {
+    pub const ALL: [OutputFormat; 4] = [OutputFormat::Pdf, OutputFormat::Txt, OutputFormat::Png, OutputFormat::Markdown];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Pdf => "pdf",
+            OutputFormat::Txt => "txt",
+            OutputFormat::Png => "png",
+            OutputFormat::Markdown => "md",
+        }
+    }
+
+    /// Whether files of `file_type` can be written in this format.
+    pub fn applies_to(self, file_type: FileType) -> bool {
+        match self {
+            OutputFormat::Markdown => matches!(file_type, FileType::Docx | FileType::Html),
+            _ => true,
+        }
+    }
+}
+
+/// The PDF standard the output conforms to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Conformance {
+    /// Plain PDF with the smallest feature set.
+    #[default]
+    Minimal,
+    /// Archival PDF/A-2b with embedded fonts, XMP metadata and an sRGB output intent.
+    PdfA2b,
+}
+
+impl Conformance {
+    pub const ALL: [Conformance; 2] = [Conformance::Minimal, Conformance::PdfA2b];
+}
+
+/// Where images go on the page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImagePlacement {
+    /// At their size for the image resolution, in the flow of the text, shrunk only to fit.
+    #[default]
+    Inline,
+    /// Scaled up or down to fill the width or height of the page, centered.
+    Fit,
+    /// Scaled like [`ImagePlacement::Fit`], each on a page of its own.
+    FullPage,
+}
+
+impl ImagePlacement {
+    pub const ALL: [ImagePlacement; 3] = [ImagePlacement::Inline, ImagePlacement::Fit, ImagePlacement::FullPage];
+}
+
+/// What a batch does when one of its files fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorPolicy {
+    /// Convert the remaining files anyway.
+    #[default]
+    Continue,
+    /// Cancel the files that have not finished yet.
+    Stop,
+}
+
+/// Options that control how documents are rendered.
+///
+/// Missing fields in a preset from the configuration file take their default values.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConversionOptions {
+    pub output_format: OutputFormat,
+    pub page_size: PageSize,
+    /// Turn the page to landscape when a CSV, Excel or SQLite table is too wide for portrait.
+    pub auto_landscape: bool,
+    /// Base font size in points.
+    pub font_size: u8,
+    /// Columns between tab stops; tabs are expanded to spaces before rendering.
+    pub tab_width: u8,
+    pub text_encoding: TextEncoding,
+    pub margins: PageMargins,
+    pub page_numbers: bool,
+    pub text_align: TextAlign,
+    pub markdown_density: Density,
+    /// Lay out Markdown and Org files as slides: twice the font size, wide margins and a new
+    /// page for each level 1 and 2 heading.
+    pub presentation: bool,
+    /// Indent of the first line of each plain text and Markdown paragraph, in millimetres.
+    pub first_line_indent_mm: u8,
+    /// Extra space after each plain text and Markdown paragraph, in lines.
+    pub paragraph_spacing: f64,
+    /// Turn Markdown `--`, `---`, `...` and straight quotes into dashes, ellipses and curly quotes.
+    pub smart_punctuation: bool,
+    pub json_layout: JsonLayout,
+    pub tracked_changes: TrackedChanges,
+    pub image_compression: ImageCompression,
+    /// Resolution image pixels are printed at, so a 300 dpi scan keeps its physical size.
+    /// Images that would not fit the page at this resolution are shrunk to fit.
+    pub image_dpi: u16,
+    pub image_placement: ImagePlacement,
+    /// Images with more than this many million pixels are shrunk to that size as soon as they
+    /// are decoded, and decoded one at a time; 0 keeps every pixel.
+    pub image_max_megapixels: u32,
+    pub conformance: Conformance,
+    pub error_policy: ErrorPolicy,
+    /// Rows shown per SQLite table; the rest are summarized in a note.
+    pub sqlite_row_limit: u32,
+    /// Convert every worksheet of an Excel workbook to its own file instead of only the first.
+    pub excel_sheet_files: bool,
+    /// Convert every section of a Markdown, Org or DOCX document, from one top-level heading to
+    /// the next, to its own file instead of the whole document to one.
+    pub split_by_heading: bool,
+    /// Lay out CSV and Excel data as a framed table with every other row shaded.
+    pub table_stripes: bool,
+    /// Directory relative image paths in HTML files are resolved against; the HTML file's own
+    /// directory if unset.
+    pub html_base_dir: Option<PathBuf>,
+    /// Relative widths of the first columns of framed tables, by column index.  Columns it
+    /// leaves out, or gives 0, have weight 1.
+    pub column_weights: Vec<u32>,
+    /// Content beyond about this many pages is cut off; 0 means no limit.
+    pub max_pages: u32,
+    /// Seconds a file may take to convert before it is abandoned; 0 means no limit.
+    pub timeout_secs: u32,
+    /// Text lines longer than this many characters are laid out as several paragraphs of at most
+    /// this length, since genpdf can take minutes to wrap a single huge paragraph such as a
+    /// minified script.  0 never splits.
+    pub long_line_chars: u32,
+    /// Worksheets of Excel workbooks and tables of SQLite databases to convert, as a
+    /// [`PartRange`] such as `1-3,5`; empty for all of them.
+    pub part_range: String,
+    /// Turn URLs in plain text into clickable links.
+    pub detect_links: bool,
+    pub redaction: Redaction,
+    /// Color of Markdown, reStructuredText and DOCX headings.
+    pub heading_color: RgbColor,
+    /// Color of body paragraphs.
+    pub body_color: RgbColor,
+    /// Color of the whole page, behind everything else.  PDF output only.
+    pub page_background: RgbColor,
+    /// Append a page with the word, character, line, table and image counts and the size of the
+    /// input.  PDF output only.
+    pub stats_page: bool,
+    /// Number the lines of log files.
+    pub log_line_numbers: bool,
+    /// Header printed at the top of every page, in place of the page number.  PDF output only.
+    pub page_header: PageTemplate,
+    /// Footer printed at the bottom of every page.  PDF output only.
+    pub page_footer: PageTemplate,
+}
+
+impl Default for ConversionOptions {
+    fn default() -> Self {
+        Self {
+            output_format: OutputFormat::default(),
+            page_size: PageSize::default(),
+            auto_landscape: false,
+            font_size: 12,
+            tab_width: 4,
+            text_encoding: TextEncoding::default(),
+            margins: PageMargins::default(),
+            page_numbers: false,
+            text_align: TextAlign::default(),
+            markdown_density: Density::default(),
+            presentation: false,
+            first_line_indent_mm: 0,
+            paragraph_spacing: 0.0,
+            smart_punctuation: true,
+            json_layout: JsonLayout::default(),
+            tracked_changes: TrackedChanges::default(),
+            image_compression: ImageCompression::default(),
+            image_dpi: DEFAULT_IMAGE_DPI as u16,
+            image_placement: ImagePlacement::default(),
+            image_max_megapixels: 50,
+            conformance: Conformance::default(),
+            error_policy: ErrorPolicy::default(),
+            sqlite_row_limit: 200,
+            excel_sheet_files: false,
+            split_by_heading: false,
+            table_stripes: false,
+            html_base_dir: None,
+            column_weights: Vec::new(),
+            max_pages: 0,
+            timeout_secs: 0,
+            long_line_chars: 10_000,
+            part_range: String::new(),
+            detect_links: true,
+            redaction: Redaction::default(),
+            heading_color: RgbColor::BLACK,
+            body_color: RgbColor::BLACK,
+            page_background: RgbColor::WHITE,
+            stats_page: false,
+            log_line_numbers: false,
+            page_header: PageTemplate::default(),
+            page_footer: PageTemplate::default(),
+        }
+    }
+}
+
+impl ConversionOptions {
+    pub const FONT_SIZE_RANGE: std::ops::RangeInclusive<u8> = 8..=24;
+    pub const MARGIN_RANGE: std::ops::RangeInclusive<u8> = 0..=40;
+    /// Resolutions offered for images: screen, common scan and print resolutions.
+    pub const IMAGE_DPIS: [u16; 6] = [72, 96, 150, 200, 300, 600];
+    /// Paragraph spacings offered, in lines.
+    pub const PARAGRAPH_SPACINGS: [f64; 5] = [0.0, 0.5, 1.0, 1.5, 2.0];
+    pub const TAB_WIDTHS: [u8; 3] = [2, 4, 8];
+    /// The least contrast between text and page background considered legible; WCAG AA for
+    /// body text.
+    pub const MIN_CONTRAST: f64 = 4.5;
+
+    /// The lower contrast of the heading and body colors against the page background.
+    pub fn text_contrast(&self) -> f64 {
+        self.page_background.contrast(self.heading_color).min(self.page_background.contrast(self.body_color))
+    }
+
+    /// Column weights of a framed table with `columns` columns, from [`Self::column_weights`].
+    fn table_weights(&self, columns: usize) -> Vec<usize> {
+        (0..columns)
+            .map(|i| self.column_weights.get(i).copied().filter(|&w| w > 0).unwrap_or(1) as usize)
+            .collect()
+    }
+
+    /// These options with the font size and margins of [`Self::presentation`] mode.
+    fn for_slides(&self) -> ConversionOptions {
+        /// Margins of slides, unless the options ask for wider ones.
+        const SLIDE_MARGIN: u8 = 30;
+        let m = self.margins;
+        ConversionOptions {
+            font_size: self.font_size.saturating_mul(2),
+            margins: PageMargins {
+                top: m.top.max(SLIDE_MARGIN),
+                right: m.right.max(SLIDE_MARGIN),
+                bottom: m.bottom.max(SLIDE_MARGIN),
+                left: m.left.max(SLIDE_MARGIN),
+            },
+            ..self.clone()
+        }
+    }
+
+    /// Width and height of the printable area in millimeters.
+    fn content_size_mm(&self) -> (f64, f64) {
+        let (width, height) = self.page_size.dimensions_mm();
+        let m = self.margins;
+        let horizontal = m.left as f64 + m.right as f64;
+        let vertical = m.top as f64 + m.bottom as f64;
+        ((width - horizontal).max(1.0), (height - vertical).max(1.0))
+    }
+}
+
+/// Page margins in millimeters. Binding usually needs a wider inside (left) margin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PageMargins {
+    pub top: u8,
+    pub right: u8,
+    pub bottom: u8,
+    pub left: u8,
+}
+
+impl PageMargins {
+    pub const fn uniform(mm: u8) -> Self {
+        Self { top: mm, right: mm, bottom: mm, left: mm }
+    }
+}
+
+impl Default for PageMargins {
+    fn default() -> Self {
+        Self::uniform(10)
+    }
+}
+
+impl From<PageMargins> for genpdf::Margins {
+    fn from(m: PageMargins) -> Self {
+        genpdf::Margins::trbl(m.top, m.right, m.bottom, m.left)
+    }
+}
+
+/// Downscaling and JPEG re-encoding of embedded images to keep PDFs small.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageCompression {
+    pub enabled: bool,
+    /// JPEG quality, 1-100.
+    pub quality: u8,
+    /// Leave lossless sources (PNG, BMP) untouched, e.g. diagrams and screenshots.
+    pub keep_lossless: bool,
+}
+
+impl Default for ImageCompression {
+    fn default() -> Self {
+        Self { enabled: false, quality: 75, keep_lossless: true }
+    }
+}
+
+impl ImageCompression {
+    pub const QUALITY_RANGE: std::ops::RangeInclusive<u8> = 10..=100;
+
+    /// Whether an image read from `path` should be compressed.
+    fn applies_to(&self, path: &Path) -> bool {
+        let lossless = matches!(
+            path.extension().and_then(|s| s.to_str()).map(|s| s.to_lowercase()).as_deref(),
+            Some("png") | Some("bmp")
+        );
+        self.enabled && !(self.keep_lossless && lossless)
+    }
+}
+
+/// An sRGB color, written as `"#rrggbb"` in presets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct RgbColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl RgbColor {
+    pub const BLACK: RgbColor = RgbColor::new(0, 0, 0);
+    pub const WHITE: RgbColor = RgbColor::new(255, 255, 255);
+
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// Parses `#rrggbb` (the `#` is optional).
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.trim().trim_start_matches('#');
+        if hex.len() != 6 || !hex.is_ascii() {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        Some(Self::new(channel(0)?, channel(2)?, channel(4)?))
+    }
+
+    pub fn to_hex(self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+
+    /// The WCAG relative luminance, from 0 for black to 1 for white.
+    fn luminance(self) -> f64 {
+        let linear = |c: u8| {
+            let c = c as f64 / 255.0;
+            if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+        };
+        0.2126 * linear(self.r) + 0.7152 * linear(self.g) + 0.0722 * linear(self.b)
+    }
+
+    /// The WCAG contrast ratio between the two colors, from 1 (none) to 21 (black on white).
+    pub fn contrast(self, other: RgbColor) -> f64 {
+        let (light, dark) = (self.luminance().max(other.luminance()), self.luminance().min(other.luminance()));
+        (light + 0.05) / (dark + 0.05)
+    }
+}
+
+impl TryFrom<String> for RgbColor {
+    type Error = String;
+
+    fn try_from(hex: String) -> Result<Self, Self::Error> {
+        Self::from_hex(&hex).ok_or_else(|| format!("invalid color {:?}, expected #rrggbb", hex))
+    }
+}
+
+impl From<RgbColor> for String {
+    fn from(color: RgbColor) -> Self {
+        color.to_hex()
+    }
+}
+
+/// Text masked with `█` before rendering; see [`crate::redact`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Redaction {
+    pub emails: bool,
+    pub phone_numbers: bool,
+    /// User-defined regular expressions.
+    pub patterns: Vec<String>,
+}
+
+/// Resolution compressed images are resampled to.
+const COMPRESSED_IMAGE_DPI: f64 = 150.0;
+/// genpdf's resolution for images without an explicit DPI.
+const DEFAULT_IMAGE_DPI: f64 = 300.0;
+const MM_PER_INCH: f64 = 25.4;
+const MM_PER_POINT: f64 = MM_PER_INCH / 72.0;
+/// Columns are assumed to wrap rather than grow past this width when estimating table widths.
+const MAX_COLUMN_MM: f64 = 60.0;
+/// Cell padding, or the `" | "` separator in CSV and Excel output.
+const COLUMN_GAP_MM: f64 = 3.0;
+/// Rows measured when estimating a table's width; the rest are assumed to look alike.
+const TABLE_WIDTH_SAMPLE_ROWS: usize = 1000;
+
+pub fn prepare_font(font_data: Arc<Vec<u8>>) -> Result<Arc<FontData>> {
+    let font = FontData::new(font_data.as_ref().clone(), None)
+        .context("Failed to parse font data")?;
+    Ok(Arc::new(font))
+}
+
+/// The fonts available to a conversion; `convert` picks one per file type.
+#[derive(Debug, Clone)]
+pub struct FontSet {
+    /// Proportional font for prose, ideally CJK-capable.
+    pub text: Arc<FontData>,
+    /// Monospace font for code and structured data.
+    pub mono: Arc<FontData>,
+    /// Used for characters the primary font has no glyph for.
+    pub fallback: Option<Arc<FontData>>,
+}
+
+/// The bundled DejaVu fonts, so that tests lay out the same on every machine.
+#[cfg(test)]
+pub(crate) fn bundled_fonts() -> FontSet {
+    let font = |bytes: &[u8]| prepare_font(Arc::new(bytes.to_vec())).expect("bundled font is valid");
+    FontSet {
+        text: font(include_bytes!("../assets/DejaVuSans.ttf")),
+        mono: font(include_bytes!("../assets/DejaVuSansMono.ttf")),
+        fallback: None,
+    }
+}
+
+pub(crate) fn font_family(font: &FontData) -> genpdf::fonts::FontFamily<FontData> {
+    genpdf::fonts::FontFamily {
+        regular: font.clone(),
+        bold: font.clone(),
+        italic: font.clone(),
+        bold_italic: font.clone(),
+    }
+}
+
+fn color_style(color: RgbColor) -> style::Style {
+    match color {
+        RgbColor::BLACK => style::Style::new(),
+        RgbColor { r, g, b } => style::Style::new().with_color(style::Color::Rgb(r, g, b)),
+    }
+}
+
+/// Per-conversion state shared by the renderers.
+struct RenderContext<'a> {
+    options: &'a ConversionOptions,
+    styles: &'a StyleSheet,
+    /// Monospace family registered with the document, for code blocks inside prose.
+    mono: Option<genpdf::fonts::FontFamily<genpdf::fonts::Font>>,
+    /// Glyph lookup for the document's default font.
+    primary_glyphs: rusttype::Font<'static>,
+    fallback: Option<&'a FontData>,
+    /// The fallback family, registered with the document the first time it is needed so that
+    /// documents without uncovered characters don't embed it.
+    fallback_family: OnceCell<(genpdf::fonts::FontFamily<genpdf::fonts::Font>, rusttype::Font<'static>)>,
+    /// Characters that neither the primary nor the fallback font can display.
+    missing: RefCell<BTreeSet<char>>,
+    /// URLs of the links printed so far, in the order of their marker indices.
+    links: RefCell<Vec<String>>,
+    /// What has been printed so far, for the statistics page.
+    stats: RefCell<DocumentStats>,
+}
+
+fn has_glyph(font: &rusttype::Font<'static>, c: char) -> bool {
+    c.is_whitespace() || c.is_control() || font.glyph(c).id().0 != 0
+}
+
+impl RenderContext<'_> {
+    /// Splits text into runs so that characters missing from the primary font are printed with
+    /// the fallback font. Characters no font covers are recorded for a warning.
+    fn spans(&self, doc: &mut Blocks, text: &str) -> Vec<style::StyledString> {
+        let text = expand_tabs(text, self.options.tab_width);
+        let text = text.as_ref();
+        if text.chars().all(|c| has_glyph(&self.primary_glyphs, c)) {
+            return vec![style::StyledString::new(text, style::Style::new())];
+        }
+
+        let fallback = self.fallback.map(|font| {
+            self.fallback_family.get_or_init(|| {
+                log::debug!("Registering fallback font for uncovered characters");
+                let family = doc.add_font_family(font_family(font));
+                let glyphs = doc.font_cache().get_rt_font(family.regular).clone();
+                (family, glyphs)
+            })
+        });
+
+        let mut spans: Vec<style::StyledString> = Vec::new();
+        let mut run = String::new();
+        let mut run_fallback = false;
+        for c in text.chars() {
+            let use_fallback = if has_glyph(&self.primary_glyphs, c) {
+                false
+            } else {
+                match fallback {
+                    Some((_, glyphs)) if has_glyph(glyphs, c) => true,
+                    _ => {
+                        self.missing.borrow_mut().insert(c);
+                        false
+                    }
+                }
+            };
+            if use_fallback != run_fallback && !run.is_empty() {
+                spans.push(self.run_span(std::mem::take(&mut run), run_fallback));
+            }
+            run_fallback = use_fallback;
+            run.push(c);
+        }
+        if !run.is_empty() {
+            spans.push(self.run_span(run, run_fallback));
+        }
+        spans
+    }
+
+    fn run_span(&self, text: String, fallback: bool) -> style::StyledString {
+        match self.fallback_family.get() {
+            Some((family, _)) if fallback => style::StyledString::new(text, style::Style::new().with_font_family(*family)),
+            _ => style::StyledString::new(text, style::Style::new()),
+        }
+    }
+
+    /// Registers a link for [`links::annotate`] and returns its index, or `None` once the
+    /// document has [`links::MAX_LINKS`] links.
+    fn add_link(&self, url: &str) -> Option<usize> {
+        let mut links = self.links.borrow_mut();
+        (links.len() < links::MAX_LINKS).then(|| {
+            links.push(url.to_string());
+            links.len() - 1
+        })
+    }
+
+    /// A plain paragraph with glyph fallback applied.
+    fn paragraph(&self, doc: &mut Blocks, text: &str) -> elements::Paragraph {
+        self.count_line(text);
+        elements::Paragraph::from(self.spans(doc, text))
+    }
+
+    /// A table cell with glyph fallback applied and a paragraph per line of `text`, so that
+    /// multi-line fields wrap inside their cell; the row is counted by the caller.
+    fn cell(&self, doc: &mut Blocks, text: &str) -> elements::LinearLayout {
+        self.aligned_cell(doc, text, genpdf::Alignment::Left)
+    }
+
+    /// A [`Self::cell`] with its lines aligned to `alignment`.
+    fn aligned_cell(&self, doc: &mut Blocks, text: &str, alignment: genpdf::Alignment) -> elements::LinearLayout {
+        self.stats.borrow_mut().add_text(text);
+        let mut cell = elements::LinearLayout::vertical();
+        for line in text.split('\n') {
+            cell.push(elements::Paragraph::from(self.spans(doc, line.strip_suffix('\r').unwrap_or(line))).aligned(alignment));
+        }
+        cell
+    }
+
+    /// Counts a printed paragraph or line for the statistics page.
+    fn count_line(&self, text: &str) {
+        let mut stats = self.stats.borrow_mut();
+        stats.add_text(text);
+        stats.lines += 1;
+    }
+
+    /// The configured indent of a paragraph's first line.
+    fn first_line_indent(&self) -> genpdf::Mm {
+        genpdf::Mm::from(self.options.first_line_indent_mm as f64)
+    }
+
+    /// Scales a size designed for the default 12 pt body text to the configured font size.
+    fn scaled(&self, points: u8) -> u8 {
+        (points as f64 * self.options.font_size as f64 / 12.0).round().clamp(1.0, 255.0) as u8
+    }
+
+    /// The style sheet's `element` over the built-in look: `size` before scaling (`None` keeps
+    /// the document's font size), `color` and weight.
+    fn element_style(&self, element: &ElementStyle, size: Option<u8>, color: RgbColor, bold: bool) -> style::Style {
+        let mut style = color_style(element.color.unwrap_or(color));
+        if let Some(size) = element.size.or(size) {
+            style.set_font_size(self.scaled(size));
+        }
+        if element.weight.map_or(bold, |weight| weight == Weight::Bold) {
+            style.set_bold();
+        }
+        style
+    }
+
+    /// Style for body paragraphs. Black is genpdf's default and is left unset.
+    fn body_style(&self) -> style::Style {
+        self.element_style(&self.styles.body, None, self.options.body_color, false)
+    }
+
+    /// Bold heading text of a level 1, 2, 3... heading in the heading color.
+    fn heading_style(&self, level: usize) -> style::Style {
+        self.element_style(self.styles.heading(level), Some(heading_size(level)), self.options.heading_color, true)
+    }
+
+    /// Bold table header cells, at `points` before scaling or the document's font size.
+    fn table_header_style(&self, points: Option<u8>) -> style::Style {
+        self.element_style(&self.styles.table_header, points, RgbColor::BLACK, true)
+    }
+
+    /// Estimated width in millimeters of `rows` set at `points`, with every column as wide as
+    /// its widest cell.
+    fn table_width_mm<'r>(&self, rows: impl IntoIterator<Item = &'r [String]>, points: u8) -> f64 {
+        let scale = rusttype::Scale::uniform(points as f32);
+        let mut columns: Vec<f64> = Vec::new();
+        for row in rows.into_iter().take(TABLE_WIDTH_SAMPLE_ROWS) {
+            if columns.len() < row.len() {
+                columns.resize(row.len(), 0.0);
+            }
+            for (width, cell) in columns.iter_mut().zip(row) {
+                // Characters the primary font lacks come from the (usually CJK) fallback: one em.
+                let line_points = |line: &str| -> f32 {
+                    line.chars()
+                        .map(|c| if has_glyph(&self.primary_glyphs, c) {
+                            self.primary_glyphs.glyph(c).scaled(scale).h_metrics().advance_width
+                        } else {
+                            points as f32
+                        })
+                        .sum()
+                };
+                let cell_points = cell.lines().map(line_points).fold(0.0, f32::max);
+                *width = width.max(cell_points as f64 * MM_PER_POINT);
+            }
+        }
+        columns.iter().map(|width| width.min(MAX_COLUMN_MM) + COLUMN_GAP_MM).sum()
+    }
+
+    fn code_style(&self) -> style::Style {
+        let style = self.element_style(&self.styles.code, Some(10), RgbColor::BLACK, false);
+        match self.mono {
+            Some(family) => style.with_font_family(family),
+            None => style,
+        }
+    }
+}
+
+/// An input file read and parsed into memory, ready to be laid out.
+///
+/// Loading happens on the conversion worker; see [`crate::pipeline`] for the threading model.
+pub struct LoadedInput {
+    pub(crate) path: PathBuf,
+    pub(crate) file_type: FileType,
+    pub(crate) content: LoadedContent,
+    /// The Mermaid diagrams of a Markdown or Org document that could be drawn, each with its
+    /// source.  Drawing one takes seconds, so it is done here rather than on the render thread.
+    pub(crate) diagrams: Vec<(String, elements::Image)>,
+}
+
+#[derive(Clone)]
+pub(crate) enum LoadedContent {
+    Text(String),
+    /// CSV records or the first Excel worksheet, header first where there is one.
+    Table { header: Option<Vec<String>>, rows: Vec<Vec<String>> },
+    Image { image: Result<elements::Image, String>, compressed: bool },
+    /// DOCX paragraphs in document order.
+    Docx(Vec<DocxParagraph>),
+    Email(LoadedEmail),
+    Database(Vec<DatabaseTable>),
+    /// An HTML page, and for each of its `<img>` tags the image if it is a local file.
+    Html { html: String, images: Vec<Option<HtmlImage>> },
+    Empty,
+}
+
+#[derive(Clone)]
+pub(crate) struct HtmlImage {
+    pub(crate) path: PathBuf,
+    pub(crate) image: Result<elements::Image, String>,
+    /// The image should be JPEG-encoded after rendering.
+    pub(crate) compressed: bool,
+}
+
+/// One table of a SQLite database, cut off at the configured row limit.
+#[derive(Clone)]
+pub(crate) struct DatabaseTable {
+    pub(crate) name: String,
+    /// Column names and declared types.
+    pub(crate) columns: Vec<(String, String)>,
+    pub(crate) rows: Vec<Vec<String>>,
+    /// Row count of the whole table, of which `rows` holds the first few.
+    pub(crate) total_rows: u64,
+}
+
+#[derive(Clone)]
+pub(crate) struct DocxParagraph {
+    /// Outline level for headings, starting at 1; `None` for body text.
+    pub(crate) heading: Option<usize>,
+    /// Set for items of bulleted and numbered lists.
+    pub(crate) list: Option<DocxListItem>,
+    pub(crate) text: String,
+    /// Byte ranges of `text` that are hyperlinks, in order, with the address each opens.
+    pub(crate) links: Vec<(std::ops::Range<usize>, String)>,
+}
+
+impl DocxParagraph {
+    /// The text in consecutive pieces, each with the address it links to, if any.
+    pub(crate) fn segments(&self) -> Vec<(&str, Option<&str>)> {
+        let mut segments = Vec::new();
+        let mut start = 0;
+        for (range, url) in &self.links {
+            if range.start > start {
+                segments.push((&self.text[start..range.start], None));
+            }
+            segments.push((&self.text[range.clone()], Some(url.as_str())));
+            start = range.end;
+        }
+        if start < self.text.len() || segments.is_empty() {
+            segments.push((&self.text[start..], None));
+        }
+        segments
+    }
+
+    /// The paragraph with its text replaced by `segments`, as returned by [`Self::segments`].
+    fn with_segments(self, segments: Vec<(String, Option<String>)>) -> Self {
+        let mut text = String::new();
+        let mut links = Vec::new();
+        for (segment, url) in segments {
+            let start = text.len();
+            text.push_str(&segment);
+            if let Some(url) = url {
+                links.push((start..text.len(), url));
+            }
+        }
+        DocxParagraph { text, links, ..self }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct DocxListItem {
+    /// Nesting depth, starting at 0.
+    pub(crate) level: usize,
+    pub(crate) ordered: bool,
+}
+
+/// The parts of an `.eml` message that end up in the PDF.
+#[derive(Clone)]
+pub(crate) struct LoadedEmail {
+    /// From, To, Cc, Subject and Date, in that order, for those the message has.
+    pub(crate) headers: Vec<(&'static str, String)>,
+    pub(crate) body: String,
+    /// Whether `body` is HTML rather than plain text.
+    pub(crate) html: bool,
+    pub(crate) attachments: Vec<String>,
+}
+
+/// What the render thread reports back for post-processing on the worker.
+pub struct RenderedDocument {
+    /// The PDF contains raw images that should be JPEG-encoded.
+    pub jpeg_images: bool,
+    /// URLs of the link markers to turn into annotations.
+    pub links: Vec<String>,
+    /// The PDF contains table cell or page markers for [`shading::fill`].
+    pub shaded: bool,
+}
+
+/// Converts `input` to `output` in the configured [`OutputFormat`].
+pub fn convert(input: &Path, output: &Path, fonts: &FontSet, options: &ConversionOptions) -> Result<(), ConversionError> {
+    log::info!("Starting conversion for: {:?} ({:?})", input, options.output_format);
+    let file_type = FileType::from_path(input);
+    let loading = |e| ConversionError::while_loading(e, file_type);
+    if !options.output_format.applies_to(file_type) {
+        return Err(ConversionError::Unsupported(format!("{:?} files cannot be written as {:?}", file_type, options.output_format)));
+    }
+    PartRange::parse(&options.part_range).map_err(ConversionError::InvalidOption)?;
+    if options.output_format == OutputFormat::Png && matches!(file_type, FileType::Image) {
+        // Decoding is what fails here, so a failure counts as unreadable input.
+        catch_stage("re-encoding", input, || export::reencode_image(input, output)).map_err(loading)?;
+    } else {
+        let loaded = catch_stage("loading", input, || load_input(input, options)).map_err(loading)?;
+        write_output(loaded, output, fonts, options)?;
+    }
+    log::info!("Conversion complete for {:?}", input);
+    Ok(())
+}
+
+/// Converts every worksheet of the Excel or OpenDocument workbook `input` to its own file,
+/// `<output_dir>/<stem>_<sheet>.<ext>`, and returns the files written.
+///
+/// All sheets are attempted even if some fail; the error lists every failed sheet.
+pub fn convert_sheets(input: &Path, output_dir: &Path, fonts: &FontSet, options: &ConversionOptions) -> Result<Vec<PathBuf>, ConversionError> {
+    log::info!("Starting per-sheet conversion for: {:?} ({:?})", input, options.output_format);
+    let file_type = FileType::from_path(input);
+    let loading = |e| ConversionError::while_loading(e, file_type);
+    if !options.output_format.applies_to(file_type) {
+        return Err(ConversionError::Unsupported(format!("{:?} files cannot be written as {:?}", file_type, options.output_format)));
+    }
+    let range = PartRange::parse(&options.part_range).map_err(ConversionError::InvalidOption)?;
+    if is_encrypted_office_file(input) {
+        return Err(ConversionError::Encrypted(format!("{} is password protected", input.display())));
+    }
+    let sheets = catch_stage("loading", input, || load_excel_sheets(input)).map_err(loading)?;
+    if sheets.is_empty() {
+        return Err(ConversionError::EmptyInput(format!("{} has no worksheets", input.display())));
+    }
+    let count = sheets.len();
+    let sheets = range.select(sheets);
+    if sheets.is_empty() {
+        return Err(ConversionError::EmptyInput(format!(
+            "{} has {} worksheets, none of them in the range {:?}", input.display(), count, options.part_range,
+        )));
+    }
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    let total = sheets.len();
+    let mut outputs = Vec::new();
+    let mut failed = Vec::new();
+
+    for (name, rows) in sheets {
+        let output = output_dir.join(format!("{}_{}.{}", stem, file_name_safe(&name), options.output_format.extension()));
+        let content = if rows.is_empty() { LoadedContent::Empty } else { LoadedContent::Table { header: None, rows } };
+        let result = redact::Redactor::new(&options.redaction)
+            .map(|redactor| match redactor {
+                Some(redactor) => redact_content(content, &redactor),
+                None => content,
+            })
+            .map_err(loading)
+            .and_then(|content| {
+                let loaded = LoadedInput { path: input.to_path_buf(), file_type, content, diagrams: Vec::new() };
+                write_output(loaded, &output, fonts, options)
+            });
+        match result {
+            Ok(()) => outputs.push(output),
+            Err(e) => {
+                log::error!("Conversion failed for sheet {:?} of {:?}: {}", name, input, e);
+                failed.push((name, e));
+            }
+        }
+    }
+
+    if failed.is_empty() {
+        log::info!("Conversion complete for {:?} ({} sheets)", input, total);
+        Ok(outputs)
+    } else {
+        Err(ConversionError::Entries { failed, total })
+    }
+}
+
+/// The title of the document for `{title}` in page headers and footers: the first top-level
+/// heading of Markdown, Org and DOCX documents and the subject of emails.
+fn document_title(content: &LoadedContent, file_type: FileType) -> Option<String> {
+    match content {
+        LoadedContent::Text(text) if file_type == FileType::Markdown => markdown_sections(text).into_iter().find_map(|(title, _)| title),
+        LoadedContent::Text(text) if file_type == FileType::Org => {
+            markdown_sections(&org::to_markdown(text)).into_iter().find_map(|(title, _)| title)
+        }
+        LoadedContent::Docx(paragraphs) => paragraphs.iter().find(|p| p.heading == Some(1)).map(|p| p.text.trim().to_string()),
+        LoadedContent::Email(email) => email.headers.iter().find(|(name, _)| *name == "Subject").map(|(_, subject)| subject.clone()),
+        _ => None,
+    }
+    .filter(|title| !title.is_empty())
+}
+
+/// Converts every section of the Markdown, Org or DOCX document `input`, from one top-level
+/// heading to the next, to its own file, `<output_dir>/<stem>_<heading>.<ext>`, and returns the
+/// files written.  Text before the first heading goes to `<stem>.<ext>`.
+///
+/// All sections are attempted even if some fail; the error lists every failed section.
+pub fn convert_sections(input: &Path, output_dir: &Path, fonts: &FontSet, options: &ConversionOptions) -> Result<Vec<PathBuf>, ConversionError> {
+    log::info!("Starting per-section conversion for: {:?} ({:?})", input, options.output_format);
+    let file_type = FileType::from_path(input);
+    let loading = |e| ConversionError::while_loading(e, file_type);
+    if !options.output_format.applies_to(file_type) {
+        return Err(ConversionError::Unsupported(format!("{:?} files cannot be written as {:?}", file_type, options.output_format)));
+    }
+    let loaded = catch_stage("loading", input, || load_input(input, options)).map_err(loading)?;
+    let sections = split_sections(loaded);
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    let total = sections.len();
+    let mut names = HashSet::new();
+    let mut outputs = Vec::new();
+    let mut failed = Vec::new();
+
+    for (title, section) in sections {
+        let name = match &title {
+            Some(title) => format!("{}_{}", stem, section_file_name(title)),
+            None => stem.to_string(),
+        };
+        // Sections with the same heading are numbered from the second on.
+        let mut unique = name.clone();
+        for n in 2.. {
+            if names.insert(unique.clone()) {
+                break;
+            }
+            unique = format!("{}_{}", name, n);
+        }
+        let output = output_dir.join(format!("{}.{}", unique, options.output_format.extension()));
+        match write_output(section, &output, fonts, options) {
+            Ok(()) => outputs.push(output),
+            Err(e) => {
+                let label = title.unwrap_or_else(|| stem.to_string());
+                log::error!("Conversion failed for section {:?} of {:?}: {}", label, input, e);
+                failed.push((label, e));
+            }
+        }
+    }
+
+    if failed.is_empty() {
+        log::info!("Conversion complete for {:?} ({} sections)", input, total);
+        Ok(outputs)
+    } else {
+        Err(ConversionError::Entries { failed, total })
+    }
+}
+
+/// `loaded` split before each top-level heading, with the heading of each part; the part before
+/// the first heading, if there is anything in it, has none.  Other content stays whole.
+fn split_sections(loaded: LoadedInput) -> Vec<(Option<String>, LoadedInput)> {
+    let LoadedInput { path, file_type, content, diagrams } = loaded;
+    let part = |content| LoadedInput { path: path.clone(), file_type, content, diagrams: Vec::new() };
+    match content {
+        LoadedContent::Text(text) if matches!(file_type, FileType::Markdown | FileType::Org) => {
+            let markdown = if file_type == FileType::Org { org::to_markdown(&text) } else { text };
+            markdown_sections(&markdown)
+                .into_iter()
+                .map(|(title, section)| {
+                    let diagrams = diagrams.iter().filter(|(source, _)| section.contains(source.as_str())).cloned().collect();
+                    (title, LoadedInput { path: path.clone(), file_type: FileType::Markdown, content: LoadedContent::Text(section), diagrams })
+                })
+                .collect()
+        }
+        LoadedContent::Docx(paragraphs) => {
+            let mut sections: Vec<(Option<String>, Vec<DocxParagraph>)> = Vec::new();
+            for paragraph in paragraphs {
+                if paragraph.heading == Some(1) {
+                    sections.push((Some(paragraph.text.trim().to_string()), vec![paragraph]));
+                } else if let Some((_, section)) = sections.last_mut() {
+                    section.push(paragraph);
+                } else if !paragraph.text.trim().is_empty() {
+                    sections.push((None, vec![paragraph]));
+                }
+            }
+            sections.into_iter().map(|(title, section)| (title, part(LoadedContent::Docx(section)))).collect()
+        }
+        content => vec![(None, part(content))],
+    }
+}
+
+/// The Markdown `content` split before each level 1 heading, with the text of each heading.
+fn markdown_sections(content: &str) -> Vec<(Option<String>, String)> {
+    let mut headings: Vec<(usize, String)> = Vec::new();
+    let mut open: Option<(usize, String)> = None;
+    for (event, range) in Parser::new_ext(content, Options::ENABLE_FOOTNOTES | Options::ENABLE_TABLES).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading { level: HeadingLevel::H1, .. }) => open = Some((range.start, String::new())),
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, title)) = &mut open {
+                    title.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::Heading(HeadingLevel::H1)) => headings.extend(open.take()),
+            _ => {}
+        }
+    }
+    let mut sections = Vec::new();
+    let preamble = &content[..headings.first().map_or(content.len(), |(start, _)| *start)];
+    if !preamble.trim().is_empty() || headings.is_empty() {
+        sections.push((None, preamble.to_string()));
+    }
+    for (i, (start, title)) in headings.iter().enumerate() {
+        let end = headings.get(i + 1).map_or(content.len(), |(next, _)| *next);
+        sections.push((Some(title.trim().to_string()), content[*start..end].to_string()));
+    }
+    sections
+}
+
+/// A heading made fit for a file name: Windows-safe, on one line, without the trailing dots and
+/// spaces Windows drops, and cut to a sensible length.
+fn section_file_name(title: &str) -> String {
+    const MAX_CHARS: usize = 60;
+    let name: String = file_name_safe(&title.split_whitespace().collect::<Vec<_>>().join(" ")).chars().take(MAX_CHARS).collect();
+    let name = name.trim_end_matches(['.', ' ']);
+    if name.is_empty() { "section".to_string() } else { name.to_string() }
+}
+
+/// `name` with the characters Windows does not allow in file names replaced by `_`.
+fn file_name_safe(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_control() || matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*') { '_' } else { c })
+        .collect()
+}
+
+/// Writes loaded content to `output` in the configured [`OutputFormat`].
+fn write_output(loaded: LoadedInput, output: &Path, fonts: &FontSet, options: &ConversionOptions) -> Result<(), ConversionError> {
+    let input = loaded.path.clone();
+    let input = input.as_path();
+    if options.output_format != OutputFormat::Pdf {
+        return catch_stage("exporting", input, || {
+            if options.output_format == OutputFormat::Markdown {
+                let markdown = markdown::document(&loaded)?;
+                return fs::write(output, markdown).context("Failed to write Markdown file");
+            }
+            let text = export::plain_text(&loaded)?;
+            if options.output_format == OutputFormat::Txt {
+                fs::write(output, text).context("Failed to write text file")
+            } else {
+                export::render_text_png(&text, &fonts.text, options.tab_width, output)
+            }
+        })
+        .map_err(ConversionError::while_writing);
+    }
+
+    if pipeline::cancelled() {
+        return Err(ConversionError::Cancelled);
+    }
+    let rendered = pipeline::render(loaded, output, fonts, options).map_err(ConversionError::while_writing)?;
+    if pipeline::cancelled() {
+        return Err(ConversionError::Cancelled);
+    }
+
+    catch_stage("post-processing", input, || {
+        if !rendered.links.is_empty() {
+            links::annotate(output, &rendered.links)?;
+        }
+        if rendered.shaded {
+            shading::fill(output, options.page_background)?;
+        }
+        if rendered.jpeg_images {
+            jpeg_encode_pdf_images(output, options.image_compression.quality)?;
+        }
+        if options.conformance == Conformance::PdfA2b {
+            pdfa::finalize_pdfa2b(output)?;
+        }
+        Ok(())
+    })
+    .map_err(ConversionError::while_writing)
+}
+
+/// Converts `input` to PDF and appends its pages to the existing PDF `target`.
+///
+/// The target is checked before anything is converted; the document is rendered to a temporary
+/// file first and merged in afterwards.
+pub fn convert_appending(input: &Path, target: &Path, fonts: &FontSet, options: &ConversionOptions) -> Result<(), ConversionError> {
+    merge::open_target(target).map_err(ConversionError::while_writing)?;
+    if matches!(FileType::from_path(input), FileType::Pdf) {
+        // Nothing to render: the pages are copied over as they are.
+        merge::open_target(input).map_err(|e| ConversionError::while_loading(e, FileType::Pdf))?;
+        return catch_stage("appending", input, || merge::append_pdf(target, input))
+            .map(|_| ())
+            .map_err(ConversionError::while_writing);
+    }
+
+    let name = input.file_name().unwrap_or_default().to_string_lossy();
+    let temp = scratch::file(&format!("{}.pdf", name)).map_err(ConversionError::while_writing)?;
+    let options = ConversionOptions { output_format: OutputFormat::Pdf, ..options.clone() };
+    convert(input, &temp, fonts, &options)?;
+    catch_stage("appending", input, || merge::append_pdf(target, &temp))
+        .map(|_| ())
+        .map_err(ConversionError::while_writing)
+}
+
+/// Converts `inputs` to PDF and joins them, in the given order, into the single PDF `output`.
+///
+/// Files that fail are left out and listed in the error; nothing is written if all of them fail.
+pub fn convert_merged(inputs: &[PathBuf], output: &Path, fonts: &FontSet, options: &ConversionOptions) -> Result<(), ConversionError> {
+    log::info!("Merging {} files into {:?}", inputs.len(), output);
+    // Built beside the output and moved there at the end, so a failed merge leaves no half
+    // document under the chosen name.
+    let partial = output.with_extension("pdf.part");
+    let options = ConversionOptions { output_format: OutputFormat::Pdf, ..options.clone() };
+    let mut failed = Vec::new();
+    let mut started = false;
+    for input in inputs {
+        let result = if started {
+            convert_appending(input, &partial, fonts, &options)
+        } else if matches!(FileType::from_path(input), FileType::Pdf) {
+            merge::open_target(input)
+                .map_err(|e| ConversionError::while_loading(e, FileType::Pdf))
+                .and_then(|_| {
+                    fs::copy(input, &partial)
+                        .map(|_| ())
+                        .map_err(|e| ConversionError::Io(format!("Failed to copy {}: {}", input.display(), e)))
+                })
+        } else {
+            convert(input, &partial, fonts, &options)
+        };
+        match result {
+            Ok(()) => started = true,
+            Err(e) => {
+                log::warn!("Leaving {:?} out of the merged PDF: {}", input, e);
+                failed.push((input.file_name().unwrap_or_default().to_string_lossy().to_string(), e));
+            }
+        }
+    }
+    if started {
+        fs::rename(&partial, output)
+            .map_err(|e| ConversionError::Io(format!("Failed to write {}: {}", output.display(), e)))?;
+    } else if partial.exists()
+        && let Err(e) = fs::remove_file(&partial)
+    {
+        log::warn!("Failed to remove partial PDF {:?}: {}", partial, e);
+    }
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(ConversionError::Entries { failed, total: inputs.len() })
+    }
+}
+
+/// Runs one stage of converting `input`, turning a panic inside it (usually genpdf or a parser
+/// choking on unusual input) into a [`ConversionError::RenderPanic`] naming the stage.
+pub(crate) fn catch_stage<T>(stage: &str, input: &Path, job: impl FnOnce() -> Result<T>) -> Result<T> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(job)).unwrap_or_else(|payload| {
+        let reason = payload.downcast_ref::<&str>().copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown cause");
+        log::error!("Panic while {} {:?}: {}", stage, input, reason);
+        Err(ConversionError::RenderPanic { stage: stage.to_string(), reason: reason.to_string() }.into())
+    })
+}
+
+/// Reads and parses `input`. This is the I/O- and parse-heavy half of a conversion.
+pub fn load_input(input: &Path, options: &ConversionOptions) -> Result<LoadedInput> {
+    let file_type = FileType::from_path(input);
+    let content = match file_type {
+        FileType::Zip => {
+            return Err(ConversionError::Unsupported("ZIP archives are converted per entry, use convert_zip".to_string()).into());
+        }
+        FileType::Pdf => {
+            return Err(ConversionError::Unsupported(
+                "the file is already a PDF; choose a PDF to append to, and its pages are added unchanged".to_string(),
+            ).into());
+        }
+        FileType::Unknown => {
+            let msg = "Unknown file type";
+            log::error!("{}", msg);
+            return Err(ConversionError::Unsupported(msg.to_string()).into());
+        }
+        FileType::Docx | FileType::Excel if is_encrypted_office_file(input) => {
+            return Err(ConversionError::Encrypted(format!("{} is password protected", input.display())).into());
+        }
+        FileType::Csv | FileType::Tsv | FileType::Excel | FileType::Ods | FileType::Image
+            if fs::metadata(input).map(|m| m.len() == 0).unwrap_or(false) => LoadedContent::Empty,
+        FileType::Csv => load_csv(input, options.text_encoding, b',')?,
+        FileType::Tsv => load_csv(input, options.text_encoding, b'\t')?,
+        FileType::Excel | FileType::Ods => load_excel(input, options)?,
+        FileType::Image => load_image(input, options),
+        FileType::Docx => LoadedContent::Docx(read_docx(input, options.tracked_changes)?),
+        FileType::Eml => LoadedContent::Email(load_email(input)?),
+        FileType::Sqlite => LoadedContent::Database(load_sqlite(input, options)?),
+        FileType::Dot => load_dot(input, options)?,
+        FileType::Html => load_html(input, options)?,
+        _ => LoadedContent::Text(read_text(input, options.text_encoding)?),
+    };
+    let content = match content {
+        LoadedContent::Text(text) | LoadedContent::Html { html: text, .. } if text.trim().is_empty() => LoadedContent::Empty,
+        LoadedContent::Text(text) => LoadedContent::Text(normalize_line_endings(text, input)),
+        LoadedContent::Docx(paragraphs) if paragraphs.iter().all(|p| p.text.trim().is_empty()) => LoadedContent::Empty,
+        content => content,
+    };
+    let content = match redact::Redactor::new(&options.redaction)? {
+        Some(redactor) => redact_content(content, &redactor),
+        None => content,
+    };
+    let diagrams = match &content {
+        LoadedContent::Text(text) if options.output_format == OutputFormat::Pdf && matches!(file_type, FileType::Markdown | FileType::Org) => {
+            let markdown = if file_type == FileType::Org { Cow::Owned(org::to_markdown(text)) } else { Cow::Borrowed(text) };
+            load_diagrams(&markdown, &layout_options(options, file_type))
+        }
+        _ => Vec::new(),
+    };
+    log::info!("File type identified as: {:?}. Content loaded.", file_type);
+    Ok(LoadedInput { path: input.to_path_buf(), file_type, content, diagrams })
+}
+
+/// Whether `path` is a password-protected Word or Excel document.  Encryption wraps the usual
+/// ZIP package in an OLE compound file, the container legacy `.doc`/`.xls` files use.
+fn is_encrypted_office_file(path: &Path) -> bool {
+    const OLE_MAGIC: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+    let package = matches!(
+        path.extension().and_then(|s| s.to_str()).map(|s| s.to_lowercase()).as_deref(),
+        Some("docx") | Some("xlsx")
+    );
+    let mut magic = [0u8; 8];
+    package && fs::File::open(path).and_then(|mut f| f.read_exact(&mut magic)).is_ok() && magic == OLE_MAGIC
+}
+
+/// Masks sensitive text everywhere it can appear in `content`.
+fn redact_content(content: LoadedContent, redactor: &redact::Redactor) -> LoadedContent {
+    let text = |s: String| match redactor.redact(&s) {
+        Cow::Borrowed(_) => s,
+        Cow::Owned(redacted) => redacted,
+    };
+    let rows = |rows: Vec<Vec<String>>| -> Vec<Vec<String>> {
+        rows.into_iter().map(|row| row.into_iter().map(text).collect()).collect()
+    };
+    match content {
+        LoadedContent::Text(content) => LoadedContent::Text(text(content)),
+        LoadedContent::Html { html, images } => LoadedContent::Html { html: text(html), images },
+        // Piece by piece, so the links keep covering their own text.
+        LoadedContent::Docx(paragraphs) => LoadedContent::Docx(
+            paragraphs.into_iter()
+                .map(|p| {
+                    let segments = p.segments().into_iter().map(|(s, url)| (text(s.to_string()), url.map(str::to_string))).collect();
+                    p.with_segments(segments)
+                })
+                .collect(),
+        ),
+        LoadedContent::Table { header, rows: body } => LoadedContent::Table {
+            header: header.map(|h| h.into_iter().map(text).collect()),
+            rows: rows(body),
+        },
+        LoadedContent::Email(email) => LoadedContent::Email(LoadedEmail {
+            headers: email.headers.into_iter().map(|(label, value)| (label, text(value))).collect(),
+            body: text(email.body),
+            attachments: email.attachments.into_iter().map(text).collect(),
+            ..email
+        }),
+        LoadedContent::Database(tables) => LoadedContent::Database(
+            tables.into_iter().map(|table| DatabaseTable { rows: rows(table.rows), ..table }).collect(),
+        ),
+        content @ (LoadedContent::Image { .. } | LoadedContent::Empty) => content,
+    }
+}
+
+/// Reads a text file in `encoding`; see [`TextEncoding::Auto`] for the detection.
+///
+/// A UTF-8, UTF-16LE or UTF-16BE byte order mark is never part of the text, and it decides
+/// the encoding even when one is forced: a file that has one was written in that encoding.
+pub(crate) fn read_text(path: &Path, encoding: TextEncoding) -> Result<String> {
+    let bytes = fs::read(path).context("Failed to read file")?;
+    if let Some((found, bom)) = encoding_rs::Encoding::for_bom(&bytes) {
+        if encoding.encoding().is_some_and(|forced| forced != found) {
+            log::info!("{:?} starts with a {} byte order mark, ignoring the {:?} encoding setting", path, found.name(), encoding);
+        }
+        let (text, malformed) = found.decode_without_bom_handling(&bytes[bom..]);
+        if malformed {
+            log::warn!("{:?} has bytes that are not valid {}, they are replaced with U+FFFD", path, found.name());
+        }
+        return Ok(text.into_owned());
+    }
+    if let Some(encoding) = encoding.encoding() {
+        let (text, malformed) = encoding.decode_without_bom_handling(&bytes);
+        if malformed {
+            log::warn!("{:?} has bytes that are not valid {}, they are replaced with U+FFFD", path, encoding.name());
+        }
+        return Ok(text.into_owned());
+    }
+    let bytes = match String::from_utf8(bytes) {
+        Ok(text) => return Ok(text),
+        Err(e) => e.into_bytes(),
+    };
+    for encoding in FALLBACK_ENCODINGS {
+        if let Some(text) = encoding.decode_without_bom_handling_and_without_replacement(&bytes) {
+            log::info!("{:?} is not UTF-8, reading it as {}", path, encoding.name());
+            return Ok(text.into_owned());
+        }
+    }
+    log::info!("{:?} is not UTF-8 or a known CJK encoding, reading it as Latin-1", path);
+    Ok(encoding_rs::WINDOWS_1252.decode_without_bom_handling(&bytes).0.into_owned())
+}
+
+/// Converts `\r\n` and lone `\r` line endings to `\n`.
+///
+/// `str::lines` only understands `\n` and `\r\n`, so classic Mac files would otherwise become a
+/// single paragraph.
+fn normalize_line_endings(text: String, path: &Path) -> String {
+    if !text.contains('\r') {
+        return text;
+    }
+    let crlf = text.matches("\r\n").count();
+    let cr = text.matches('\r').count() - crlf;
+    let lf = text.matches('\n').count() - crlf;
+    let styles = [crlf, cr, lf].iter().filter(|&&n| n > 0).count();
+    if styles > 1 {
+        log::debug!("Mixed line endings in {:?} (CRLF: {}, CR: {}, LF: {}), normalizing to LF", path, crlf, cr, lf);
+    }
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Lays out a loaded input and writes the PDF. Must run on the render thread.
+pub(crate) fn render_document(
+    input: LoadedInput,
+    output: &Path,
+    fonts: &FontSet,
+    options: &ConversionOptions,
+) -> Result<RenderedDocument> {
+    if !options.page_header.uses_page_count() && !options.page_footer.uses_page_count() {
+        return render_pages(input, output, fonts, options, None);
+    }
+    // The page count is only known once the document has been laid out, so it is laid out
+    // twice: once to count the pages, and again with the count in the headers and footers.
+    let draft = LoadedInput {
+        path: input.path.clone(),
+        file_type: input.file_type,
+        content: input.content.clone(),
+        diagrams: input.diagrams.clone(),
+    };
+    render_pages(draft, output, fonts, options, None)?;
+    let pages = lopdf::Document::load(output).context("Failed to count the pages of the draft PDF")?.get_pages().len();
+    log::debug!("{:?} has {} pages, laying it out again with the page count", input.path, pages);
+    render_pages(input, output, fonts, options, Some(pages))
+}
+
+/// `options` as a document of `file_type` is laid out with: presentation mode only applies to
+/// Markdown and Org files.
+fn layout_options(options: &ConversionOptions, file_type: FileType) -> Cow<'_, ConversionOptions> {
+    if options.presentation && matches!(file_type, FileType::Markdown | FileType::Org) {
+        Cow::Owned(options.for_slides())
+    } else {
+        Cow::Borrowed(options)
+    }
+}
+
+/// Lays out `input` and writes it to `output`; `pages` is the page count for the headers and
+/// footers, if known.
+fn render_pages(
+    input: LoadedInput,
+    output: &Path,
+    fonts: &FontSet,
+    options: &ConversionOptions,
+    pages: Option<usize>,
+) -> Result<RenderedDocument> {
+    let (doc, rendered) = lay_out(input, fonts, options, pages)?;
+    log::info!("Rendering PDF to file {:?}", output);
+    doc.into_document().render_to_file(output).context("Failed to render PDF")?;
+    Ok(rendered)
+}
+
+/// Sets up the document for `input` and lays out its content, without rendering it yet.
+fn lay_out(
+    input: LoadedInput,
+    fonts: &FontSet,
+    options: &ConversionOptions,
+    pages: Option<usize>,
+) -> Result<(Blocks, RenderedDocument)> {
+    let LoadedInput { path, file_type, mut content, diagrams } = input;
+    let options = layout_options(options, file_type);
+    let options = &*options;
+    let truncated = options.max_pages > 0 && truncate_to_pages(&mut content, options);
+    if truncated {
+        log::warn!("{:?} is longer than about {} pages, cutting it off", path, options.max_pages);
+    }
+    let primary = if file_type.prefers_monospace() { &fonts.mono } else { &fonts.text };
+
+    log::debug!("Creating PDF document structure");
+    let mut doc = genpdf::Document::new(font_family(primary));
+    let primary_glyphs = {
+        let cache = doc.font_cache();
+        cache.get_rt_font(cache.default_font_family().regular).clone()
+    };
+    let mut ctx = RenderContext {
+        options,
+        styles: crate::styles::current(),
+        mono: None,
+        primary_glyphs,
+        fallback: fonts.fallback.as_deref(),
+        fallback_family: OnceCell::new(),
+        missing: RefCell::new(BTreeSet::new()),
+        links: RefCell::new(Vec::new()),
+        stats: RefCell::new(DocumentStats::default()),
+    };
+    if matches!(file_type, FileType::Markdown | FileType::Rst | FileType::Org | FileType::Subtitle) {
+        ctx.mono = Some(doc.add_font_family(font_family(&fonts.mono)));
+    }
+    doc.set_title("Converted Document");
+    match options.conformance {
+        Conformance::Minimal => doc.set_minimal_conformance(),
+        Conformance::PdfA2b => doc.set_conformance(printpdf::PdfConformance::A2B_2011_PDF_1_7),
+    }
+    doc.set_line_spacing(1.2);
+    let (mut width, mut height) = options.page_size.dimensions_mm();
+    if options.auto_landscape && width < height {
+        let table_width = match &content {
+            LoadedContent::Table { header, rows } => {
+                Some(ctx.table_width_mm(header.iter().chain(rows).map(Vec::as_slice), ctx.scaled(10)))
+            }
+            LoadedContent::Database(tables) => tables.iter()
+                .map(|table| {
+                    let names: Vec<String> = table.columns.iter().map(|(name, _)| name.clone()).collect();
+                    ctx.table_width_mm(std::iter::once(names.as_slice()).chain(table.rows.iter().map(Vec::as_slice)), ctx.scaled(9))
+                })
+                .max_by(f64::total_cmp),
+            _ => None,
+        };
+        let (content_width, _) = options.content_size_mm();
+        if let Some(table_width) = table_width.filter(|&w| w > content_width) {
+            log::info!(
+                "Table in {:?} is about {:.0} mm wide, more than the {:.0} mm of a portrait page; switching to landscape",
+                path, table_width, content_width
+            );
+            (width, height) = (height, width);
+        }
+    }
+    doc.set_paper_size(genpdf::Size::new(width, height));
+    doc.set_font_size(options.font_size);
+    
+    if options.text_contrast() < ConversionOptions::MIN_CONTRAST {
+        log::warn!(
+            "Text colors have a contrast of only {:.1}:1 against the page background {}",
+            options.text_contrast(), options.page_background.to_hex()
+        );
+    }
+    let header = match &options.page_header {
+        header if header.is_empty() && options.page_numbers => PageTemplate::page_number(),
+        header => header.clone(),
+    };
+    let values = PageValues {
+        filename: path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+        title: document_title(&content, file_type)
+            .unwrap_or_else(|| path.file_stem().unwrap_or_default().to_string_lossy().into_owned()),
+        date: chrono::Local::now().format("%Y-%m-%d").to_string(),
+        pages,
+    };
+    let decorator = TemplateDecorator::new(options.margins, header, options.page_footer.clone(), values);
+    let background = options.page_background != RgbColor::WHITE;
+    if background {
+        doc.set_page_decorator(shading::BackgroundDecorator::new(decorator));
+    } else {
+        doc.set_page_decorator(decorator);
+    }
+
+    log::debug!("Rendering content to document");
+    let mut doc = Blocks::new(doc);
+    let mut jpeg_images = false;
+    let shaded = background || options.table_stripes && matches!(&content, LoadedContent::Table { rows, .. } if !rows.is_empty());
+    match content {
+        LoadedContent::Empty => {
+            log::info!("Input {:?} is empty, rendering placeholder page", path);
+            render_empty_placeholder(&mut doc, &ctx);
+        }
+        LoadedContent::Table { header, rows } => render_table_rows(file_type, header, rows, &mut doc, &ctx)?,
+        LoadedContent::Docx(paragraphs) => render_docx(&paragraphs, &mut doc, &ctx),
+        LoadedContent::Email(email) => render_email(&email, &mut doc, &ctx),
+        LoadedContent::Database(tables) => render_database(&tables, &mut doc, &ctx)?,
+        LoadedContent::Image { image, compressed } => {
+            jpeg_images = compressed && image.is_ok();
+            render_image(&path, image, &mut doc, &ctx);
+        }
+        LoadedContent::Html { html, images } => {
+            jpeg_images = images.iter().flatten().any(|i| i.compressed && i.image.is_ok());
+            render_html(&html, images, &mut doc, &ctx);
+        }
+        LoadedContent::Text(content) => match file_type {
+            // A cut-off document no longer parses, so show what is left as it is.
+            FileType::Json | FileType::Xml | FileType::Yaml | FileType::Toml if truncated => render_text(&content, &mut doc, &ctx),
+            FileType::Markdown => render_markdown(&content, &diagrams, &mut doc, &ctx),
+            FileType::Rst => render_rst(&content, &mut doc, &ctx),
+            FileType::Org => render_markdown(&org::to_markdown(&content), &diagrams, &mut doc, &ctx),
+            FileType::Subtitle => render_subtitles(&content, &mut doc, &ctx),
+            FileType::Log => render_log(&content, &mut doc, &ctx),
+            FileType::Dot => render_dot_source(&content, &mut doc, &ctx),
+            FileType::Json => render_json(&content, &mut doc, &ctx)?,
+            FileType::Xml => render_xml(&content, &mut doc, &ctx)?,
+            FileType::Yaml => render_yaml(&content, &mut doc, &ctx)?,
+            FileType::Toml => render_toml(&content, &mut doc, &ctx)?,
+            FileType::Txt => render_prose(&content, &mut doc, &ctx),
+            _ => render_text(&content, &mut doc, &ctx),
+        },
+    }
+
+    let document_stats = ctx.stats.take();
+    if truncated {
+        doc.push(elements::Break::new(2.0));
+        // Through the glyph fallback: the monospace fonts of code files lack CJK glyphs.
+        let note = ctx.paragraph(&mut doc, "（内容过长，已截断）");
+        doc.push(note.aligned(genpdf::Alignment::Center).styled(style::Style::new().with_color(style::Color::Rgb(128, 128, 128))));
+    }
+
+    if options.stats_page {
+        render_stats_page(&document_stats, fs::metadata(&path).map(|m| m.len()).ok(), &mut doc, &ctx);
+    }
+
+    let links = ctx.links.into_inner();
+    let missing = ctx.missing.into_inner();
+    if !missing.is_empty() {
+        let sample: String = missing.iter().take(20).collect();
+        log::warn!(
+            "{} distinct characters in {:?} are not covered by any loaded font and will render as blank boxes: {}",
+            missing.len(), path, sample
+        );
+    }
+
+    Ok((doc, RenderedDocument { jpeg_images, links, shaded }))
+}
+
+/// Cuts `content` down to about `options.max_pages` pages and returns whether anything was cut.
+///
+/// genpdf only paginates while rendering, so pages are estimated from the lines the content
+/// takes up at the configured font size, with long lines wrapping at the page width.
+fn truncate_to_pages(content: &mut LoadedContent, options: &ConversionOptions) -> bool {
+    let (width_mm, height_mm) = options.content_size_mm();
+    let font_mm = options.font_size as f64 * MM_PER_POINT;
+    // The 1.2 line spacing applies to the font's own line height, about 1.17 em.
+    let lines_per_page = (height_mm / (font_mm * 1.4)).floor().max(1.0);
+    let budget = lines_per_page * options.max_pages as f64;
+    // Latin glyphs average about half an em; wide characters such as CJK take a full em.
+    let columns = (width_mm / (font_mm * 0.5)).max(1.0);
+    let char_width = |c: char| if c.len_utf8() >= 3 { 2.0 } else { 1.0 };
+    let lines = |text: &str| -> f64 {
+        let width: f64 = text.chars().map(char_width).sum();
+        (width / columns).ceil().max(1.0)
+    };
+    // The byte offset after which `text` no longer fits in `budget` lines; a line that only
+    // partly fits is cut where the budget runs out.
+    let cut_text = |text: &str, budget: f64| -> Option<usize> {
+        let mut used = 0.0;
+        let mut offset = 0;
+        for line in text.split_inclusive('\n') {
+            let line_lines = lines(line.trim_end_matches('\n'));
+            if used + line_lines > budget {
+                let mut room = (budget - used).max(0.0).floor() * columns;
+                let fitting = line.char_indices()
+                    .find(|&(_, c)| {
+                        room -= char_width(c);
+                        room < 0.0
+                    })
+                    .map_or(line.len(), |(i, _)| i);
+                return Some(offset + fitting);
+            }
+            used += line_lines;
+            offset += line.len();
+        }
+        None
+    };
+
+    match content {
+        LoadedContent::Text(text) | LoadedContent::Html { html: text, .. } => {
+            cut_text(text, budget).map(|end| text.truncate(end)).is_some()
+        }
+        LoadedContent::Email(email) => {
+            let header_lines = email.headers.len() as f64 + 2.0;
+            cut_text(&email.body, budget - header_lines).map(|end| email.body.truncate(end)).is_some()
+        }
+        LoadedContent::Table { rows, .. } => {
+            let mut used = 2.0;
+            let keep = rows.iter().take_while(|row| {
+                // Multi-line fields are printed as framed cells, a row as tall as its tallest cell.
+                let tallest = row.iter().map(|cell| cell.lines().map(lines).sum::<f64>()).fold(1.0, f64::max);
+                used += lines(&row.join(" | ")).max(tallest);
+                used <= budget
+            }).count();
+            let cut = keep < rows.len();
+            rows.truncate(keep);
+            cut
+        }
+        LoadedContent::Docx(paragraphs) => {
+            let mut used = 0.0;
+            let keep = paragraphs.iter().take_while(|p| {
+                used += lines(&p.text) + 0.5;
+                used <= budget
+            }).count();
+            let cut = keep < paragraphs.len();
+            paragraphs.truncate(keep);
+            cut
+        }
+        LoadedContent::Database(tables) => {
+            // The schema overview, then every table from a fresh page with padded rows.
+            let mut used = lines_per_page;
+            let mut cut = false;
+            let mut keep_tables = tables.len();
+            for (i, table) in tables.iter_mut().enumerate() {
+                used = (used / lines_per_page).ceil() * lines_per_page + 2.0;
+                if used > budget {
+                    keep_tables = i;
+                    cut = true;
+                    break;
+                }
+                let room = ((budget - used) / 1.5).floor() as usize;
+                if room < table.rows.len() {
+                    table.rows.truncate(room);
+                    cut = true;
+                }
+                used += table.rows.len() as f64 * 1.5;
+            }
+            tables.truncate(keep_tables);
+            cut
+        }
+        LoadedContent::Image { .. } | LoadedContent::Empty => false,
+    }
 }
 
-pub fn prepare_font(font_data: Arc<Vec<u8>>) -> Result<Arc<FontData>> {
-    let font = FontData::new(font_data.as_ref().clone(), None)
-        .context("Failed to parse font data")?;
-    Ok(Arc::new(font))
+/// Lists the entries of a ZIP archive that can be converted, in archive order.
+///
+/// Directories, unsupported file types and nested archives are skipped.
+pub fn list_zip_entries(path: &Path) -> Result<Vec<String>> {
+    let file = fs::File::open(path).context("Failed to open ZIP archive")?;
+    let mut archive = ZipArchive::new(file).context("Failed to read ZIP archive")?;
+    let mut entries = Vec::new();
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i)?;
+        if entry.is_dir() {
+            continue;
+        }
+        let Some(name) = entry.enclosed_name() else {
+            log::warn!("Skipping ZIP entry with unsafe path: {}", entry.name());
+            continue;
+        };
+        match FileType::from_path(&name) {
+            FileType::Unknown => {}
+            FileType::Zip => log::info!("Skipping nested archive: {}", entry.name()),
+            FileType::Pdf => log::info!("Skipping PDF entry, which has nothing to convert: {}", entry.name()),
+            _ => entries.push(entry.name().to_string()),
+        }
+    }
+    Ok(entries)
 }
 
-pub fn convert(input: &Path, output: &Path, font: Arc<FontData>) -> Result<()> {
-    log::info!("Starting conversion for: {:?}", input);
-    let file_type = FileType::from_path(input);
-    
-    let content = match file_type {
-        FileType::Image => String::new(), 
-        FileType::Docx => read_docx(input)?,
-        FileType::Csv | FileType::Excel => String::new(), 
-        _ => fs::read_to_string(input).context("Failed to read file")?,
-    };
-    log::info!("File type identified as: {:?}. Content loaded.", file_type);
+/// Extracts the selected entries of a ZIP archive to a temporary directory and converts each
+/// of them to `<output_dir>/<archive stem>/<entry path>.pdf`.
+///
+/// All entries are attempted even if some fail; the error lists every failed entry.
+pub fn convert_zip(
+    input: &Path,
+    output_dir: &Path,
+    entries: &[String],
+    fonts: &FontSet,
+    options: &ConversionOptions,
+) -> Result<Vec<PathBuf>, ConversionError> {
+    log::info!("Starting ZIP conversion for: {:?} ({} entries)", input, entries.len());
+    if entries.is_empty() {
+        return Err(ConversionError::EmptyInput("No ZIP entries are selected".to_string()));
+    }
+    let archive_stem = input.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let temp_dir = scratch::dir(&archive_stem).map_err(ConversionError::while_writing)?;
+    convert_zip_entries(input, output_dir.join(&archive_stem), &temp_dir, entries, fonts, options)
+}
 
-    let font_family = genpdf::fonts::FontFamily {
-        regular: font.as_ref().clone(),
-        bold: font.as_ref().clone(),
-        italic: font.as_ref().clone(),
-        bold_italic: font.as_ref().clone(),
-    };
+fn convert_zip_entries(
+    input: &Path,
+    output_dir: PathBuf,
+    temp_dir: &Path,
+    entries: &[String],
+    fonts: &FontSet,
+    options: &ConversionOptions,
+) -> Result<Vec<PathBuf>, ConversionError> {
+    let archive = fs::File::open(input)
+        .context("Failed to open ZIP archive")
+        .and_then(|file| ZipArchive::new(file).context("Failed to read ZIP archive"));
+    let mut archive = archive.map_err(|e| ConversionError::while_loading(e, FileType::Zip))?;
+    let mut outputs = Vec::new();
+    let mut failed = Vec::new();
 
-    log::debug!("Creating PDF document structure");
-    let mut doc = genpdf::Document::new(font_family);
-    doc.set_title("Converted Document");
-    doc.set_minimal_conformance();
-    doc.set_line_spacing(1.2);
-    
-    let mut decorator = genpdf::SimplePageDecorator::new();
-    decorator.set_margins(10);
-    doc.set_page_decorator(decorator);
+    for name in entries {
+        let mut entry = match archive.by_name(name).with_context(|| format!("Missing ZIP entry: {}", name)) {
+            Ok(entry) => entry,
+            Err(e) => {
+                log::error!("{:#}", e);
+                failed.push((name.clone(), ConversionError::while_loading(e, FileType::Zip)));
+                continue;
+            }
+        };
+        let Some(relative) = entry.enclosed_name() else {
+            failed.push((name.clone(), ConversionError::Unsupported("Unsafe path".to_string())));
+            continue;
+        };
+        let extracted = temp_dir.join(&relative);
+        let output_path = zip_output_path(&output_dir, &relative, options.output_format.extension());
+        if let Err(e) = extract_zip_entry(&mut entry, &extracted, &output_path).with_context(|| format!("Failed to extract {}", name)) {
+            log::error!("{:#}", e);
+            failed.push((name.clone(), ConversionError::while_loading(e, FileType::Zip)));
+            continue;
+        }
 
-    log::debug!("Rendering content to document");
-    match file_type {
-        FileType::Markdown => render_markdown(&content, &mut doc),
-        FileType::Json => render_json(&content, &mut doc)?,
-        FileType::Xml => render_xml(&content, &mut doc)?,
-        FileType::Txt | FileType::Docx => render_text(&content, &mut doc),
-        FileType::Html => render_html(&content, &mut doc),
-        FileType::Csv => render_csv(input, &mut doc)?,
-        FileType::Image => render_image(input, &mut doc)?,
-        FileType::Yaml => render_yaml(&content, &mut doc)?,
-        FileType::Toml => render_toml(&content, &mut doc)?,
-        FileType::Excel => render_excel(input, &mut doc)?,
-        FileType::Unknown => {
-            let msg = "Unknown file type";
-            log::error!("{}", msg);
-            return Err(anyhow::anyhow!(msg));
+        match convert(&extracted, &output_path, fonts, options) {
+            Ok(()) => outputs.push(output_path),
+            Err(e) => {
+                log::error!("Conversion failed for ZIP entry {}: {}", name, e);
+                failed.push((name.clone(), e));
+            }
         }
     }
 
-    log::info!("Rendering PDF to file {:?}", output);
-    doc.render_to_file(output).context("Failed to render PDF")?;
-    log::info!("Conversion complete for {:?}", input);
+    if failed.is_empty() {
+        Ok(outputs)
+    } else {
+        Err(ConversionError::Entries { failed, total: entries.len() })
+    }
+}
+
+/// Where the output of the ZIP entry at `relative` goes.  The name keeps the entry's own
+/// extension, as in `notes.md.pdf`, so that `notes.md` and `notes.txt` get one each, and is
+/// numbered, as in `notes.md (2).pdf`, rather than overwrite a file that is already there.
+fn zip_output_path(output_dir: &Path, relative: &Path, extension: &str) -> PathBuf {
+    let path = output_dir.join(relative);
+    let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    std::iter::once(format!("{}.{}", name, extension))
+        .chain((2..).map(|n| format!("{} ({}).{}", name, n, extension)))
+        .map(|file_name| path.with_file_name(file_name))
+        .find(|candidate| !candidate.exists())
+        .unwrap_or_default()
+}
+
+/// Writes `entry` to `extracted` and creates the directory its output goes to.
+fn extract_zip_entry(entry: &mut impl Read, extracted: &Path, output_path: &Path) -> Result<()> {
+    if let Some(parent) = extracted.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut out = fs::File::create(extracted)?;
+    std::io::copy(entry, &mut out)?;
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
     Ok(())
 }
 
-fn read_docx(path: &Path) -> Result<String> {
+fn read_docx(path: &Path, revisions: TrackedChanges) -> Result<Vec<DocxParagraph>> {
     log::debug!("Reading DOCX file: {:?}", path);
     let file = fs::File::open(path)?;
     let mut archive = ZipArchive::new(file)?;
-    let mut document_xml = archive.by_name("word/document.xml")?;
     let mut content = String::new();
-    document_xml.read_to_string(&mut content)?;
+    archive.by_name("word/document.xml")?.read_to_string(&mut content)?;
+    let heading_styles = match archive.by_name("word/styles.xml") {
+        Ok(mut styles_xml) => {
+            let mut styles = String::new();
+            styles_xml.read_to_string(&mut styles)?;
+            docx_heading_styles(&styles)
+        }
+        Err(_) => HashMap::new(),
+    };
+    let ordered_levels = match archive.by_name("word/numbering.xml") {
+        Ok(mut numbering_xml) => {
+            let mut numbering = String::new();
+            numbering_xml.read_to_string(&mut numbering)?;
+            docx_ordered_levels(&numbering)
+        }
+        Err(_) => HashSet::new(),
+    };
+    let link_targets = match archive.by_name("word/_rels/document.xml.rels") {
+        Ok(mut rels_xml) => {
+            let mut rels = String::new();
+            rels_xml.read_to_string(&mut rels)?;
+            docx_link_targets(&rels)
+        }
+        Err(_) => HashMap::new(),
+    };
 
-    let mut text = String::new();
+    let mut paragraphs = Vec::new();
     let doc = roxmltree::Document::parse(&content)?;
     
     for node in doc.descendants() {
          if node.has_tag_name("p") {
+             let mut text = String::new();
+             let mut links: Vec<(std::ops::Range<usize>, String)> = Vec::new();
              for child in node.descendants() {
-                 if child.has_tag_name("t") {
-                     if let Some(t) = child.text() {
-                         text.push_str(t);
+                 // Deleted runs keep their text in `w:delText`; moved text appears twice, once
+                 // under `w:moveFrom` (old position) and once under `w:moveTo` (new position).
+                 let visible = match child.tag_name().name() {
+                     "t" => {
+                         let (added, removed) = revision_marks(child, node);
+                         match revisions {
+                             TrackedChanges::Final => !removed,
+                             TrackedChanges::Original => !added,
+                         }
+                     }
+                     "delText" => revisions == TrackedChanges::Original,
+                     _ => false,
+                 };
+                 if visible
+                     && let Some(t) = child.text()
+                 {
+                     let start = text.len();
+                     text.push_str(t);
+                     // Links to bookmarks, and relationship ids the package does not resolve,
+                     // stay plain text.
+                     let url = child.ancestors()
+                         .take_while(|a| *a != node)
+                         .find(|a| a.has_tag_name("hyperlink"))
+                         .and_then(|link| docx_attribute(link, "id"))
+                         .and_then(|id| link_targets.get(id));
+                     if let Some(url) = url {
+                         match links.last_mut() {
+                             Some((range, last)) if range.end == start && last == url => range.end = text.len(),
+                             _ => links.push((start..text.len(), url.clone())),
+                         }
                      }
                  }
              }
-             text.push('\n');
+             let properties = node.children().find(|c| c.has_tag_name("pPr"));
+             let heading = properties
+                 .and_then(|ppr| ppr.children().find(|c| c.has_tag_name("pStyle")))
+                 .and_then(|style| docx_attribute(style, "val"))
+                 .and_then(|id| heading_styles.get(id).copied());
+             let list = properties
+                 .and_then(|ppr| ppr.children().find(|c| c.has_tag_name("numPr")))
+                 .and_then(|numbering| {
+                     let value = |tag: &str| numbering.children().find(|c| c.has_tag_name(tag)).and_then(|c| docx_attribute(c, "val"));
+                     let id = value("numId").filter(|&id| id != "0")?;
+                     let level = value("ilvl").and_then(|l| l.parse().ok()).unwrap_or(0);
+                     Some(DocxListItem { level, ordered: ordered_levels.contains(&(id.to_string(), level)) })
+                 });
+             paragraphs.push(DocxParagraph { heading, list, text, links });
          }
     }
-    Ok(text)
+    Ok(paragraphs)
+}
+
+/// `w:`-namespaced attributes are matched by local name.
+fn docx_attribute<'a>(node: roxmltree::Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attributes().find(|a| a.name() == name).map(|a| a.value())
+}
+
+/// Maps the relationship IDs of external hyperlinks to their addresses.
+fn docx_link_targets(rels: &str) -> HashMap<String, String> {
+    let Ok(doc) = roxmltree::Document::parse(rels) else {
+        log::warn!("Failed to parse DOCX relationships, links will render as plain text");
+        return HashMap::new();
+    };
+    doc.descendants()
+        .filter(|node| node.has_tag_name("Relationship"))
+        .filter(|node| docx_attribute(*node, "Type").is_some_and(|t| t.ends_with("/hyperlink")))
+        .filter_map(|node| Some((docx_attribute(node, "Id")?.to_string(), docx_attribute(node, "Target")?.to_string())))
+        .collect()
+}
+
+/// Maps the IDs of heading paragraph styles to their outline level, starting at 1.
+///
+/// Style IDs are localized (`Heading1`, `1`, `标题1`...), so headings are recognized by the
+/// built-in style name (`heading 1`, `Title`) or by an outline level in the style itself.
+fn docx_heading_styles(styles: &str) -> HashMap<String, usize> {
+    let Ok(doc) = roxmltree::Document::parse(styles) else {
+        log::warn!("Failed to parse DOCX styles, headings will render as body text");
+        return HashMap::new();
+    };
+    doc.descendants()
+        .filter(|node| node.has_tag_name("style") && docx_attribute(*node, "type") == Some("paragraph"))
+        .filter_map(|style| {
+            let id = docx_attribute(style, "styleId")?;
+            let child = |tag: &str| style.descendants().find(|c| c.has_tag_name(tag));
+            let name = child("name").and_then(|n| docx_attribute(n, "val")).unwrap_or_default().to_lowercase();
+            let level = if name == "title" {
+                Some(1)
+            } else if let Some(n) = name.strip_prefix("heading ") {
+                n.parse().ok()
+            } else {
+                child("outlineLvl").and_then(|l| docx_attribute(l, "val")).and_then(|v| v.parse::<usize>().ok()).map(|l| l + 1)
+            };
+            Some((id.to_string(), level?))
+        })
+        .collect()
+}
+
+/// The numbering IDs and levels whose items are numbered rather than bulleted.
+///
+/// A paragraph's `w:numId` names a `w:num`, which points to the `w:abstractNum` that gives each
+/// level its number format.
+fn docx_ordered_levels(numbering: &str) -> HashSet<(String, usize)> {
+    let Ok(doc) = roxmltree::Document::parse(numbering) else {
+        log::warn!("Failed to parse DOCX numbering, list items will be bulleted");
+        return HashSet::new();
+    };
+    let abstract_levels: HashMap<&str, Vec<usize>> = doc.descendants()
+        .filter(|node| node.has_tag_name("abstractNum"))
+        .filter_map(|abstract_num| {
+            let ordered = abstract_num.children()
+                .filter(|lvl| lvl.has_tag_name("lvl"))
+                .filter(|lvl| {
+                    let format = lvl.children().find(|c| c.has_tag_name("numFmt")).and_then(|f| docx_attribute(f, "val"));
+                    !matches!(format, Some("bullet") | Some("none"))
+                })
+                .filter_map(|lvl| docx_attribute(lvl, "ilvl")?.parse().ok())
+                .collect();
+            Some((docx_attribute(abstract_num, "abstractNumId")?, ordered))
+        })
+        .collect();
+    doc.descendants()
+        .filter(|node| node.has_tag_name("num"))
+        .filter_map(|num| {
+            let abstract_id = num.children().find(|c| c.has_tag_name("abstractNumId")).and_then(|a| docx_attribute(a, "val"))?;
+            Some((docx_attribute(num, "numId")?, abstract_levels.get(abstract_id)?))
+        })
+        .flat_map(|(id, levels)| levels.iter().map(move |&level| (id.to_string(), level)))
+        .collect()
+}
+
+fn render_docx(paragraphs: &[DocxParagraph], doc: &mut Blocks, ctx: &RenderContext) {
+    for paragraph in paragraphs {
+        match paragraph.heading {
+            Some(level) if !paragraph.text.trim().is_empty() => {
+                doc.push(elements::Break::new(0.5));
+                push_heading(doc, &paragraph.text, level, ctx);
+            }
+            _ if !paragraph.links.is_empty() => {
+                let mut inline = InlineText::default();
+                for (text, url) in paragraph.segments() {
+                    let link = url.and_then(|url| inline.add_url(url));
+                    inline.push_str(text, InlineStyle { link, ..InlineStyle::default() });
+                }
+                push_inline_paragraph(doc, &inline, genpdf::Mm::default(), ctx);
+            }
+            _ => push_body_paragraph(doc, &paragraph.text, ctx),
+        }
+    }
+}
+
+/// Whether `node` lies inside an insertion and/or a deletion within `paragraph`.
+fn revision_marks(node: roxmltree::Node, paragraph: roxmltree::Node) -> (bool, bool) {
+    let mut marks = (false, false);
+    for ancestor in node.ancestors().take_while(|a| *a != paragraph) {
+        match ancestor.tag_name().name() {
+            "ins" | "moveTo" => marks.0 = true,
+            "del" | "moveFrom" => marks.1 = true,
+            _ => {}
+        }
+    }
+    marks
+}
+
+/// The base direction of a line with right-to-left characters: `Some(true)` if it is
+/// right-to-left, `None` if the line has no right-to-left characters at all.
+fn bidi_direction(line: &str) -> Option<bool> {
+    let info = BidiInfo::new(line, None);
+    info.has_rtl().then(|| info.paragraphs.iter().any(|para| para.level.is_rtl()))
+}
+
+/// A paragraph of `line` that is wrapped first and reordered line by line, or `None` if the
+/// line has no right-to-left characters.  Right aligned if its base direction is right-to-left.
+fn bidi_paragraph(doc: &mut Blocks, line: &str, alignment: genpdf::Alignment, ctx: &RenderContext) -> Option<BidiParagraph> {
+    let rtl = bidi_direction(line)?;
+    ctx.count_line(line);
+    let alignment = if rtl { genpdf::Alignment::Right } else { alignment };
+    Some(BidiParagraph::new(ctx.spans(doc, line), alignment))
+}
+
+/// Pushes a level `level` heading, right aligned and reordered if it is right-to-left.
+fn push_heading(doc: &mut Blocks, line: &str, level: usize, ctx: &RenderContext) {
+    let style = ctx.heading_style(level);
+    let block = Block::Heading { level, text: line.to_string() };
+    match bidi_paragraph(doc, line, genpdf::Alignment::Left, ctx) {
+        Some(paragraph) => doc.push_block(block, paragraph.styled(style)),
+        None => {
+            let paragraph = ctx.paragraph(doc, line);
+            doc.push_block(block, paragraph.styled(style));
+        }
+    }
+}
+
+/// Pushes a line of code, as it is, in the code font.
+fn push_code_line(doc: &mut Blocks, line: &str, ctx: &RenderContext) {
+    let paragraph = ctx.paragraph(doc, line);
+    doc.push_block(Block::Code(line.to_string()), paragraph.styled(ctx.code_style()));
+}
+
+/// Pushes a body paragraph using the configured alignment. RTL text is always right aligned.
+fn push_body_paragraph(doc: &mut Blocks, line: &str, ctx: &RenderContext) {
+    push_indented_paragraph(doc, line, genpdf::Mm::default(), ctx);
+}
+
+/// A body paragraph whose first line starts `indent` in; text with right-to-left characters is
+/// not indented or justified.
+fn push_indented_paragraph(doc: &mut Blocks, line: &str, indent: genpdf::Mm, ctx: &RenderContext) {
+    let alignment = match ctx.options.text_align {
+        TextAlign::Left | TextAlign::Justify => genpdf::Alignment::Left,
+        TextAlign::Center => genpdf::Alignment::Center,
+        TextAlign::Right => genpdf::Alignment::Right,
+    };
+    let block = Block::Paragraph(line.to_string());
+    if let Some(paragraph) = bidi_paragraph(doc, line, alignment, ctx) {
+        doc.push_block(block, paragraph.styled(ctx.body_style()));
+        return;
+    }
+    let align = ctx.options.text_align;
+    if (align == TextAlign::Justify || indent > genpdf::Mm::default()) && !line.trim().is_empty() {
+        ctx.count_line(line);
+        let body = ctx.body_style();
+        let spans = ctx.spans(doc, line).into_iter()
+            .map(|span| (style::StyledString::new(span.s, body.and(span.style)), None))
+            .collect();
+        doc.push_block(block, JustifiedParagraph::from_linked_spans(spans, span_alignment(align)).with_first_line_indent(indent));
+        return;
+    }
+    let paragraph = ctx.paragraph(doc, line);
+    doc.push_block(block, paragraph.aligned(alignment).styled(ctx.body_style()));
+}
+
+fn render_text(content: &str, doc: &mut Blocks, ctx: &RenderContext) {
+    push_text_lines(content, false, doc, ctx);
+}
+
+/// Plain text files, a paragraph per line, with the configured first-line indent and paragraph
+/// spacing.  Blank lines keep their own height and get neither.
+fn render_prose(content: &str, doc: &mut Blocks, ctx: &RenderContext) {
+    push_text_lines(content, true, doc, ctx);
 }
 
-fn render_text(content: &str, doc: &mut genpdf::Document) {
+fn push_text_lines(content: &str, prose: bool, doc: &mut Blocks, ctx: &RenderContext) {
     for line in content.lines() {
-        doc.push(elements::Paragraph::new(line));
+        // Expanded here rather than in `spans`, where the tab stops of a line split into
+        // links would start over at each piece.
+        let line = expand_tabs(line, ctx.options.tab_width);
+        let paragraph = prose && !line.trim().is_empty();
+        let mut indent = if paragraph { ctx.first_line_indent() } else { genpdf::Mm::default() };
+        for line in line_chunks(&line, ctx.options.long_line_chars as usize) {
+            if !(ctx.options.detect_links && push_linked_paragraph(doc, line, indent, ctx)) {
+                push_indented_paragraph(doc, line, indent, ctx);
+            }
+            // The pieces of a split line continue the same paragraph.
+            indent = genpdf::Mm::default();
+        }
+        if paragraph && ctx.options.paragraph_spacing > 0.0 {
+            doc.push(elements::Break::new(ctx.options.paragraph_spacing));
+        }
     }
 }
 
-fn render_json(content: &str, doc: &mut genpdf::Document) -> Result<()> {
-    let v: Value = serde_json::from_str(content).unwrap_or(Value::Null);
-    let pretty = if v.is_null() { content.to_string() } else { serde_json::to_string_pretty(&v)? };
-    
+/// `line` in pieces of at most `max_chars` characters; the whole line if `max_chars` is 0.
+fn line_chunks(line: &str, max_chars: usize) -> impl Iterator<Item = &str> {
+    let mut rest = line;
+    let mut first = true;
+    std::iter::from_fn(move || {
+        // An empty line still yields one (empty) piece.
+        if rest.is_empty() && !first {
+            return None;
+        }
+        first = false;
+        let end = match rest.char_indices().nth(max_chars) {
+            Some((end, _)) if max_chars > 0 => end,
+            _ => rest.len(),
+        };
+        let (chunk, tail) = rest.split_at(end);
+        rest = tail;
+        Some(chunk)
+    })
+}
+
+/// Replaces each tab with the spaces up to the next tab stop, every `width` characters.
+pub(crate) fn expand_tabs(text: &str, width: u8) -> Cow<'_, str> {
+    if !text.contains('\t') {
+        return Cow::Borrowed(text);
+    }
+    let width = width.max(1) as usize;
+    let mut expanded = String::with_capacity(text.len() + width);
+    let mut column = 0;
+    for c in text.chars() {
+        match c {
+            '\t' => {
+                let spaces = width - column % width;
+                expanded.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+            }
+            '\n' => {
+                expanded.push(c);
+                column = 0;
+            }
+            _ => {
+                expanded.push(c);
+                column += 1;
+            }
+        }
+    }
+    Cow::Owned(expanded)
+}
+
+/// Prints `line` with its URLs as links, or returns `false` if it has none.  Right-to-left
+/// lines are left to [`push_body_paragraph`], which reorders them.
+fn push_linked_paragraph(doc: &mut Blocks, line: &str, indent: genpdf::Mm, ctx: &RenderContext) -> bool {
+    let urls = links::find_urls(line);
+    if urls.is_empty() || bidi_direction(line) == Some(true) {
+        return false;
+    }
+    ctx.count_line(line);
+    let body = ctx.body_style();
+    let mut spans = Vec::new();
+    let mut push = |doc: &mut Blocks, text: &str, style: style::Style, link: Option<usize>| {
+        spans.extend(ctx.spans(doc, text).into_iter().map(|span| (style::StyledString::new(span.s, style.and(span.style)), link)));
+    };
+    let mut start = 0;
+    for range in urls {
+        push(doc, &line[start..range.start], body, None);
+        let url = &line[range.clone()];
+        match ctx.add_link(url) {
+            Some(index) => push(doc, url, body.with_color(links::LINK_COLOR), Some(index)),
+            None => push(doc, url, body, None),
+        }
+        start = range.end;
+    }
+    push(doc, &line[start..], body, None);
+    let paragraph = JustifiedParagraph::from_linked_spans(spans, span_alignment(ctx.options.text_align));
+    doc.push_block(Block::Paragraph(line.to_string()), paragraph.with_first_line_indent(indent));
+    true
+}
+
+/// The alignment of a [`JustifiedParagraph`] built from spans; `None` justifies it.
+fn span_alignment(align: TextAlign) -> Option<genpdf::Alignment> {
+    match align {
+        TextAlign::Justify => None,
+        TextAlign::Left => Some(genpdf::Alignment::Left),
+        TextAlign::Center => Some(genpdf::Alignment::Center),
+        TextAlign::Right => Some(genpdf::Alignment::Right),
+    }
+}
+
+fn render_json(content: &str, doc: &mut Blocks, ctx: &RenderContext) -> Result<()> {
     doc.push(elements::Paragraph::new("JSON Content:").styled(style::Style::new().bold()));
     doc.push(elements::Break::new(1.0));
-    for line in pretty.lines() {
-        doc.push(elements::Paragraph::new(line).styled(style::Style::new().with_font_size(10)));
+    match serde_json::from_str::<Value>(content) {
+        Ok(v) if ctx.options.json_layout == JsonLayout::Compact => render_code_lines(&tree::compact_lines(&v).join("\n"), doc, ctx),
+        Ok(v) => render_value_tree(&v, doc, ctx),
+        Err(_) => render_code_lines(content, doc, ctx),
+    }
+    Ok(())
+}
+
+fn render_yaml(content: &str, doc: &mut Blocks, ctx: &RenderContext) -> Result<()> {
+    doc.push(elements::Paragraph::new("YAML Content:").styled(style::Style::new().bold()));
+    doc.push(elements::Break::new(1.0));
+    match serde_yaml::from_str::<serde_yaml::Value>(content).ok().as_ref().and_then(tree::from_yaml) {
+        Some(v) => render_value_tree(&v, doc, ctx),
+        // Not valid YAML, or keys the tree cannot show.
+        None => render_code_lines(content, doc, ctx),
+    }
+    Ok(())
+}
+
+fn render_toml(content: &str, doc: &mut Blocks, ctx: &RenderContext) -> Result<()> {
+    doc.push(elements::Paragraph::new("TOML Content:").styled(style::Style::new().bold()));
+    doc.push(elements::Break::new(1.0));
+    match toml::from_str::<toml::Value>(content) {
+        Ok(v) => render_value_tree(&tree::from_toml(v), doc, ctx),
+        Err(_) => render_code_lines(content, doc, ctx),
+    }
+    Ok(())
+}
+
+/// Each line of `content` as it is, in the code font.
+fn render_code_lines(content: &str, doc: &mut Blocks, ctx: &RenderContext) {
+    for line in content.lines() {
+        push_code_line(doc, line, ctx);
+    }
+}
+
+const TREE_CONNECTOR_COLOR: style::Color = style::Color::Rgb(160, 160, 160);
+const TREE_STRING_COLOR: style::Color = style::Color::Rgb(0, 128, 0);
+const TREE_NUMBER_COLOR: style::Color = style::Color::Rgb(0, 64, 192);
+const TREE_BOOL_COLOR: style::Color = style::Color::Rgb(160, 0, 160);
+const TREE_NULL_COLOR: style::Color = style::Color::Rgb(128, 128, 128);
+
+const LOG_ERROR_COLOR: style::Color = style::Color::Rgb(0xb0, 0x1c, 0x1c);
+const LOG_WARN_COLOR: style::Color = style::Color::Rgb(0xb0, 0x80, 0x00);
+const LOG_DEBUG_COLOR: style::Color = style::Color::Rgb(128, 128, 128);
+const LOG_LINE_NUMBER_COLOR: style::Color = style::Color::Rgb(160, 160, 160);
+
+/// The color of a log line by the first log level in it: errors red, warnings yellow, debug and
+/// trace output gray.  `None` for info lines and lines without a level.
+///
+/// A level counts when it is written in capitals, or in any case right after `[`, `<` or `=`
+/// (`[error]`, `level=warn`), so that "error" in a message does not color the line.
+fn log_level_color(line: &str) -> Option<style::Color> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in line.char_indices().chain(std::iter::once((line.len(), ' '))) {
+        match (c.is_ascii_alphabetic(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                words.push((s, &line[s..i]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    words.into_iter()
+        .filter(|(start, word)| {
+            word.chars().all(|c| c.is_ascii_uppercase()) || line[..*start].ends_with(['[', '<', '='])
+        })
+        .find_map(|(_, word)| match word.to_ascii_uppercase().as_str() {
+            "ERROR" | "ERR" | "FATAL" | "CRITICAL" | "CRIT" | "PANIC" | "SEVERE" => Some(Some(LOG_ERROR_COLOR)),
+            "WARN" | "WARNING" => Some(Some(LOG_WARN_COLOR)),
+            "DEBUG" | "TRACE" | "VERBOSE" => Some(Some(LOG_DEBUG_COLOR)),
+            "INFO" | "NOTICE" => Some(None),
+            _ => None,
+        })
+        .flatten()
+}
+
+/// Log files, a line per paragraph colored by [`log_level_color`], with line numbers if the
+/// options ask for them.
+fn render_log(content: &str, doc: &mut Blocks, ctx: &RenderContext) {
+    let width = content.lines().count().to_string().len();
+    for (number, line) in content.lines().enumerate() {
+        let line_style = log_level_color(line).map_or_else(style::Style::new, |color| style::Style::new().with_color(color));
+        let mut number = ctx.options.log_line_numbers.then(|| format!("{:>width$}  ", number + 1));
+        for chunk in line_chunks(line, ctx.options.long_line_chars as usize) {
+            ctx.count_line(chunk);
+            let mut spans = Vec::new();
+            // The pieces of a split line after the first are indented instead of numbered.
+            if let Some(number) = number.take() {
+                spans.push(style::StyledString::new(number, style::Style::new().with_color(LOG_LINE_NUMBER_COLOR)));
+            } else if ctx.options.log_line_numbers {
+                spans.push(style::StyledString::new(" ".repeat(width + 2), style::Style::new()));
+            }
+            spans.extend(ctx.spans(doc, chunk).into_iter().map(|span| style::StyledString::new(span.s, line_style.and(span.style))));
+            doc.push(elements::Paragraph::from(spans));
+        }
+    }
+}
+
+/// Prints `value` as a [`tree`], a line per entry, with scalars colored by type.
+fn render_value_tree(value: &Value, doc: &mut Blocks, ctx: &RenderContext) {
+    for line in tree::tree_lines(value) {
+        let (value, color) = match &line.leaf {
+            tree::Leaf::String(s) => (s.clone(), Some(TREE_STRING_COLOR)),
+            tree::Leaf::Number(n) => (n.clone(), Some(TREE_NUMBER_COLOR)),
+            tree::Leaf::Bool(b) => (b.to_string(), Some(TREE_BOOL_COLOR)),
+            tree::Leaf::Null => ("null".to_string(), Some(TREE_NULL_COLOR)),
+            tree::Leaf::Empty(empty) => (empty.to_string(), None),
+            tree::Leaf::Branch => (String::new(), None),
+        };
+        let separator = match (&line.key, &line.leaf) {
+            (Some(_), tree::Leaf::Branch) | (None, _) => "",
+            (Some(_), _) => ": ",
+        };
+        let key = line.key.as_deref().unwrap_or_default();
+        let text = format!("{}{}{}{}", line.prefix, key, separator, value);
+        ctx.count_line(&text);
+
+        let mut spans = Vec::new();
+        let mut push = |doc: &mut Blocks, text: &str, style: style::Style| {
+            spans.extend(ctx.spans(doc, text).into_iter().map(|span| style::StyledString::new(span.s, style.and(span.style))));
+        };
+        push(doc, &line.prefix, style::Style::new().with_color(TREE_CONNECTOR_COLOR));
+        push(doc, key, style::Style::new().bold());
+        push(doc, separator, style::Style::new());
+        let value_style = color.map_or_else(style::Style::new, |color| style::Style::new().with_color(color));
+        push(doc, &value, value_style);
+        doc.push_block(Block::Code(text), elements::Paragraph::from(spans).styled(ctx.code_style()));
+    }
+}
+
+/// The first worksheet of an Excel or OpenDocument workbook, or the first one in
+/// `options.part_range`.
+fn load_excel(path: &Path, options: &ConversionOptions) -> Result<LoadedContent> {
+    let part_range = PartRange::parse(&options.part_range).map_err(ConversionError::InvalidOption)?;
+    let index = if part_range.is_all() {
+        0
+    } else {
+        let count = excel_sheet_count(path)?;
+        match (1..=count).find(|&n| part_range.contains(n)) {
+            Some(n) => n - 1,
+            None => return Err(ConversionError::EmptyInput(format!(
+                "the workbook has {} worksheets, none of them in the range {:?}", count, options.part_range,
+            )).into()),
+        }
+    };
+
+    let range = open_workbook(path)?.worksheet_range_at(index).and_then(|r| r.ok());
+    let rows = range.map(|r| range_rows(&r)).unwrap_or_default();
+    Ok(LoadedContent::Table { header: None, rows })
+}
+
+/// Opens an XLSX, XLS or ODS workbook, picking the reader by extension.
+fn open_workbook(path: &Path) -> Result<calamine::Sheets<std::io::BufReader<fs::File>>> {
+    calamine::open_workbook_auto(path).map_err(|e| match e {
+        // Encrypted ODS files are still ZIP packages, so `is_encrypted_office_file` misses them.
+        calamine::Error::Ods(calamine::OdsError::Password) => {
+            ConversionError::Encrypted(format!("{} is password protected", path.display())).into()
+        }
+        e => anyhow::Error::new(e).context("Cannot open spreadsheet"),
+    })
+}
+
+fn excel_sheet_count(path: &Path) -> Result<usize> {
+    Ok(open_workbook(path)?.sheet_names().len())
+}
+
+/// Every worksheet of an Excel or OpenDocument workbook with its name, in workbook order.
+fn load_excel_sheets(path: &Path) -> Result<Vec<(String, Vec<Vec<String>>)>> {
+    let sheets = open_workbook(path)?.worksheets();
+    Ok(sheets.into_iter().map(|(name, range)| (name, range_rows(&range))).collect())
+}
+
+fn range_rows(range: &calamine::Range<calamine::Data>) -> Vec<Vec<String>> {
+    range.rows().map(|row| row.iter().map(cell_text).collect()).collect()
+}
+
+/// The text of a worksheet cell.  ODS dates and times come as ISO 8601 strings, shortened here
+/// to what a spreadsheet shows.
+fn cell_text(cell: &calamine::Data) -> String {
+    match cell {
+        calamine::Data::DateTimeIso(iso) => iso.strip_suffix("T00:00:00").unwrap_or(iso).replacen('T', " ", 1),
+        calamine::Data::DurationIso(iso) => iso_duration(iso).unwrap_or_else(|| iso.clone()),
+        cell => cell.to_string(),
+    }
+}
+
+/// `PT01H30M00S` as `01:30:00`.
+fn iso_duration(iso: &str) -> Option<String> {
+    let rest = iso.strip_prefix("PT")?;
+    let (hours, rest) = rest.split_once('H')?;
+    let (minutes, rest) = rest.split_once('M')?;
+    let seconds = rest.strip_suffix('S')?;
+    Some(format!("{}:{}:{}", hours, minutes, seconds))
+}
+
+fn render_xml(content: &str, doc: &mut Blocks, ctx: &RenderContext) -> Result<()> {
+    doc.push(elements::Paragraph::new("XML Content:").styled(style::Style::new().bold()));
+    doc.push(elements::Break::new(1.0));
+     for line in content.lines() {
+        push_code_line(doc, line, ctx);
+    }
+    Ok(())
+}
+
+/// Prints an HTML page with its local images where their `<img>` tags were.
+fn render_html(content: &str, mut images: Vec<Option<HtmlImage>>, doc: &mut Blocks, ctx: &RenderContext) {
+    doc.push(elements::Paragraph::new("HTML Content:").styled(style::Style::new().bold()));
+    doc.push(elements::Break::new(1.0));
+    let loaded: Vec<bool> = images.iter().map(Option::is_some).collect();
+    let Some(text) = html_text(&html::mark_images(content, &loaded), ctx) else {
+        doc.push(elements::Paragraph::new("Failed to parse HTML").styled(style::Style::new().with_color(style::Color::Rgb(255, 0, 0))));
+        return;
+    };
+    let full_page = ctx.options.image_placement == ImagePlacement::FullPage;
+    // A full-page image was the last thing printed, so text after it starts a new page.
+    let mut after_image = false;
+    for line in text.lines() {
+        let (line, found) = html::take_markers(line);
+        if found.is_empty() || !line.trim().is_empty() {
+            if std::mem::take(&mut after_image) {
+                doc.push(elements::PageBreak::new());
+            }
+            render_text(&line, doc, ctx);
+        }
+        for image in found.into_iter().filter_map(|i| images.get_mut(i)?.take()) {
+            if full_page {
+                doc.push(elements::PageBreak::new());
+            }
+            render_image(&image.path, image.image, doc, ctx);
+            after_image = full_page;
+        }
+    }
+}
+
+fn render_html_body(content: &str, doc: &mut Blocks, ctx: &RenderContext) {
+    if let Some(text) = html_text(content, ctx) {
+        render_text(&text, doc, ctx);
+    } else {
+        doc.push(elements::Paragraph::new("Failed to parse HTML").styled(style::Style::new().with_color(style::Color::Rgb(255, 0, 0))));
+    }
+}
+
+/// The text of an HTML document or fragment, with tables drawn in text and images by their
+/// alt text, or `None` if it cannot be parsed.
+fn html_text(content: &str, ctx: &RenderContext) -> Option<String> {
+    // Justified text needs whole paragraphs, so don't let html2text hard-wrap them.
+    let width = if ctx.options.text_align == TextAlign::Justify { 10_000 } else { 80 };
+    html2text::from_read(content.as_bytes(), width)
+        .inspect_err(|e| log::warn!("Failed to parse HTML content: {}", e))
+        .ok()
+}
+
+fn load_email(path: &Path) -> Result<LoadedEmail> {
+    let raw = fs::read(path).context("Failed to read email")?;
+    let message = mail_parser::MessageParser::default().parse(&raw).context("Not a valid email message")?;
+
+    let addresses = |address: Option<&mail_parser::Address>| {
+        address.map(|address| {
+            address.iter()
+                .map(|addr| match (addr.name(), addr.address()) {
+                    (Some(name), Some(email)) => format!("{} <{}>", name, email),
+                    (name, email) => name.or(email).unwrap_or_default().to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+    };
+    let headers = [
+        ("From", addresses(message.from())),
+        ("To", addresses(message.to())),
+        ("Cc", addresses(message.cc())),
+        ("Subject", message.subject().map(str::to_string)),
+        ("Date", message.date().map(|date| date.to_rfc822())),
+    ];
+    let headers: Vec<_> = headers.into_iter()
+        .filter_map(|(label, value)| value.filter(|v| !v.is_empty()).map(|v| (label, v)))
+        .collect();
+    // The parser accepts almost anything, so judge by whether any message header was found.
+    if headers.is_empty() {
+        return Err(anyhow::anyhow!("Not a valid email message: no From, To, Subject or Date header"));
+    }
+
+    // Prefer the HTML alternative, which usually carries the formatting the sender saw.
+    let (body, html) = match message.html_body_count() {
+        0 => (message.body_text(0).unwrap_or_default().into_owned(), false),
+        _ => (message.body_html(0).unwrap_or_default().into_owned(), true),
+    };
+    let attachments = message.attachments()
+        .enumerate()
+        .map(|(i, part)| {
+            use mail_parser::MimeHeaders;
+            part.attachment_name().map(str::to_string).unwrap_or_else(|| format!("attachment {}", i + 1))
+        })
+        .collect();
+
+    Ok(LoadedEmail { headers, body: normalize_line_endings(body, path), html, attachments })
+}
+
+/// Header block, then the body, then the attachment names.
+fn render_email(email: &LoadedEmail, doc: &mut Blocks, ctx: &RenderContext) {
+    for (label, value) in &email.headers {
+        let mut paragraph = elements::Paragraph::default();
+        paragraph.push_styled(format!("{}: ", label), style::Style::new().bold());
+        ctx.count_line(value);
+        for span in ctx.spans(doc, value) {
+            paragraph.push(span);
+        }
+        doc.push(paragraph.styled(style::Style::new().with_font_size(ctx.scaled(10))));
+    }
+    doc.push(elements::Paragraph::new("────────").styled(style::Style::new().with_color(style::Color::Rgb(150, 150, 150))));
+    doc.push(elements::Break::new(0.5));
+
+    if email.html {
+        render_html_body(&email.body, doc, ctx);
+    } else {
+        render_text(&email.body, doc, ctx);
+    }
+
+    if !email.attachments.is_empty() {
+        doc.push(elements::Break::new(1.0));
+        doc.push(elements::Paragraph::new(format!("Attachments ({}):", email.attachments.len())).styled(style::Style::new().bold()));
+        for name in &email.attachments {
+            let paragraph = ctx.paragraph(doc, &format!("• {}", name));
+            doc.push(paragraph.styled(style::Style::new().with_font_size(ctx.scaled(10))));
+        }
+    }
+}
+
+/// Longest cell text shown in a database table before it is cut off.
+const MAX_CELL_CHARS: usize = 200;
+
+/// The tables of a SQLite database in name order, limited to `options.part_range`, with up to
+/// `options.sqlite_row_limit` rows each.
+fn load_sqlite(path: &Path, options: &ConversionOptions) -> Result<Vec<DatabaseTable>> {
+    use rusqlite::types::ValueRef;
+
+    let row_limit = options.sqlite_row_limit;
+    let part_range = PartRange::parse(&options.part_range).map_err(ConversionError::InvalidOption)?;
+
+    let db = rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .context("Failed to open SQLite database")?;
+    let names: Vec<String> = db
+        .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name")
+        .and_then(|mut statement| statement.query_map([], |row| row.get(0))?.collect())
+        .context("Failed to list tables (is this a SQLite database?)")?;
+    let count = names.len();
+    let names = part_range.select(names);
+    if names.is_empty() && count > 0 {
+        return Err(ConversionError::EmptyInput(format!(
+            "the database has {} tables, none of them in the range {:?}", count, options.part_range,
+        )).into());
+    }
+
+    let mut tables = Vec::new();
+    for name in names {
+        let quoted = format!("\"{}\"", name.replace('"', "\"\""));
+        let columns = db.prepare(&format!("PRAGMA table_info({})", quoted))
+            .and_then(|mut statement| {
+                statement.query_map([], |row| Ok((row.get::<_, String>(1)?, row.get::<_, String>(2)?)))?.collect()
+            })
+            .with_context(|| format!("Failed to read columns of table {}", name))?;
+        let total_rows: i64 = db.query_row(&format!("SELECT COUNT(*) FROM {}", quoted), [], |row| row.get(0))
+            .with_context(|| format!("Failed to count rows of table {}", name))?;
+
+        let mut statement = db.prepare(&format!("SELECT * FROM {} LIMIT ?1", quoted))?;
+        let column_count = statement.column_count();
+        let rows = statement
+            .query_map([row_limit], |row| {
+                (0..column_count).map(|i| {
+                    Ok(match row.get_ref(i)? {
+                        ValueRef::Null => "NULL".to_string(),
+                        ValueRef::Integer(v) => v.to_string(),
+                        ValueRef::Real(v) => v.to_string(),
+                        ValueRef::Text(bytes) => {
+                            let text = String::from_utf8_lossy(bytes);
+                            match text.char_indices().nth(MAX_CELL_CHARS) {
+                                Some((end, _)) => format!("{}…", &text[..end]),
+                                None => text.into_owned(),
+                            }
+                        }
+                        ValueRef::Blob(bytes) => format!("<{} bytes>", bytes.len()),
+                    })
+                }).collect()
+            })?
+            .collect::<rusqlite::Result<Vec<Vec<String>>>>()
+            .with_context(|| format!("Failed to read rows of table {}", name))?;
+
+        tables.push(DatabaseTable { name, columns, rows, total_rows: total_rows.max(0) as u64 });
+    }
+    Ok(tables)
+}
+
+/// A schema overview listing every table's columns, then each table on its own page.
+fn render_database(tables: &[DatabaseTable], doc: &mut Blocks, ctx: &RenderContext) -> Result<()> {
+    doc.push(elements::Paragraph::new("Database Schema").styled(style::Style::new().with_font_size(ctx.scaled(18)).bold()));
+    doc.push(elements::Break::new(1.0));
+    if tables.is_empty() {
+        doc.push(elements::Paragraph::new("The database has no tables."));
+        return Ok(());
+    }
+    for table in tables {
+        let title = format!("{} ({} rows)", table.name, table.total_rows);
+        let paragraph = ctx.paragraph(doc, &title);
+        doc.push(paragraph.styled(style::Style::new().bold()));
+        for (column, kind) in &table.columns {
+            let line = if kind.is_empty() { format!("    {}", column) } else { format!("    {}  {}", column, kind) };
+            let paragraph = ctx.paragraph(doc, &line);
+            doc.push(paragraph.styled(style::Style::new().with_font_size(ctx.scaled(10))));
+        }
+        doc.push(elements::Break::new(0.5));
+    }
+
+    let cell_style = style::Style::new().with_font_size(ctx.scaled(9));
+    for table in tables {
+        doc.push(elements::PageBreak::new());
+        let heading = ctx.paragraph(doc, &table.name);
+        doc.push(heading.styled(style::Style::new().with_font_size(ctx.scaled(16)).bold()));
+        doc.push(elements::Break::new(0.5));
+        if table.columns.is_empty() {
+            continue;
+        }
+
+        let mut layout = elements::TableLayout::new(ctx.options.table_weights(table.columns.len()));
+        layout.set_cell_decorator(elements::FrameCellDecorator::new(true, true, false));
+        let mut header = layout.row();
+        for (column, _) in &table.columns {
+            header.push_element(ctx.cell(doc, column).styled(ctx.table_header_style(Some(9))).padded(1));
+        }
+        header.push().context("Failed to lay out table header")?;
+        ctx.stats.borrow_mut().lines += 1;
+        for row in &table.rows {
+            let mut table_row = layout.row();
+            for cell in row {
+                table_row.push_element(ctx.cell(doc, cell).styled(cell_style).padded(1));
+            }
+            table_row.push().context("Failed to lay out table row")?;
+            ctx.stats.borrow_mut().lines += 1;
+        }
+        let alignments = vec![genpdf::Alignment::Left; table.columns.len()];
+        doc.push_block(Block::Table { rows: table.rows.len() + 1, alignments }, layout);
+        ctx.stats.borrow_mut().tables += 1;
+
+        let hidden = table.total_rows.saturating_sub(table.rows.len() as u64);
+        if hidden > 0 {
+            doc.push(elements::Break::new(0.5));
+            doc.push(
+                elements::Paragraph::new(format!("… {} more rows", hidden))
+                    .styled(style::Style::new().with_font_size(ctx.scaled(10)).with_color(style::Color::Rgb(128, 128, 128))),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Reads a table whose fields are separated by `delimiter`.  Tab-separated files have no
+/// quoting, so quotes in them are kept as they are.
+fn load_csv(path: &Path, encoding: TextEncoding, delimiter: u8) -> Result<LoadedContent> {
+    let text = read_text(path, encoding)?;
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .quoting(delimiter != b'\t')
+        .from_reader(text.as_bytes());
+    let header = reader.headers().ok().map(|headers| headers.iter().map(str::to_string).collect());
+    let rows = reader.records().flatten()
+        .map(|record| record.iter().map(str::to_string).collect())
+        .collect();
+    Ok(LoadedContent::Table { header, rows })
+}
+
+fn render_table_rows(
+    file_type: FileType,
+    header: Option<Vec<String>>,
+    rows: Vec<Vec<String>>,
+    doc: &mut Blocks,
+    ctx: &RenderContext,
+) -> Result<()> {
+    let title = match file_type {
+        FileType::Csv => "CSV Content:",
+        FileType::Tsv => "TSV Content:",
+        FileType::Ods => "ODS Content:",
+        _ => "Excel Content:",
+    };
+    doc.push(elements::Paragraph::new(title).styled(style::Style::new().bold()));
+    doc.push(elements::Break::new(1.0));
+    if ctx.options.table_stripes && !rows.is_empty() {
+        return render_framed_table(header, &rows, &[], true, doc, ctx);
+    }
+    // Quoted fields may span lines, which a row joined with " | " cannot show, and column
+    // weights need columns to apply to.
+    let weighted = !ctx.options.column_weights.is_empty() && !rows.is_empty();
+    if weighted || header.iter().chain(&rows).flatten().any(|cell| cell.contains('\n')) {
+        return render_framed_table(header, &rows, &[], false, doc, ctx);
+    }
+
+    if let Some(header) = header {
+        let line = header.join(" | ");
+        let paragraph = ctx.paragraph(doc, &line);
+        doc.push_block(Block::Paragraph(line), paragraph.styled(ctx.table_header_style(None)));
+    }
+    
+    for row in &rows {
+         let line = row.join(" | ");
+         let paragraph = ctx.paragraph(doc, &line);
+         doc.push_block(Block::Paragraph(line), paragraph.styled(style::Style::new().with_font_size(ctx.scaled(10))));
+    }
+    if rows.is_empty() {
+        render_empty_placeholder(doc, ctx);
+    } else {
+        ctx.stats.borrow_mut().tables += 1;
     }
     Ok(())
 }
 
-fn render_yaml(content: &str, doc: &mut genpdf::Document) -> Result<()> {
-    doc.push(elements::Paragraph::new("YAML Content:").styled(style::Style::new().bold()));
-    doc.push(elements::Break::new(1.0));
-    // Try to parse as Value to validate, or just print content if valid.
-    // Ideally we pretty print it, but for now we just print the content if it's text.
-    // If we want to re-serialize to ensure pretty printing:
-    match serde_yaml::from_str::<serde_yaml::Value>(content) {
-        Ok(v) => {
-            let pretty = serde_yaml::to_string(&v)?;
-             for line in pretty.lines() {
-                doc.push(elements::Paragraph::new(line).styled(style::Style::new().with_font_size(10)));
-            }
-        },
-        Err(_) => {
-             // Fallback to raw text if parse fails
-             for line in content.lines() {
-                doc.push(elements::Paragraph::new(line).styled(style::Style::new().with_font_size(10)));
-            }
+/// Table data in framed cells, each column aligned as in `alignments` or to the left; if
+/// `striped`, the header and every other row are shaded by [`shading::fill`].
+fn render_framed_table(
+    header: Option<Vec<String>>,
+    rows: &[Vec<String>],
+    alignments: &[genpdf::Alignment],
+    striped: bool,
+    doc: &mut Blocks,
+    ctx: &RenderContext,
+) -> Result<()> {
+    let columns = header.iter().chain(rows).map(Vec::len).max().unwrap_or(0).max(1);
+    let mut layout = elements::TableLayout::new(ctx.options.table_weights(columns));
+    if striped {
+        layout.set_cell_decorator(shading::StripeDecorator::new(header.is_some()));
+    } else {
+        layout.set_cell_decorator(elements::FrameCellDecorator::new(true, true, false));
+    }
+    let cell_style = style::Style::new().with_font_size(ctx.scaled(10));
+    // Every row needs a cell per column, so short rows are padded with empty cells.
+    let mut push_row = |doc: &mut Blocks, cells: &[String], style: style::Style| {
+        let mut table_row = layout.row();
+        for column in 0..columns {
+            let cell = cells.get(column).map_or("", String::as_str);
+            let alignment = alignments.get(column).copied().unwrap_or(genpdf::Alignment::Left);
+            table_row.push_element(ctx.aligned_cell(doc, cell, alignment).styled(style).padded(1));
         }
+        ctx.stats.borrow_mut().lines += 1;
+        table_row.push().context("Failed to lay out table row")
+    };
+    if let Some(header) = &header {
+        push_row(doc, header, ctx.table_header_style(Some(10)))?;
     }
+    for row in rows {
+        push_row(doc, row, cell_style)?;
+    }
+    let alignments = (0..columns).map(|column| alignments.get(column).copied().unwrap_or(genpdf::Alignment::Left)).collect();
+    doc.push_block(Block::Table { rows: header.iter().count() + rows.len(), alignments }, layout);
+    ctx.stats.borrow_mut().tables += 1;
     Ok(())
 }
 
-fn render_toml(content: &str, doc: &mut genpdf::Document) -> Result<()> {
-    doc.push(elements::Paragraph::new("TOML Content:").styled(style::Style::new().bold()));
-    doc.push(elements::Break::new(1.0));
-    // TOML usually is pretty enough, but we can try to re-serialize.
-    match toml::from_str::<toml::Value>(content) {
-        Ok(v) => {
-            let pretty = toml::to_string_pretty(&v)?;
-            for line in pretty.lines() {
-                doc.push(elements::Paragraph::new(line).styled(style::Style::new().with_font_size(10)));
-            }
-        },
-        Err(_) => {
-            for line in content.lines() {
-                doc.push(elements::Paragraph::new(line).styled(style::Style::new().with_font_size(10)));
-            }
+/// Marks a document whose input had no content, so the output is never silently blank.
+fn render_empty_placeholder(doc: &mut Blocks, ctx: &RenderContext) {
+    doc.push(elements::Break::new(2.0));
+    let note = ctx.paragraph(doc, "（空文件）");
+    doc.push(
+        note.aligned(genpdf::Alignment::Center)
+            .styled(style::Style::new().with_font_size(ctx.scaled(16)).with_color(style::Color::Rgb(128, 128, 128))),
+    );
+}
+
+/// The graph in `input` as an image, or its source when it cannot be drawn.  Only PDF output
+/// gets the image; text and PNG output show the source.
+fn load_dot(input: &Path, options: &ConversionOptions) -> Result<LoadedContent> {
+    if options.output_format == OutputFormat::Pdf && graphviz::available() {
+        // The image is decoded into memory here, so the file can go right away.
+        let rendered = scratch::file("graph.png")
+            .and_then(|temp| graphviz::render_png(input, &temp).map(|()| load_image(&temp, options)));
+        match rendered {
+            Ok(content @ LoadedContent::Image { image: Ok(_), .. }) => return Ok(content),
+            Ok(LoadedContent::Image { image: Err(e), .. }) => log::warn!("Failed to load rendered graph of {:?}: {}", input, e),
+            Ok(_) => {}
+            Err(e) => log::warn!("{:#}; showing the source of {:?} instead", e, input),
         }
     }
-    Ok(())
+    Ok(LoadedContent::Text(read_text(input, options.text_encoding)?))
 }
 
-fn render_excel(path: &Path, doc: &mut genpdf::Document) -> Result<()> {
-    doc.push(elements::Paragraph::new("Excel Content:").styled(style::Style::new().bold()));
+/// Graphviz source that could not be drawn, under a note saying why.
+fn render_dot_source(content: &str, doc: &mut Blocks, ctx: &RenderContext) {
+    let note = if graphviz::available() {
+        "Graphviz 无法绘制此图形（详见日志），以下为图形源代码"
+    } else {
+        "未找到 Graphviz（dot 命令），无法绘制图形，以下为图形源代码。安装 Graphviz 后即可输出图形。"
+    };
+    let note = ctx.paragraph(doc, note);
+    doc.push(note.styled(style::Style::new().with_font_size(ctx.scaled(9)).with_color(style::Color::Rgb(128, 128, 128))));
     doc.push(elements::Break::new(1.0));
-    
-    // Attempt to open as XLSX first, then XLS
-    let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("").to_lowercase();
-    
-    if ext == "xlsx" {
-        let mut workbook: Xlsx<_> = open_workbook(path).context("Cannot open Excel file")?;
-        if let Some(Ok(r)) = workbook.worksheet_range_at(0) {
-             for row in r.rows() {
-                 let line = row.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(" | ");
-                 doc.push(elements::Paragraph::new(line).styled(style::Style::new().with_font_size(10)));
-             }
-        }
-    } else if ext == "xls" {
-        let mut workbook: Xls<_> = open_workbook(path).context("Cannot open Excel file")?;
-         if let Some(Ok(r)) = workbook.worksheet_range_at(0) {
-             for row in r.rows() {
-                 let line = row.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(" | ");
-                 doc.push(elements::Paragraph::new(line).styled(style::Style::new().with_font_size(10)));
+    render_text(content, doc, ctx);
+}
+
+/// Reads an HTML page and loads the images it shows from disk.
+fn load_html(input: &Path, options: &ConversionOptions) -> Result<LoadedContent> {
+    let html = normalize_line_endings(read_text(input, options.text_encoding)?, input);
+    let base = options.html_base_dir.clone()
+        .or_else(|| input.parent().map(Path::to_path_buf))
+        .unwrap_or_default();
+    if options.output_format != OutputFormat::Pdf {
+        // Text and PNG exports print the alt text.
+        return Ok(LoadedContent::Html { html, images: Vec::new() });
+    }
+    let images = html::image_sources(&html)
+        .into_iter()
+        .map(|src| {
+            let path = html::resolve(&src, &base)?;
+            if !path.is_file() || !matches!(FileType::from_path(&path), FileType::Image) {
+                log::warn!("Image {:?} of {:?} is not a local image file, printing its alt text", src, input);
+                return None;
+            }
+            let LoadedContent::Image { image, compressed } = load_image(&path, options) else {
+                return None;
+            };
+            Some(HtmlImage { path, image, compressed })
+        })
+        .collect();
+    Ok(LoadedContent::Html { html, images })
+}
+
+fn load_image(path: &Path, options: &ConversionOptions) -> LoadedContent {
+    let compressed = options.image_compression.applies_to(path);
+    let dpi = match options.image_placement {
+        ImagePlacement::Inline => options.image_dpi.max(1) as f64,
+        // Below any fitting resolution, so the image is scaled to fill the page.
+        ImagePlacement::Fit | ImagePlacement::FullPage => 0.0,
+    };
+    let max_megapixels = options.image_max_megapixels;
+    let image = if compressed {
+        downscale_image(path, dpi, options.content_size_mm(), max_megapixels)
+    } else {
+        image::image_dimensions(path)
+            .context("Failed to read image size")
+            .and_then(|pixels| {
+                let dpi = fitting_dpi(pixels, dpi, options.content_size_mm());
+                if oversized(pixels, max_megapixels) {
+                    let (image, _) = decode_image(path, max_megapixels)?;
+                    image_element(image, pixels.0 as f64 / dpi)
+                } else {
+                    Ok(elements::Image::from_path(path)?.with_dpi(dpi))
+                }
+            })
+    };
+    LoadedContent::Image { image: image.map_err(|e| e.to_string()), compressed }
+}
+
+fn render_image(path: &Path, image: Result<elements::Image, String>, doc: &mut Blocks, ctx: &RenderContext) {
+    match image {
+        Ok(img) => {
+             match ctx.options.image_placement {
+                 ImagePlacement::Inline => doc.push(img),
+                 ImagePlacement::Fit | ImagePlacement::FullPage => doc.push(img.with_alignment(genpdf::Alignment::Center)),
              }
+             ctx.stats.borrow_mut().images += 1;
+        },
+        Err(e) => {
+             log::error!("Error loading image {}: {}", path.display(), e);
+             doc.push(elements::Paragraph::new(format!("Error loading image: {}", e)));
         }
     }
-    Ok(())
 }
 
-fn render_xml(content: &str, doc: &mut genpdf::Document) -> Result<()> {
-    doc.push(elements::Paragraph::new("XML Content:").styled(style::Style::new().bold()));
-    doc.push(elements::Break::new(1.0));
-     for line in content.lines() {
-        doc.push(elements::Paragraph::new(line).styled(style::Style::new().with_font_size(10)));
+/// The resolution that prints an image of `pixels` at `dpi`, or higher if that is needed for
+/// it to fit within `content_mm`.
+fn fitting_dpi(pixels: (u32, u32), dpi: f64, content_mm: (f64, f64)) -> f64 {
+    let fit_dpi = (pixels.0 as f64 / content_mm.0).max(pixels.1 as f64 / content_mm.1) * MM_PER_INCH;
+    if fit_dpi > dpi && dpi > 0.0 {
+        log::debug!("Image of {}x{} pixels does not fit the page at {} dpi, using {:.0} dpi", pixels.0, pixels.1, dpi, fit_dpi);
     }
-    Ok(())
+    dpi.max(fit_dpi)
 }
 
-fn render_html(content: &str, doc: &mut genpdf::Document) {
-    doc.push(elements::Paragraph::new("HTML Content:").styled(style::Style::new().bold()));
-    doc.push(elements::Break::new(1.0));
-    if let Ok(text) = html2text::from_read(content.as_bytes(), 80) {
-        render_text(&text, doc);
+/// Loads an image sized for `dpi` (shrunk to fit the page if needed) and resampled to at most
+/// [`COMPRESSED_IMAGE_DPI`].
+///
+/// Large photos would otherwise embed far more pixels than can be seen.
+fn downscale_image(path: &Path, dpi: f64, content_mm: (f64, f64), max_megapixels: u32) -> Result<elements::Image> {
+    let (source, pixels) = decode_image(path, max_megapixels)?;
+    let (width, height) = (source.width() as f64, source.height() as f64);
+
+    // The physical size follows the pixels of the file, not of a copy shrunk while decoding.
+    let display_inches = pixels.0 as f64 / fitting_dpi(pixels, dpi, content_mm);
+    let target_width = (display_inches * COMPRESSED_IMAGE_DPI).round().max(1.0);
+
+    let resized = if target_width < width {
+        let target_height = (height * target_width / width).round().max(1.0);
+        source.resize_exact(target_width as u32, target_height as u32, image::imageops::FilterType::Triangle)
     } else {
-        log::warn!("Failed to parse HTML content");
-        doc.push(elements::Paragraph::new("Failed to parse HTML").styled(style::Style::new().with_color(style::Color::Rgb(255, 0, 0))));
-    }
+        source
+    };
+    log::debug!(
+        "Downscaled {:?} from {}x{} to {}x{}",
+        path, width, height, resized.width(), resized.height()
+    );
+    image_element(resized, display_inches)
 }
 
-fn render_csv(path: &Path, doc: &mut genpdf::Document) -> Result<()> {
-    let mut reader = csv::Reader::from_path(path)?;
-    doc.push(elements::Paragraph::new("CSV Content:").styled(style::Style::new().bold()));
-    doc.push(elements::Break::new(1.0));
+/// `image` as an element `display_inches` wide.
+fn image_element(image: image::DynamicImage, display_inches: f64) -> Result<elements::Image> {
+    // JPEG has no alpha channel, and genpdf rejects images that have one.
+    let image = image::DynamicImage::ImageRgb8(image.to_rgb8());
+    // Hand the pixels to genpdf losslessly; any JPEG encoding happens on the finished PDF.
+    let mut bmp = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut bmp, image::ImageFormat::Bmp).context("Failed to encode image")?;
+    bmp.set_position(0);
+    let dpi = image.width() as f64 / display_inches;
+    Ok(elements::Image::from_reader(bmp)?.with_dpi(dpi))
+}
+
+/// Whether an image of `pixels` has more than `max_megapixels` million pixels; never if
+/// `max_megapixels` is 0.
+fn oversized(pixels: (u32, u32), max_megapixels: u32) -> bool {
+    max_megapixels > 0 && pixels.0 as u64 * pixels.1 as u64 > max_megapixels as u64 * 1_000_000
+}
 
-    if let Ok(headers) = reader.headers() {
-        let header_line = headers.iter().collect::<Vec<&str>>().join(" | ");
-        doc.push(elements::Paragraph::new(header_line).styled(style::Style::new().bold()));
+/// Decodes the image at `path`, shrunk to at most `max_megapixels` million pixels, and returns
+/// it with the pixel size of the file.
+///
+/// The `image` crate cannot decode at a reduced size, so an oversized image is still decoded in
+/// full before it is shrunk.  Such images are decoded one at a time, so that a batch of
+/// panoramas holds at most one of them at full size.
+fn decode_image(path: &Path, max_megapixels: u32) -> Result<(image::DynamicImage, (u32, u32))> {
+    static OVERSIZED_DECODE: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    let reader = || -> Result<_> {
+        Ok(image::ImageReader::open(path).context("Failed to open image")?.with_guessed_format()?)
+    };
+    let pixels = reader()?.into_dimensions().context("Failed to read image size")?;
+    if !oversized(pixels, max_megapixels) {
+        return Ok((reader()?.decode().context("Failed to decode image")?, pixels));
     }
-    
-    for result in reader.records() {
-        if let Ok(record) = result {
-             let line = record.iter().collect::<Vec<&str>>().join(" | ");
-             doc.push(elements::Paragraph::new(line).styled(style::Style::new().with_font_size(10)));
+    let scale = (max_megapixels as f64 * 1_000_000.0 / (pixels.0 as f64 * pixels.1 as f64)).sqrt();
+    let (width, height) = ((pixels.0 as f64 * scale).max(1.0) as u32, (pixels.1 as f64 * scale).max(1.0) as u32);
+    log::info!(
+        "{:?} has {}x{} pixels, more than {} million; shrinking it to {}x{}",
+        path, pixels.0, pixels.1, max_megapixels, width, height
+    );
+    let _guard = OVERSIZED_DECODE.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+    let full = reader()?.decode().context("Failed to decode image")?;
+    Ok((full.thumbnail(width, height), pixels))
+}
+
+/// Replaces the raw image streams in a written PDF with JPEG data.
+///
+/// printpdf embeds decoded pixels, which is compact for diagrams but not for photos.
+fn jpeg_encode_pdf_images(path: &Path, quality: u8) -> Result<()> {
+    let mut pdf = lopdf::Document::load(path).context("Failed to reopen PDF for image compression")?;
+    let mut encoded = 0;
+    for object in pdf.objects.values_mut() {
+        if let lopdf::Object::Stream(stream) = object
+            && let Some(jpeg) = jpeg_encode_stream(stream, quality)
+        {
+            stream.dict.remove(b"DecodeParms");
+            stream.dict.set("Filter", "DCTDecode");
+            stream.set_content(jpeg);
+            encoded += 1;
         }
     }
+    if encoded > 0 {
+        pdf.save(path).context("Failed to save compressed PDF")?;
+        log::info!("JPEG-encoded {} image(s) at quality {} in {:?}", encoded, quality, path);
+    }
     Ok(())
 }
 
-fn render_image(path: &Path, doc: &mut genpdf::Document) -> Result<()> {
-    match elements::Image::from_path(path) {
-        Ok(img) => {
-             doc.push(img);
-        },
-        Err(e) => {
-             log::error!("Error loading image {}: {}", path.display(), e);
-             doc.push(elements::Paragraph::new(format!("Error loading image: {}", e)));
+fn jpeg_encode_stream(stream: &lopdf::Stream, quality: u8) -> Option<Vec<u8>> {
+    let dict = &stream.dict;
+    let name = |key: &[u8]| dict.get(key).and_then(lopdf::Object::as_name_str).ok();
+    let int = |key: &[u8]| dict.get(key).and_then(lopdf::Object::as_i64).ok();
+    if name(b"Subtype") != Some("Image") || int(b"BitsPerComponent") != Some(8) {
+        return None;
+    }
+    let (color, channels) = match name(b"ColorSpace")? {
+        "DeviceRGB" => (image::ExtendedColorType::Rgb8, 3),
+        "DeviceGray" => (image::ExtendedColorType::L8, 1),
+        _ => return None,
+    };
+    let (width, height) = (u32::try_from(int(b"Width")?).ok()?, u32::try_from(int(b"Height")?).ok()?);
+
+    let pixels = match stream.filters() {
+        Err(_) => stream.content.clone(),
+        Ok(filters) if filters == ["FlateDecode"] => {
+            let mut pixels = Vec::new();
+            flate2::read::ZlibDecoder::new(stream.content.as_slice()).read_to_end(&mut pixels).ok()?;
+            pixels
         }
+        Ok(_) => return None,
+    };
+    if pixels.len() != width as usize * height as usize * channels {
+        return None;
     }
-    Ok(())
+
+    let mut jpeg = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, quality)
+        .encode(&pixels, width, height, color)
+        .ok()?;
+    Some(jpeg)
+}
+
+/// Footnotes and tables, and smart punctuation if `options` turns it on.
+fn markdown_parser_options(options: &ConversionOptions) -> Options {
+    let mut parser_options = Options::ENABLE_FOOTNOTES | Options::ENABLE_TABLES;
+    parser_options.set(Options::ENABLE_SMART_PUNCTUATION, options.smart_punctuation);
+    parser_options
 }
 
-fn render_markdown(content: &str, doc: &mut genpdf::Document) {
-    let parser = Parser::new(content);
+/// Lays out a Markdown document; its Mermaid blocks show the drawn `diagrams` of the same
+/// source, or their source where there is none.
+fn render_markdown(content: &str, diagrams: &[(String, elements::Image)], doc: &mut Blocks, ctx: &RenderContext) {
+    // References to undefined footnotes are left as plain text by the parser.
+    // Entities such as `&amp;` and `&copy;` arrive decoded in the text events.
+    let parser = Parser::new_ext(content, markdown_parser_options(ctx.options));
+    let spacing = ctx.options.markdown_density.block_spacing();
     
-    let mut current_text = String::new();
+    let mut current_text = InlineText::default();
+    // Footnote labels in order of first reference, and the collected definitions.
+    let mut footnote_order: Vec<String> = Vec::new();
+    let mut footnotes: HashMap<String, String> = HashMap::new();
+    let mut in_footnote: Option<String> = None;
+    // Image titles waiting to be captioned below their paragraph.
+    let mut captions: Vec<String> = Vec::new();
+    // Raw HTML of the open HTML block.
+    let mut html_block = String::new();
+    // Column alignments, header and rows of the open table, and the cells of its open row.
+    let mut table_alignments: Vec<genpdf::Alignment> = Vec::new();
+    let mut table_header: Option<Vec<String>> = None;
+    let mut table_rows: Vec<Vec<String>> = Vec::new();
+    let mut table_cells: Vec<String> = Vec::new();
+    // Language of the open fenced code block.
+    let mut code_language = String::new();
 
     for event in parser {
         match event {
-            Event::Text(text) => current_text.push_str(&text),
-            Event::SoftBreak => current_text.push(' '),
-            Event::HardBreak => current_text.push('\n'),
+            event if current_text.push_event(&event) => {}
+            Event::Start(Tag::Table(alignments)) => {
+                table_alignments = alignments.iter().map(|a| markdown_alignment(*a)).collect();
+                table_header = None;
+                table_rows.clear();
+            }
+            Event::Start(Tag::TableHead | Tag::TableRow) => table_cells.clear(),
+            Event::Start(Tag::TableCell) => current_text.clear(),
+            Event::End(TagEnd::TableCell) => {
+                table_cells.push(current_text.text.trim().to_string());
+                current_text.clear();
+            }
+            Event::End(TagEnd::TableHead) => table_header = Some(std::mem::take(&mut table_cells)),
+            Event::End(TagEnd::TableRow) => table_rows.push(std::mem::take(&mut table_cells)),
+            Event::End(TagEnd::Table) => {
+                // Striping needs the PDF rewritten afterwards, which only table inputs get.
+                let table = render_framed_table(table_header.take(), &table_rows, &table_alignments, false, doc, ctx);
+                if let Err(e) = table {
+                    log::warn!("Failed to lay out a Markdown table: {:#}", e);
+                }
+                push_captions(&mut captions, doc, ctx);
+                doc.push(elements::Break::new(spacing));
+                table_rows.clear();
+            }
+            Event::Start(Tag::HtmlBlock) => html_block.clear(),
+            Event::Html(html) => html_block.push_str(&html),
+            Event::End(TagEnd::HtmlBlock) => {
+                render_markdown_html(&html_block, doc, ctx);
+                html_block.clear();
+            }
+            Event::Start(Tag::Image { title, .. }) if !title.is_empty() => captions.push(title.to_string()),
+            Event::FootnoteReference(label) => {
+                let number = match footnote_order.iter().position(|l| l.as_str() == &*label) {
+                    Some(index) => index + 1,
+                    None => {
+                        footnote_order.push(label.to_string());
+                        footnote_order.len()
+                    }
+                };
+                current_text.push_str(&superscript(number), current_text.style());
+            }
+            Event::Start(Tag::FootnoteDefinition(label)) => {
+                in_footnote = Some(label.to_string());
+                current_text.clear();
+            }
+            Event::End(TagEnd::FootnoteDefinition) => {
+                in_footnote = None;
+                current_text.clear();
+            }
+            Event::End(TagEnd::Paragraph | TagEnd::CodeBlock) if in_footnote.is_some() => {
+                let definition = footnotes.entry(in_footnote.clone().unwrap_or_default()).or_default();
+                if !definition.is_empty() {
+                    definition.push(' ');
+                }
+                definition.push_str(current_text.text.trim());
+                current_text.clear();
+            }
             Event::Start(Tag::Paragraph) => {
                 current_text.clear();
             },
             Event::End(TagEnd::Paragraph) => {
-                if !current_text.is_empty() {
-                    doc.push(elements::Paragraph::new(&current_text));
-                    doc.push(elements::Break::new(0.5));
+                if !current_text.text.is_empty() {
+                    push_inline_paragraph(doc, &current_text, ctx.first_line_indent(), ctx);
+                    push_captions(&mut captions, doc, ctx);
+                    doc.push(elements::Break::new(spacing + ctx.options.paragraph_spacing));
                 }
                 current_text.clear();
             },
-            Event::Start(Tag::Heading{..}) => {
+            Event::Start(Tag::Heading { level, .. }) => {
+                 // Each section of a presentation is a slide of its own.
+                 let slide = matches!(level, HeadingLevel::H1 | HeadingLevel::H2);
+                 if ctx.options.presentation && slide && ctx.stats.borrow().has_content() {
+                     doc.push(elements::PageBreak::new());
+                 }
                  current_text.clear();
             },
             Event::End(TagEnd::Heading(level)) => {
-                 let size = match level {
-                     HeadingLevel::H1 => 20,
-                     HeadingLevel::H2 => 18,
-                     _ => 14,
-                 };
-                 doc.push(elements::Paragraph::new(&current_text).styled(style::Style::new().with_font_size(size).bold()));
-                 doc.push(elements::Break::new(0.5));
+                 push_heading(doc, &current_text.text, level as usize, ctx);
+                 push_captions(&mut captions, doc, ctx);
+                 doc.push(elements::Break::new(spacing));
                  current_text.clear();
             },
-            Event::Code(text) => {
-                 current_text.push_str(&format!(" {} ", text));
-            },
-            Event::Start(Tag::CodeBlock(_)) => {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                code_language = match kind {
+                    CodeBlockKind::Fenced(info) => info.split_whitespace().next().unwrap_or_default().to_lowercase(),
+                    CodeBlockKind::Indented => String::new(),
+                };
                 current_text.clear();
             },
+            Event::End(TagEnd::CodeBlock) if code_language == "mermaid" => {
+                push_mermaid(&current_text.text, diagrams, doc, ctx);
+                doc.push(elements::Break::new(spacing));
+                current_text.clear();
+            }
             Event::End(TagEnd::CodeBlock) => {
-                 for line in current_text.lines() {
-                    doc.push(elements::Paragraph::new(line).styled(style::Style::new().with_font_size(10)));
+                 for line in current_text.text.lines() {
+                    push_code_line(doc, line, ctx);
                  }
-                 doc.push(elements::Break::new(0.5));
+                 doc.push(elements::Break::new(spacing));
                  current_text.clear();
             }
              _ => {}
         }
     }
-    if !current_text.is_empty() {
-        doc.push(elements::Paragraph::new(&current_text));
+    if !current_text.text.is_empty() {
+        push_inline_paragraph(doc, &current_text, ctx.first_line_indent(), ctx);
+    }
+    push_captions(&mut captions, doc, ctx);
+    render_footnotes(&footnote_order, &footnotes, doc, ctx);
+}
+
+/// The Mermaid diagrams of `markdown` drawn with the Mermaid CLI, each with its source, sized
+/// for the page of `options`.  Diagrams that cannot be drawn are left out.
+fn load_diagrams(markdown: &str, options: &ConversionOptions) -> Vec<(String, elements::Image)> {
+    let sources = mermaid_sources(markdown);
+    if sources.is_empty() || !mermaid::available() {
+        return Vec::new();
+    }
+    sources.into_iter()
+        .filter_map(|source| {
+            let image = scratch::file("diagram.png").and_then(|temp| {
+                mermaid::render_png(&source, &temp)?;
+                let (image, pixels) = decode_image(&temp, options.image_max_megapixels)?;
+                let display_inches = pixels.0 as f64 / fitting_dpi(pixels, mermaid::RENDER_DPI, options.content_size_mm());
+                image_element(image, display_inches)
+            });
+            match image {
+                Ok(image) => Some((source, image)),
+                Err(e) => {
+                    log::warn!("{:#}; showing the Mermaid source instead", e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// The sources of the distinct ```` ```mermaid ```` blocks of `markdown`, as the renderer reads
+/// them.
+fn mermaid_sources(markdown: &str) -> Vec<String> {
+    let mut sources = Vec::new();
+    let mut source = None;
+    for event in Parser::new_ext(markdown, Options::ENABLE_FOOTNOTES | Options::ENABLE_TABLES) {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info)))
+                if info.split_whitespace().next().is_some_and(|language| language.eq_ignore_ascii_case("mermaid")) =>
+            {
+                source = Some(String::new());
+            }
+            Event::Text(text) => source.iter_mut().for_each(|source| source.push_str(&text)),
+            Event::End(TagEnd::CodeBlock) => sources.extend(source.take()),
+            _ => {}
+        }
+    }
+    sources.sort();
+    sources.dedup();
+    sources
+}
+
+/// Prints the drawn diagram of `source`, or the source under a note saying why it could not be
+/// drawn.
+fn push_mermaid(source: &str, diagrams: &[(String, elements::Image)], doc: &mut Blocks, ctx: &RenderContext) {
+    if let Some((_, image)) = diagrams.iter().find(|(drawn, _)| drawn == source) {
+        doc.push(image.clone().with_alignment(genpdf::Alignment::Center));
+        ctx.stats.borrow_mut().images += 1;
+        return;
+    }
+    let note = if mermaid::available() {
+        "Mermaid 无法绘制此图表（详见日志），以下为图表源代码"
+    } else {
+        "未找到 Mermaid 命令行工具（mmdc），无法绘制图表，以下为图表源代码。安装 @mermaid-js/mermaid-cli 后即可输出图表。"
+    };
+    let note = ctx.paragraph(doc, note);
+    doc.push(note.styled(style::Style::new().with_font_size(ctx.scaled(9)).with_color(style::Color::Rgb(128, 128, 128))));
+    for line in source.lines() {
+        push_code_line(doc, line, ctx);
+    }
+}
+
+/// Prints an HTML block of a Markdown file through the HTML renderer.  Blocks that only wrap
+/// Markdown, such as a lone `<div>` or a comment, print nothing; HTML that cannot be parsed
+/// is printed as it is written.
+fn render_markdown_html(html: &str, doc: &mut Blocks, ctx: &RenderContext) {
+    match html_text(html, ctx) {
+        Some(text) => {
+            if !text.trim().is_empty() {
+                render_text(text.trim_end(), doc, ctx);
+                doc.push(elements::Break::new(ctx.options.markdown_density.block_spacing()));
+            }
+        }
+        None => {
+            for line in html.lines() {
+                push_body_paragraph(doc, line, ctx);
+            }
+        }
+    }
+}
+
+/// The alignment of a Markdown table column; columns without a marker are left-aligned.
+fn markdown_alignment(alignment: pulldown_cmark::Alignment) -> genpdf::Alignment {
+    match alignment {
+        pulldown_cmark::Alignment::None | pulldown_cmark::Alignment::Left => genpdf::Alignment::Left,
+        pulldown_cmark::Alignment::Center => genpdf::Alignment::Center,
+        pulldown_cmark::Alignment::Right => genpdf::Alignment::Right,
+    }
+}
+
+/// Whether an inline HTML tag is `<br>`, in any of its spellings.
+fn is_line_break(tag: &str) -> bool {
+    let tag = tag.trim().to_ascii_lowercase();
+    tag.strip_prefix("<br").is_some_and(|rest| rest.trim_start().trim_start_matches('/').trim_start() == ">")
+}
+
+/// Inline Markdown formatting that can enclose other formatting.
+enum Inline {
+    Strong,
+    Emphasis,
+    /// A link, with the index of its target in [`InlineText::urls`] if it is a web address.
+    Link(Option<usize>),
+}
+
+/// Formatting of a piece of Markdown text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct InlineStyle {
+    bold: bool,
+    italic: bool,
+    /// A code span, printed in the monospace font.
+    code: bool,
+    /// Index of the link target in [`InlineText::urls`].
+    link: Option<usize>,
+}
+
+/// The text of the Markdown block being collected, in runs of the same formatting.
+#[derive(Default)]
+struct InlineText {
+    text: String,
+    /// Byte ranges of `text` that follow each other without gaps, with their formatting.
+    runs: Vec<(std::ops::Range<usize>, InlineStyle)>,
+    /// Web addresses the links of the block open.
+    urls: Vec<String>,
+    /// Emphasis and links open at the current point, innermost last.
+    open: Vec<Inline>,
+    /// Title of the open link, printed after its text.
+    link_title: Option<String>,
+}
+
+impl InlineText {
+    /// Adds text, a code span or a line break to the text, or opens or closes emphasis or a
+    /// link.  Returns `false` for events that are not inline formatting.
+    fn push_event(&mut self, event: &Event<'_>) -> bool {
+        match event {
+            Event::Text(text) => self.push_str(text, self.style()),
+            // The monospace font applies on top of the surrounding emphasis, which goes on
+            // after the code span.
+            Event::Code(text) => self.push_str(text, InlineStyle { code: true, ..self.style() }),
+            Event::SoftBreak => self.push_str(" ", self.style()),
+            Event::HardBreak => self.push_str("\n", self.style()),
+            Event::InlineHtml(tag) if is_line_break(tag) => self.push_str("\n", self.style()),
+            Event::Start(Tag::Strong) => self.open.push(Inline::Strong),
+            Event::Start(Tag::Emphasis) => self.open.push(Inline::Emphasis),
+            Event::Start(Tag::Link { dest_url, title, .. }) => {
+                self.link_title = (!title.is_empty()).then(|| title.to_string());
+                let url = self.add_url(dest_url);
+                self.open.push(Inline::Link(url));
+            }
+            Event::End(TagEnd::Strong | TagEnd::Emphasis) => {
+                self.open.pop();
+            }
+            Event::End(TagEnd::Link) => {
+                self.open.pop();
+                if let Some(title) = self.link_title.take() {
+                    self.push_str(&format!(" ({})", title), self.style());
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// The combined formatting of the open emphasis and links.
+    fn style(&self) -> InlineStyle {
+        self.open.iter().fold(InlineStyle::default(), |style, element| match element {
+            Inline::Strong => InlineStyle { bold: true, ..style },
+            Inline::Emphasis => InlineStyle { italic: true, ..style },
+            Inline::Link(url) => InlineStyle { link: url.or(style.link), ..style },
+        })
+    }
+
+    /// The runs of the text, each with its formatting.
+    fn segments(&self) -> impl Iterator<Item = (&str, InlineStyle)> + '_ {
+        self.runs.iter().map(|(range, style)| (&self.text[range.clone()], *style))
+    }
+
+    fn push_str(&mut self, text: &str, style: InlineStyle) {
+        let start = self.text.len();
+        self.text.push_str(text);
+        match self.runs.last_mut() {
+            Some((range, last)) if *last == style => range.end = self.text.len(),
+            _ => self.runs.push((start..self.text.len(), style)),
+        }
+    }
+
+    /// Registers the target of a link and returns its index, or `None` for targets that are
+    /// not web addresses, such as anchors and relative paths.
+    fn add_url(&mut self, url: &str) -> Option<usize> {
+        let web = ["http://", "https://", "ftp://", "mailto:"].iter().any(|scheme| {
+            url.get(..scheme.len()).is_some_and(|prefix| prefix.eq_ignore_ascii_case(scheme))
+        });
+        web.then(|| {
+            self.urls.push(url.to_string());
+            self.urls.len() - 1
+        })
+    }
+
+    fn clear(&mut self) {
+        self.text.clear();
+        self.runs.clear();
+        self.urls.clear();
+    }
+}
+
+/// Prints a Markdown paragraph with its bold, italic, code and link runs.  Paragraphs without
+/// formatting, and right-to-left ones, which are reordered line by line, are left to
+/// [`push_body_paragraph`].  The first line starts `indent` in.
+fn push_inline_paragraph(doc: &mut Blocks, paragraph: &InlineText, indent: genpdf::Mm, ctx: &RenderContext) {
+    let plain = paragraph.runs.iter().all(|(_, style)| *style == InlineStyle::default());
+    if plain || paragraph.text.trim().is_empty() || bidi_direction(&paragraph.text) == Some(true) {
+        push_indented_paragraph(doc, &paragraph.text, indent, ctx);
+        return;
+    }
+    ctx.count_line(&paragraph.text);
+    // Registered only now, so that links in headings and footnotes get no half-drawn markers.
+    let links: Vec<Option<usize>> = paragraph.urls.iter().map(|url| ctx.add_link(url)).collect();
+    let body = ctx.body_style();
+    let mut spans = Vec::new();
+    for (text, inline) in paragraph.segments() {
+        let mut style = body;
+        if inline.bold {
+            style.set_bold();
+        }
+        if inline.italic {
+            style.set_italic();
+        }
+        if inline.code && let Some(family) = ctx.mono {
+            style.set_font_family(family);
+        }
+        let link = inline.link.and_then(|index| links[index]);
+        if link.is_some() {
+            style.set_color(links::LINK_COLOR);
+        }
+        spans.extend(ctx.spans(doc, text).into_iter().map(|span| (style::StyledString::new(span.s, style.and(span.style)), link)));
+    }
+    let justified = JustifiedParagraph::from_linked_spans(spans, span_alignment(ctx.options.text_align));
+    doc.push_block(Block::Paragraph(paragraph.text.clone()), justified.with_first_line_indent(indent));
+}
+
+/// Image titles as small italic captions, centered below the paragraph holding the images.
+fn push_captions(captions: &mut Vec<String>, doc: &mut Blocks, ctx: &RenderContext) {
+    for caption in captions.drain(..) {
+        let paragraph = ctx.paragraph(doc, &caption).aligned(genpdf::Alignment::Center);
+        doc.push(paragraph.styled(
+            style::Style::new().italic().with_font_size(ctx.scaled(9)).with_color(style::Color::Rgb(96, 96, 96)),
+        ));
+    }
+}
+
+/// Font size (before scaling) of a level 1, 2, 3... heading.
+fn heading_size(level: usize) -> u8 {
+    match level {
+        1 => 20,
+        2 => 18,
+        _ => 14,
+    }
+}
+
+/// Lays out the blocks of an RST document like their Markdown counterparts.
+fn render_rst(content: &str, doc: &mut Blocks, ctx: &RenderContext) {
+    let spacing = ctx.options.markdown_density.block_spacing();
+    for block in rst::parse(content) {
+        match block {
+            rst::Block::Heading { level, text } => {
+                push_heading(doc, &text, level, ctx);
+            }
+            rst::Block::Paragraph(text) => push_body_paragraph(doc, &text, ctx),
+            rst::Block::ListItem { marker, depth, text } => {
+                let indent = "    ".repeat(depth);
+                push_body_paragraph(doc, &format!("{}{} {}", indent, marker, text), ctx);
+            }
+            rst::Block::Literal(lines) => {
+                for line in lines {
+                    push_code_line(doc, &line, ctx);
+                }
+            }
+        }
+        doc.push(elements::Break::new(spacing));
+    }
+}
+
+/// Each cue as a small gray timing line above its text.
+fn render_subtitles(content: &str, doc: &mut Blocks, ctx: &RenderContext) {
+    let timing_style = style::Style::new().with_font_size(ctx.scaled(9)).with_color(style::Color::Rgb(128, 128, 128));
+    let spacing = ctx.options.markdown_density.block_spacing();
+    for item in subtitle::parse(content) {
+        match item {
+            subtitle::Item::Cue(cue) => {
+                let timing = match &cue.id {
+                    Some(id) => format!("{}    {} → {}", id, cue.start, cue.end),
+                    None => format!("{} → {}", cue.start, cue.end),
+                };
+                let paragraph = ctx.paragraph(doc, &timing);
+                doc.push(paragraph.styled(timing_style));
+                for line in cue.lines {
+                    push_body_paragraph(doc, &line, ctx);
+                }
+            }
+            subtitle::Item::Verbatim(lines) => {
+                for line in lines {
+                    push_code_line(doc, &line, ctx);
+                }
+            }
+        }
+        doc.push(elements::Break::new(spacing));
+    }
+}
+
+/// Lists referenced footnotes below a short rule, numbered in order of first reference.
+fn render_footnotes(order: &[String], definitions: &HashMap<String, String>, doc: &mut Blocks, ctx: &RenderContext) {
+    let unused = definitions.keys().filter(|label| !order.contains(label)).count();
+    if unused > 0 {
+        log::debug!("Skipping {} unreferenced footnote definitions", unused);
+    }
+    let notes: Vec<(usize, &String)> = order.iter().enumerate()
+        .filter_map(|(i, label)| definitions.get(label).map(|text| (i + 1, text)))
+        .collect();
+    if notes.is_empty() {
+        return;
+    }
+
+    doc.push(elements::Break::new(1.0));
+    doc.push(elements::Paragraph::new("────────").styled(style::Style::new().with_color(style::Color::Rgb(128, 128, 128))));
+    for (number, text) in notes {
+        let paragraph = ctx.paragraph(doc, &format!("{} {}", superscript(number), text));
+        doc.push(paragraph.styled(style::Style::new().with_font_size(ctx.scaled(9))));
+    }
+}
+
+/// Formats a number with Unicode superscript digits, e.g. `12` as `¹²`.
+fn superscript(number: usize) -> String {
+    const DIGITS: [char; 10] = ['⁰', '¹', '²', '³', '⁴', '⁵', '⁶', '⁷', '⁸', '⁹'];
+    number.to_string().chars().map(|c| DIGITS[c.to_digit(10).unwrap_or(0) as usize]).collect()
+}
+
+/// A page of `stats` and the input's size in bytes, after the content.
+fn render_stats_page(stats: &DocumentStats, size: Option<u64>, doc: &mut Blocks, ctx: &RenderContext) {
+    doc.push(elements::PageBreak::new());
+    let heading = ctx.paragraph(doc, "文档统计");
+    doc.push(heading.styled(ctx.heading_style(1)));
+    doc.push(elements::Break::new(1.0));
+    let mut rows = vec![
+        ("字数", stats.words.to_string()),
+        ("字符数（不含空白）", stats.characters.to_string()),
+        ("段落与行数", stats.lines.to_string()),
+        ("表格", stats.tables.to_string()),
+        ("图片", stats.images.to_string()),
+    ];
+    if let Some(size) = size {
+        rows.push(("文件大小", stats::format_size(size)));
+    }
+    for (label, value) in rows {
+        let paragraph = ctx.paragraph(doc, &format!("{}：{}", label, value));
+        doc.push(paragraph.styled(ctx.body_style()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The formatted runs of a one-paragraph Markdown document.
+    fn inline_segments(markdown: &str) -> (Vec<(String, InlineStyle)>, Vec<String>) {
+        let mut text = InlineText::default();
+        for event in Parser::new(markdown) {
+            text.push_event(&event);
+        }
+        let segments = text.segments().map(|(s, style)| (s.to_string(), style)).collect();
+        (segments, text.urls)
+    }
+
+    fn run(text: &str, bold: bool, italic: bool, code: bool, link: Option<usize>) -> (String, InlineStyle) {
+        (text.to_string(), InlineStyle { bold, italic, code, link })
+    }
+
+    #[test]
+    fn zip_outputs_keep_the_entry_extension_and_never_overwrite() {
+        let dir = std::env::temp_dir().join(format!("topdf_zip_names_{}", std::process::id()));
+        fs::create_dir_all(dir.join("docs")).unwrap();
+        fs::write(dir.join("docs/report.md.pdf"), b"").unwrap();
+        let name = |entry: &str| zip_output_path(&dir, Path::new(entry), "pdf").strip_prefix(&dir).unwrap().to_path_buf();
+        assert_eq!(name("docs/notes.md"), Path::new("docs/notes.md.pdf"));
+        assert_eq!(name("docs/notes.txt"), Path::new("docs/notes.txt.pdf"));
+        assert_eq!(name("docs/report.md"), Path::new("docs/report.md (2).pdf"));
+        fs::write(dir.join("docs/report.md (2).pdf"), b"").unwrap();
+        assert_eq!(name("docs/report.md"), Path::new("docs/report.md (3).pdf"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn table_weights_default_to_one() {
+        let options = ConversionOptions { column_weights: vec![2, 0, 5], ..ConversionOptions::default() };
+        assert_eq!(options.table_weights(5), [2, 1, 5, 1, 1]);
+        assert_eq!(options.table_weights(2), [2, 1]);
+        assert_eq!(ConversionOptions::default().table_weights(3), [1, 1, 1]);
+    }
+
+    /// Converts `content`, written to a file called `name`, to a PDF and returns the scratch
+    /// folder holding both, and the PDF read back.
+    fn convert_text(name: &str, content: &str, options: &ConversionOptions) -> (scratch::Scratch, lopdf::Document) {
+        let dir = scratch::dir("test").unwrap();
+        let input = dir.join(name);
+        fs::write(&input, content).unwrap();
+        let output = dir.join("output.pdf");
+        convert(&input, &output, &bundled_fonts(), options).unwrap();
+        let pdf = lopdf::Document::load(&output).unwrap();
+        (dir, pdf)
+    }
+
+    /// The x coordinates of the vertical lines on the first page, left to right.
+    fn vertical_lines(pdf: &lopdf::Document) -> Vec<f64> {
+        let page = pdf.page_iter().next().unwrap();
+        let content = lopdf::content::Content::decode(&pdf.get_page_content(page).unwrap()).unwrap();
+        let x = |op: &lopdf::content::Operation| op.operands[0].as_f64().unwrap_or_else(|_| op.operands[0].as_i64().unwrap() as f64);
+        let mut lines: Vec<f64> = content.operations.windows(2)
+            .filter(|ops| ops[0].operator == "m" && ops[1].operator == "l" && (x(&ops[0]) - x(&ops[1])).abs() < 0.01)
+            .map(|ops| x(&ops[0]))
+            .collect();
+        lines.sort_by(f64::total_cmp);
+        lines.dedup_by(|a, b| (*a - *b).abs() < 0.1);
+        lines
+    }
+
+    #[test]
+    fn weighted_column_keeps_its_share_of_the_width() {
+        let options = ConversionOptions { column_weights: vec![1, 3], ..ConversionOptions::default() };
+        let long = "word ".repeat(60);
+        for csv in [format!("id,text\n1,{}\n", long), format!("id,text\n{},x\n", long)] {
+            let (_dir, pdf) = convert_text("table.csv", &csv, &options);
+            let lines = vertical_lines(&pdf);
+            assert_eq!(lines.len(), 3, "column edges {:?}", lines);
+            let share = (lines[1] - lines[0]) / (lines[2] - lines[0]);
+            assert!((share - 0.25).abs() < 0.01, "first column takes {:.3} of the width", share);
+        }
+    }
+
+    #[test]
+    fn quoted_csv_fields_stay_in_their_cells() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/quoted.csv");
+        let Ok(LoadedInput { content: LoadedContent::Table { header, rows }, .. }) = load_input(&path, &ConversionOptions::default()) else {
+            panic!("quoted.csv is not read as a table");
+        };
+        assert_eq!(header.unwrap(), ["name", "address", "notes"]);
+        assert_eq!(rows[0], ["Wang, Li", "12 Main St, Apt 4", "line1\nline2"]);
+
+        // The line break in a field makes the rows a framed table, one column per field.
+        let (_dir, pdf) = convert_text("quoted.csv", &fs::read_to_string(&path).unwrap(), &ConversionOptions::default());
+        assert_eq!(vertical_lines(&pdf).len(), 4);
+    }
+
+    #[test]
+    fn table_columns_take_their_delimiter_alignment() {
+        let markdown = "| Left | Center | Right |\n|:--|:-:|--:|\n| a | b | c |\n| d | e | f |\n";
+        let alignments: Vec<genpdf::Alignment> = Parser::new_ext(markdown, Options::ENABLE_TABLES)
+            .find_map(|event| match event {
+                Event::Start(Tag::Table(alignments)) => Some(alignments.iter().map(|a| markdown_alignment(*a)).collect()),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(alignments, [genpdf::Alignment::Left, genpdf::Alignment::Center, genpdf::Alignment::Right]);
+
+        // The table is printed as framed cells, one column per delimiter.
+        let (_dir, pdf) = convert_text("table.md", markdown, &ConversionOptions::default());
+        assert_eq!(vertical_lines(&pdf).len(), 4);
+    }
+
+    #[test]
+    fn smart_punctuation_curls_quotes_and_makes_dashes() {
+        let markdown = "\"Double\" and 'single' quotes -- pages 1--2 --- done\n";
+        let paragraph = |options: &ConversionOptions| {
+            let mut text = InlineText::default();
+            for event in Parser::new_ext(markdown, markdown_parser_options(options)) {
+                text.push_event(&event);
+            }
+            text.text
+        };
+        let smart = ConversionOptions { smart_punctuation: true, ..ConversionOptions::default() };
+        let curled = "\u{201c}Double\u{201d} and \u{2018}single\u{2019} quotes \u{2013} pages 1\u{2013}2 \u{2014} done";
+        assert_eq!(paragraph(&smart), curled);
+
+        let verbatim = ConversionOptions { smart_punctuation: false, ..ConversionOptions::default() };
+        assert_eq!(paragraph(&verbatim), markdown.trim_end());
+    }
+
+    #[test]
+    fn code_span_inside_bold_stays_bold() {
+        let (segments, _) = inline_segments("**bold with `code` inside**");
+        assert_eq!(segments, [
+            run("bold with ", true, false, false, None),
+            run("code", true, false, true, None),
+            run(" inside", true, false, false, None),
+        ]);
+    }
+
+    #[test]
+    fn link_inside_italic_keeps_its_target() {
+        let (segments, urls) = inline_segments("*see [the docs](https://example.com) now*");
+        assert_eq!(segments, [
+            run("see ", false, true, false, None),
+            run("the docs", false, true, false, Some(0)),
+            run(" now", false, true, false, None),
+        ]);
+        assert_eq!(urls, ["https://example.com"]);
+    }
+
+    #[test]
+    fn triple_nested_emphasis_closes_in_order() {
+        let (segments, _) = inline_segments("*one **two *three* two** one* plain");
+        assert_eq!(segments, [
+            run("one ", false, true, false, None),
+            run("two three two", true, true, false, None),
+            run(" one", false, true, false, None),
+            run(" plain", false, false, false, None),
+        ]);
+    }
+
+    /// The blocks that `name` in `tests/fixtures` is laid out as.
+    fn fixture_blocks(name: &str, options: &ConversionOptions) -> Vec<Block> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name);
+        let input = load_input(&path, options).unwrap();
+        let (doc, _) = lay_out(input, &bundled_fonts(), options, None).unwrap();
+        doc.blocks().cloned().collect()
+    }
+
+    fn headings(blocks: &[Block]) -> Vec<(usize, &str)> {
+        blocks.iter()
+            .filter_map(|block| match block {
+                Block::Heading { level, text } => Some((*level, text.as_str())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn count(blocks: &[Block], matches: impl Fn(&Block) -> bool) -> usize {
+        blocks.iter().filter(|block| matches(block)).count()
+    }
+
+    #[test]
+    fn markdown_fixture_has_its_headings_code_and_table() {
+        let blocks = fixture_blocks("sample.md", &ConversionOptions::default());
+        assert_eq!(headings(&blocks), [(1, "Topdf"), (2, "Usage")]);
+        assert_eq!(count(&blocks, |b| matches!(b, Block::Paragraph(_))), 2);
+        assert!(blocks.contains(&Block::Paragraph("Converts documents to PDF.".to_string())));
+        assert_eq!(count(&blocks, |b| matches!(b, Block::Code(_))), 1);
+        assert!(blocks.contains(&Block::Table { rows: 3, alignments: vec![genpdf::Alignment::Left; 2] }));
+    }
+
+    #[test]
+    fn rst_fixture_has_the_headings_of_its_markdown_twin() {
+        let blocks = fixture_blocks("sample.rst", &ConversionOptions::default());
+        assert_eq!(headings(&blocks), [(1, "Topdf"), (2, "Usage")]);
+        assert!(blocks.contains(&Block::Paragraph("Start the server:".to_string())));
+        assert!(blocks.contains(&Block::Code("Topdf --serve".to_string())));
+    }
+
+    #[test]
+    fn json_fixture_is_a_code_line_per_tree_entry() {
+        let blocks = fixture_blocks("sample.json", &ConversionOptions::default());
+        let lines: Vec<_> = blocks.iter()
+            .filter_map(|block| match block {
+                Block::Code(line) => Some(line.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines.contains(&"├─ name: \"Topdf\""));
+        assert!(headings(&blocks).is_empty());
+    }
+
+    #[test]
+    fn csv_fixture_is_a_line_per_row_or_a_striped_table() {
+        let blocks = fixture_blocks("sample.csv", &ConversionOptions::default());
+        let rows: Vec<_> = blocks.iter().filter(|block| matches!(block, Block::Paragraph(_))).collect();
+        assert_eq!(rows, [
+            &Block::Paragraph("name | size".to_string()),
+            &Block::Paragraph("report.docx | 12".to_string()),
+            &Block::Paragraph("sheet.xlsx | 7".to_string()),
+        ]);
+
+        let options = ConversionOptions { table_stripes: true, ..ConversionOptions::default() };
+        let blocks = fixture_blocks("sample.csv", &options);
+        assert_eq!(count(&blocks, |b| matches!(b, Block::Table { .. })), 1);
+        assert!(blocks.contains(&Block::Table { rows: 3, alignments: vec![genpdf::Alignment::Left; 2] }));
+    }
+
+    #[test]
+    fn text_fixture_is_a_paragraph_per_line() {
+        let blocks = fixture_blocks("sample.txt", &ConversionOptions::default());
+        assert_eq!(blocks, [
+            Block::Paragraph("First line".to_string()),
+            Block::Paragraph(String::new()),
+            Block::Paragraph("Second line".to_string()),
+        ]);
+    }
+
+    /// The blocks that `content`, written to a file called `name`, is laid out as.
+    fn text_blocks(name: &str, content: &str, options: &ConversionOptions) -> Vec<Block> {
+        let dir = scratch::dir("test").unwrap();
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        let input = load_input(&path, options).unwrap();
+        let (doc, _) = lay_out(input, &bundled_fonts(), options, None).unwrap();
+        doc.blocks().cloned().collect()
+    }
+
+    /// The heading each page break of `blocks` is followed by.
+    fn slide_headings(blocks: &[Block]) -> Vec<(usize, &str)> {
+        blocks.windows(2)
+            .filter(|pair| pair[0] == Block::PageBreak)
+            .flat_map(|pair| headings(&pair[1..]))
+            .collect()
+    }
+
+    #[test]
+    fn presentation_starts_a_slide_at_each_top_level_heading() {
+        let markdown = "# Intro\n\nHello\n\n## Agenda\n\nItems\n\n### Detail\n\nMore\n\n# Summary\n\nBye\n";
+        let slides = ConversionOptions { presentation: true, ..ConversionOptions::default() };
+        let blocks = text_blocks("slides.md", markdown, &slides);
+        // The first heading is on the first page already, and H3 stays on its slide.
+        assert_eq!(slide_headings(&blocks), [(2, "Agenda"), (1, "Summary")]);
+        assert_ne!(blocks.first(), Some(&Block::PageBreak));
+
+        let blocks = text_blocks("slides.md", markdown, &ConversionOptions::default());
+        assert!(!blocks.contains(&Block::PageBreak));
+    }
+
+    #[test]
+    fn slides_get_larger_text_and_margins() {
+        let options = ConversionOptions::default();
+        let slides = options.for_slides();
+        assert_eq!(slides.font_size, options.font_size * 2);
+        assert!(slides.margins.left >= 30 && slides.margins.top >= 30);
+    }
+
+    #[test]
+    fn mermaid_blocks_show_the_diagram_drawn_while_loading() {
+        let markdown = "# Flow\n\n```mermaid\ngraph TD\n  A --> B\n```\n\n```Mermaid\ngraph TD\n  A --> B\n```\n\n```rust\nfn main() {}\n```\n";
+        assert_eq!(mermaid_sources(markdown), ["graph TD\n  A --> B\n"]);
+
+        let image = image_element(image::DynamicImage::new_rgb8(4, 4), 1.0).unwrap();
+        let input = LoadedInput {
+            path: PathBuf::from("flow.md"),
+            file_type: FileType::Markdown,
+            content: LoadedContent::Text(markdown.to_string()),
+            diagrams: vec![("graph TD\n  A --> B\n".to_string(), image)],
+        };
+        let options = ConversionOptions::default();
+        let (doc, _) = lay_out(input, &bundled_fonts(), &options, None).unwrap();
+        let blocks: Vec<_> = doc.blocks().cloned().collect();
+        assert_eq!(count(&blocks, |b| *b == Block::Image), 2);
+        assert_eq!(count(&blocks, |b| matches!(b, Block::Code(_))), 1);
     }
 }
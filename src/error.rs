@@ -0,0 +1,91 @@
+//! Why a conversion failed.
+//!
+//! The converter works with `anyhow` internally; [`ConversionError`] is what leaves
+//! [`crate::converter::convert`], so the UI can explain a failure in the user's language and
+//! group failures by cause.  Code that knows the cause of a failure (a panic, an encrypted file)
+//! returns a `ConversionError` wrapped in `anyhow`, and the classification keeps it.
+
+use std::fmt;
+
+use crate::converter::FileType;
+
+#[derive(Debug, Clone)]
+pub enum ConversionError {
+    /// Reading the input or writing the output failed.
+    Io(String),
+    /// The input is damaged or not what its extension claims.
+    Parse(FileType, String),
+    /// The file type, or the requested output for it, is not supported.
+    Unsupported(String),
+    /// The input, or the PDF to append to, is password protected.
+    Encrypted(String),
+    /// There was nothing to convert.
+    EmptyInput(String),
+    /// A conversion option has a value that cannot be used, such as a malformed page range.
+    InvalidOption(String),
+    /// A stage of the conversion panicked.
+    RenderPanic { stage: String, reason: String },
+    /// Laying out or post-processing the output failed for another reason.
+    Render(String),
+    /// Some entries of a ZIP archive failed; the others were converted.
+    Entries { failed: Vec<(String, ConversionError)>, total: usize },
+    /// The conversion was stopped before it finished.
+    Cancelled,
+    /// The conversion did not finish within this many seconds and was abandoned.
+    TimedOut(u64),
+}
+
+impl ConversionError {
+    /// Classifies a failure to read or parse an input of `file_type`.
+    pub fn while_loading(error: anyhow::Error, file_type: FileType) -> Self {
+        Self::classify(error).unwrap_or_else(|error| ConversionError::Parse(file_type, format!("{:#}", error)))
+    }
+
+    /// Classifies a failure to lay out, write or post-process an output.
+    pub fn while_writing(error: anyhow::Error) -> Self {
+        Self::classify(error).unwrap_or_else(|error| ConversionError::Render(format!("{:#}", error)))
+    }
+
+    /// Errors that already carry their cause, and I/O errors anywhere in the chain.  Invalid
+    /// data (such as text that is not UTF-8) is left to the caller, since it means the content
+    /// could not be parsed.
+    fn classify(error: anyhow::Error) -> Result<Self, anyhow::Error> {
+        let error = match error.downcast::<ConversionError>() {
+            Ok(cause) => return Ok(cause),
+            Err(error) => error,
+        };
+        let io = error.chain()
+            .filter_map(|cause| cause.downcast_ref::<std::io::Error>())
+            .any(|e| e.kind() != std::io::ErrorKind::InvalidData);
+        if io { Ok(ConversionError::Io(format!("{:#}", error))) } else { Err(error) }
+    }
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConversionError::Io(detail)
+            | ConversionError::Unsupported(detail)
+            | ConversionError::Encrypted(detail)
+            | ConversionError::EmptyInput(detail)
+            | ConversionError::InvalidOption(detail)
+            | ConversionError::Render(detail) => write!(f, "{}", detail),
+            ConversionError::Parse(file_type, detail) => write!(f, "Failed to parse {:?} input: {}", file_type, detail),
+            ConversionError::RenderPanic { stage, reason } => write!(f, "Crashed while {}: {}", stage, reason),
+            ConversionError::Entries { failed, total } => {
+                write!(f, "{} of {} entries failed: ", failed.len(), total)?;
+                for (i, (name, error)) in failed.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{}: {}", name, error)?;
+                }
+                Ok(())
+            }
+            ConversionError::Cancelled => write!(f, "Conversion was cancelled"),
+            ConversionError::TimedOut(secs) => write!(f, "Conversion did not finish within {} seconds", secs),
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
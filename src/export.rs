@@ -0,0 +1,172 @@
+//! Output formats other than PDF: plain text and PNG.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use genpdf::fonts::FontData;
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use rusttype::{point, Scale};
+
+use crate::converter::{self, FileType, LoadedContent, LoadedInput};
+use crate::error::ConversionError;
+
+/// Width of rendered text images: an A4 page at 150 dpi.
+const PNG_WIDTH: u32 = 1240;
+const PNG_MARGIN: u32 = 60;
+const PNG_FONT_PX: f32 = 24.0;
+const PNG_LINE_HEIGHT: f32 = PNG_FONT_PX * 1.4;
+/// Tall images are cut off here; most viewers refuse far larger ones.
+const PNG_MAX_HEIGHT: u32 = 32_000;
+
+/// The readable text of a loaded input, with line breaks between blocks and rows.
+pub fn plain_text(input: &LoadedInput) -> Result<String> {
+    Ok(match &input.content {
+        LoadedContent::Empty => String::new(),
+        LoadedContent::Text(text) => match input.file_type {
+            FileType::Markdown => markdown_text(text),
+            FileType::Html => html2text::from_read(text.as_bytes(), 80)
+                .context("Failed to extract text from HTML")?,
+            _ => text.clone(),
+        },
+        LoadedContent::Html { html, .. } => html2text::from_read(html.as_bytes(), 80)
+            .context("Failed to extract text from HTML")?,
+        LoadedContent::Table { header, rows } => header.iter().chain(rows)
+            .map(|row| row.join(" | "))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        LoadedContent::Docx(paragraphs) => paragraphs.iter()
+            .map(|p| p.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        LoadedContent::Email(email) => {
+            let mut text: String = email.headers.iter()
+                .map(|(label, value)| format!("{}: {}\n", label, value))
+                .collect();
+            text.push('\n');
+            if email.html {
+                text.push_str(&html2text::from_read(email.body.as_bytes(), 80).context("Failed to extract text from HTML")?);
+            } else {
+                text.push_str(&email.body);
+            }
+            if !email.attachments.is_empty() {
+                text.push_str(&format!("\n\nAttachments ({}):\n", email.attachments.len()));
+                for name in &email.attachments {
+                    text.push_str(&format!("- {}\n", name));
+                }
+            }
+            text
+        }
+        LoadedContent::Database(tables) => {
+            let mut text = String::new();
+            for table in tables {
+                text.push_str(&format!("== {} ({} rows) ==\n", table.name, table.total_rows));
+                let header: Vec<&str> = table.columns.iter().map(|(name, _)| name.as_str()).collect();
+                text.push_str(&header.join(" | "));
+                text.push('\n');
+                for row in &table.rows {
+                    text.push_str(&row.join(" | "));
+                    text.push('\n');
+                }
+                let hidden = table.total_rows.saturating_sub(table.rows.len() as u64);
+                if hidden > 0 {
+                    text.push_str(&format!("… {} more rows\n", hidden));
+                }
+                text.push('\n');
+            }
+            text
+        }
+        LoadedContent::Image { .. } => {
+            return Err(ConversionError::Unsupported("Images cannot be exported as text".to_string()).into());
+        }
+    })
+}
+
+fn markdown_text(content: &str) -> String {
+    let mut text = String::new();
+    for event in Parser::new_ext(content, Options::ENABLE_FOOTNOTES) {
+        match event {
+            Event::Text(t) | Event::Code(t) => text.push_str(&t),
+            Event::FootnoteReference(label) => text.push_str(&format!("[{}]", label)),
+            Event::Start(Tag::FootnoteDefinition(label)) => text.push_str(&format!("[{}] ", label)),
+            Event::SoftBreak => text.push(' '),
+            Event::HardBreak => text.push('\n'),
+            Event::End(TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::CodeBlock) => text.push_str("\n\n"),
+            Event::End(TagEnd::Item) => text.push('\n'),
+            _ => {}
+        }
+    }
+    text.trim_end().to_string() + "\n"
+}
+
+/// Saves an image input as PNG, without the alpha channel genpdf would reject elsewhere.
+pub fn reencode_image(input: &Path, output: &Path) -> Result<()> {
+    let image = image::open(input).context("Failed to decode image")?;
+    image.save_with_format(output, image::ImageFormat::Png).context("Failed to write PNG")?;
+    Ok(())
+}
+
+/// Draws `text` in black on a white page-wide image, wrapping long lines.
+pub fn render_text_png(text: &str, font: &FontData, tab_width: u8, output: &Path) -> Result<()> {
+    let cache = genpdf::fonts::FontCache::new(converter::font_family(font));
+    let font = cache.get_rt_font(cache.default_font_family().regular);
+    let scale = Scale::uniform(PNG_FONT_PX);
+    let max_width = (PNG_WIDTH - 2 * PNG_MARGIN) as f32;
+
+    let text = if text.trim().is_empty() { "（空文件）" } else { text };
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        wrap_line(font, scale, &converter::expand_tabs(line, tab_width), max_width, &mut lines);
+    }
+
+    let content_height = (lines.len() as f32 * PNG_LINE_HEIGHT).ceil() as u32;
+    let height = (content_height + 2 * PNG_MARGIN).min(PNG_MAX_HEIGHT);
+    if content_height + 2 * PNG_MARGIN > PNG_MAX_HEIGHT {
+        log::warn!("Text is too long for one image, cutting it off at {} pixels", PNG_MAX_HEIGHT);
+    }
+
+    let mut image = image::GrayImage::from_pixel(PNG_WIDTH, height, image::Luma([255]));
+    let ascent = font.v_metrics(scale).ascent;
+    for (i, line) in lines.iter().enumerate() {
+        let baseline = PNG_MARGIN as f32 + i as f32 * PNG_LINE_HEIGHT + ascent;
+        if baseline > (height - PNG_MARGIN) as f32 {
+            break;
+        }
+        for glyph in font.layout(line, scale, point(PNG_MARGIN as f32, baseline)) {
+            let Some(bounds) = glyph.pixel_bounding_box() else { continue };
+            glyph.draw(|x, y, coverage| {
+                let (px, py) = (bounds.min.x + x as i32, bounds.min.y + y as i32);
+                if px >= 0 && py >= 0 && (px as u32) < PNG_WIDTH && (py as u32) < height {
+                    let pixel = image.get_pixel_mut(px as u32, py as u32);
+                    pixel.0[0] = pixel.0[0].min((255.0 * (1.0 - coverage)) as u8);
+                }
+            });
+        }
+    }
+    image.save_with_format(output, image::ImageFormat::Png).context("Failed to write PNG")?;
+    Ok(())
+}
+
+/// Splits `line` so every piece fits `max_width`, preferring breaks at spaces.
+fn wrap_line(font: &rusttype::Font<'static>, scale: Scale, line: &str, max_width: f32, lines: &mut Vec<String>) {
+    let width = |s: &str| {
+        font.layout(s, scale, point(0.0, 0.0))
+            .last()
+            .map(|g| g.position().x + g.unpositioned().h_metrics().advance_width)
+            .unwrap_or(0.0)
+    };
+    let mut rest = line;
+    while width(rest) > max_width {
+        // The longest prefix that still fits, but always at least one character.
+        let mut end = rest.char_indices().nth(1).map(|(i, _)| i).unwrap_or(rest.len());
+        for (i, c) in rest.char_indices().skip(1) {
+            if width(&rest[..i + c.len_utf8()]) > max_width {
+                break;
+            }
+            end = i + c.len_utf8();
+        }
+        let split = rest[..end].rfind(' ').filter(|&i| i > 0).unwrap_or(end);
+        lines.push(rest[..split].to_string());
+        rest = rest[split..].trim_start();
+    }
+    lines.push(rest.to_string());
+}
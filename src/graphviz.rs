@@ -0,0 +1,56 @@
+//! Rendering Graphviz (`.dot`) graphs with the system's `dot` command.
+//!
+//! Graphviz is not bundled; when `dot` is not on the `PATH`, graphs are shown as source instead.
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+use anyhow::{bail, Context, Result};
+
+/// Resolution of the rendered image; it is shrunk to the page like any other image.
+const RENDER_DPI: u32 = 150;
+
+/// Whether the `dot` command can be run.  Checked once per run of the program.
+pub fn available() -> bool {
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| {
+        let found = Command::new("dot")
+            .arg("-V")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        if found {
+            log::info!("Graphviz found, .dot files will be rendered as diagrams");
+        } else {
+            log::warn!("Graphviz `dot` command not found, .dot files will be shown as source");
+        }
+        found
+    })
+}
+
+/// Lays out the graph in `input` and writes it to `output` as PNG.
+pub fn render_png(input: &Path, output: &Path) -> Result<()> {
+    let result = Command::new("dot")
+        .arg("-Tpng")
+        .arg(format!("-Gdpi={}", RENDER_DPI))
+        // Only a default: a graph that sets its own background keeps it.
+        .arg("-Gbgcolor=white")
+        .arg("-o")
+        .arg(output)
+        .arg(input)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .context("Failed to run Graphviz")?;
+    if !result.status.success() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        bail!("Graphviz could not render the graph ({}): {}", result.status, stderr.trim());
+    }
+    // Graphviz writes RGBA, and genpdf does not accept images with an alpha channel.
+    let image = image::open(output).context("Failed to read rendered graph")?;
+    image.to_rgb8().save_with_format(output, image::ImageFormat::Png).context("Failed to write rendered graph")?;
+    Ok(())
+}
@@ -0,0 +1,66 @@
+//! Content hashes of the files converted so far, so that a file added again shows whether it
+//! changed since its last conversion.
+//!
+//! Unlike the journal, the history outlives batches: an entry is written whenever a file is
+//! converted successfully and replaced the next time that file is converted.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Location of the history, next to the configuration file.
+pub const HISTORY_FILE: &str = "topdf_history.json";
+
+/// The content hash of each file at its last successful conversion.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct History {
+    files: HashMap<PathBuf, String>,
+}
+
+impl History {
+    /// Reads the history; a missing or unreadable file gives an empty one.
+    pub fn load() -> Self {
+        let Ok(content) = std::fs::read_to_string(HISTORY_FILE) else {
+            return Self::default();
+        };
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            log::warn!("Ignoring unreadable history {}: {}", HISTORY_FILE, e);
+            Self::default()
+        })
+    }
+
+    /// The hash `source` had when it was last converted.
+    pub fn last_hash(&self, source: &Path) -> Option<&str> {
+        self.files.get(source).map(String::as_str)
+    }
+
+    /// Records that `source` was converted with content `hash` and saves the history; failures
+    /// are only logged.
+    pub fn record(&mut self, source: &Path, hash: String) {
+        self.files.insert(source.to_path_buf(), hash);
+        let result = serde_json::to_string(self)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| std::fs::write(HISTORY_FILE, json).map_err(anyhow::Error::from));
+        if let Err(e) = result {
+            log::warn!("Failed to write history {}: {}", HISTORY_FILE, e);
+        }
+    }
+}
+
+/// The SHA-256 of the content of `path`, in hex.
+pub fn content_hash(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
@@ -0,0 +1,125 @@
+//! Local images of HTML pages.
+//!
+//! html2text prints an `<img>` as its alt text.  To print the image itself, [`image_sources`]
+//! lists the `src` of every `<img>`, and [`mark_images`] replaces the tags whose image was loaded
+//! with a placeholder that survives html2text, so the renderer can put each image where its
+//! placeholder ends up.
+
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+/// Placeholders are `MARK_START`, the image index and `MARK_END`; private-use characters do not
+/// occur in real text.
+const MARK_START: char = '\u{E000}';
+const MARK_END: char = '\u{E001}';
+
+fn img_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r#"(?is)<img\b[^>]*?\bsrc\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>]+))[^>]*>"#).expect("img pattern is valid")
+    })
+}
+
+fn marker_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new("\u{E000}([0-9]+)\u{E001}").expect("marker pattern is valid"))
+}
+
+/// The `<img>` tags of `html` with their `src`, in document order.
+fn image_tags(html: &str) -> Vec<(Range<usize>, String)> {
+    img_pattern()
+        .captures_iter(html)
+        .filter_map(|captures| {
+            let tag = captures.get(0)?;
+            let src = captures.get(1).or(captures.get(2)).or(captures.get(3))?;
+            Some((tag.range(), src.as_str().replace("&amp;", "&")))
+        })
+        .collect()
+}
+
+/// The `src` of every `<img>` in `html`, in document order.
+pub fn image_sources(html: &str) -> Vec<String> {
+    image_tags(html).into_iter().map(|(_, src)| src).collect()
+}
+
+/// Replaces the `<img>` tags whose index is set in `loaded` with placeholders for [`take_markers`].
+pub fn mark_images(html: &str, loaded: &[bool]) -> String {
+    let mut marked = String::with_capacity(html.len());
+    let mut start = 0;
+    for (index, (tag, _)) in image_tags(html).into_iter().enumerate() {
+        if loaded.get(index).copied().unwrap_or(false) {
+            marked.push_str(&html[start..tag.start]);
+            marked.push_str(&format!("{}{}{}", MARK_START, index, MARK_END));
+            start = tag.end;
+        }
+    }
+    marked.push_str(&html[start..]);
+    marked
+}
+
+/// `line` without its image placeholders, and the indices of the images they stood for.
+pub fn take_markers(line: &str) -> (String, Vec<usize>) {
+    if !line.contains(MARK_START) {
+        return (line.to_string(), Vec::new());
+    }
+    let indices = marker_pattern()
+        .captures_iter(line)
+        .filter_map(|captures| captures.get(1)?.as_str().parse().ok())
+        .collect();
+    (marker_pattern().replace_all(line, "").into_owned(), indices)
+}
+
+/// The file an image `src` refers to, relative paths taken from `base`.  Remote and inline
+/// (`data:`) images have none.
+pub fn resolve(src: &str, base: &Path) -> Option<PathBuf> {
+    let src = src.trim();
+    let path = match src.get(..7) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("file://") => {
+            let path = &src[7..];
+            // file:///C:/page/a.png names C:/page/a.png.
+            let drive = path.len() > 2 && path.starts_with('/') && path.as_bytes()[2] == b':';
+            if drive { &path[1..] } else { path }
+        }
+        _ => {
+            // A scheme has more than one letter, unlike a Windows drive.
+            let scheme = src.find(':').is_some_and(|colon| {
+                colon > 1 && src[..colon].chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+            });
+            if scheme || src.starts_with("//") || src.is_empty() {
+                log::debug!("Not a local image, leaving its alt text: {}", src);
+                return None;
+            }
+            src
+        }
+    };
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let path = PathBuf::from(percent_decode(path));
+    Some(if path.is_relative() { base.join(path) } else { path })
+}
+
+/// Decodes `%XX` escapes, which saved web pages use for spaces and non-ASCII names.
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escape {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
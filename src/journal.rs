@@ -0,0 +1,105 @@
+//! A record of the running batch, so a batch cut short by a crash or by closing the window can
+//! be resumed without converting finished files again.
+//!
+//! The journal is written when a batch starts, updated after each finished file and removed when
+//! the batch ends.  A journal left behind at startup therefore means the last batch never ended.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::converter::{ConversionOptions, OutputFormat};
+
+/// Location of the journal, next to the configuration file.
+pub const JOURNAL_FILE: &str = "topdf_journal.json";
+
+/// The queued files and settings of a batch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Journal {
+    pub output_dir: Option<PathBuf>,
+    pub options: ConversionOptions,
+    pub files: Vec<JournalEntry>,
+}
+
+/// One queued file and, once it has been converted, what it produced.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub source: PathBuf,
+    #[serde(default)]
+    pub zip_entries: Vec<(String, bool)>,
+    #[serde(default)]
+    pub format_override: Option<OutputFormat>,
+    /// The folder the file was added with, if it came from one.
+    #[serde(default)]
+    pub root: Option<PathBuf>,
+    #[serde(default)]
+    pub outputs: Vec<PathBuf>,
+    #[serde(default)]
+    pub done: bool,
+}
+
+impl Journal {
+    /// Reads the journal left by an unfinished batch, if there is one worth resuming.
+    pub fn load() -> Option<Self> {
+        let content = std::fs::read_to_string(JOURNAL_FILE).ok()?;
+        match serde_json::from_str::<Journal>(&content) {
+            Ok(journal) if journal.unfinished() > 0 => {
+                log::info!("Found journal of an interrupted batch ({} unfinished files)", journal.unfinished());
+                Some(journal)
+            }
+            Ok(_) => {
+                Self::remove();
+                None
+            }
+            Err(e) => {
+                log::warn!("Ignoring unreadable journal {}: {}", JOURNAL_FILE, e);
+                Self::remove();
+                None
+            }
+        }
+    }
+
+    /// Files that still need converting.
+    pub fn unfinished(&self) -> usize {
+        self.files.iter().filter(|f| f.needs_conversion()).count()
+    }
+
+    /// Writes the journal; failures are only logged, since the batch itself is unaffected.
+    pub fn save(&self) {
+        let result = serde_json::to_string_pretty(self)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| std::fs::write(JOURNAL_FILE, json).map_err(anyhow::Error::from));
+        if let Err(e) = result {
+            log::warn!("Failed to write journal {}: {}", JOURNAL_FILE, e);
+        }
+    }
+
+    /// Records that `source` was converted to `outputs` and saves the journal.
+    pub fn mark_done(&mut self, source: &Path, outputs: &[PathBuf]) {
+        if let Some(entry) = self.files.iter_mut().find(|f| f.source == source) {
+            entry.done = true;
+            entry.outputs = outputs.to_vec();
+            self.save();
+        }
+    }
+
+    pub fn remove() {
+        if let Err(e) = std::fs::remove_file(JOURNAL_FILE)
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            log::warn!("Failed to remove journal {}: {}", JOURNAL_FILE, e);
+        }
+    }
+}
+
+impl JournalEntry {
+    /// Whether the source still exists and lacks up-to-date outputs: either it was never
+    /// converted, or an output has since been deleted or is older than the source.
+    pub fn needs_conversion(&self) -> bool {
+        let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        let Some(source_modified) = modified(&self.source) else {
+            return false;
+        };
+        !(self.done && self.outputs.iter().all(|output| modified(output).is_some_and(|m| m >= source_modified)))
+    }
+}
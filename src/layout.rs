@@ -0,0 +1,287 @@
+//! Custom genpdf elements for layouts that genpdf does not provide out of the box.
+
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use genpdf::{render, style, Context, Element, Mm, Position, RenderResult, Size};
+use genpdf::error::Error;
+use unicode_bidi::{BidiInfo, ParagraphInfo};
+
+use crate::links;
+
+/// A paragraph whose lines are stretched to the full width of the area.
+///
+/// genpdf's `Paragraph` only supports left, center and right alignment.  This element wraps the
+/// text word by word and distributes the remaining width of each line across the gaps between the
+/// words.  The last line of the paragraph is printed with normal spacing.
+///
+/// Since it knows where each word goes, it also prints text with links (see [`links`]), then
+/// with any alignment.
+pub struct JustifiedParagraph {
+    /// Words of the paragraph; a word may consist of several differently styled pieces, each
+    /// with the index of the link it belongs to.
+    words: VecDeque<Vec<(style::StyledString, Option<usize>)>>,
+    /// `None` stretches the lines.
+    alignment: Option<genpdf::Alignment>,
+    /// Extra space before the first line; zero once the first line is printed.
+    first_line_indent: Mm,
+    style_applied: bool,
+}
+
+impl JustifiedParagraph {
+    /// Creates a paragraph from spans of which some are links, splitting them into words at
+    /// whitespace; justified if `alignment` is `None`.
+    pub fn from_linked_spans(spans: Vec<(style::StyledString, Option<usize>)>, alignment: Option<genpdf::Alignment>) -> Self {
+        let mut words = VecDeque::new();
+        let mut current = Vec::new();
+        for (span, link) in spans {
+            let mut piece = String::new();
+            for c in span.s.chars() {
+                if c.is_whitespace() {
+                    if !piece.is_empty() {
+                        current.push((style::StyledString::new(std::mem::take(&mut piece), span.style), link));
+                    }
+                    if !current.is_empty() {
+                        words.push_back(std::mem::take(&mut current));
+                    }
+                } else {
+                    piece.push(c);
+                }
+            }
+            if !piece.is_empty() {
+                current.push((style::StyledString::new(piece, span.style), link));
+            }
+        }
+        if !current.is_empty() {
+            words.push_back(current);
+        }
+        Self { words, alignment, first_line_indent: Mm::default(), style_applied: false }
+    }
+
+    /// Starts the first line `indent` in from the left edge.
+    pub fn with_first_line_indent(mut self, indent: Mm) -> Self {
+        self.first_line_indent = indent;
+        self
+    }
+
+    fn word_width(context: &Context, word: &[(style::StyledString, Option<usize>)]) -> Mm {
+        word.iter().map(|(s, _)| s.width(&context.font_cache)).sum()
+    }
+}
+
+impl Element for JustifiedParagraph {
+    fn render(
+        &mut self,
+        context: &Context,
+        mut area: render::Area<'_>,
+        style: style::Style,
+    ) -> Result<RenderResult, Error> {
+        if !self.style_applied {
+            for word in &mut self.words {
+                for (piece, _) in word {
+                    piece.style = style.and(piece.style);
+                }
+            }
+            self.style_applied = true;
+        }
+
+        let mut result = RenderResult::default();
+        let full_width = area.size().width;
+        let height = style.line_height(&context.font_cache);
+        let space = style.char_width(&context.font_cache, ' ');
+
+        while !self.words.is_empty() {
+            let indent = self.first_line_indent;
+            let width = full_width - indent;
+            // Greedily fill the line, always taking at least one word.
+            let mut count = 0;
+            let mut words_width = Mm::default();
+            for word in &self.words {
+                let w = Self::word_width(context, word);
+                if count > 0 && words_width + space * count as f64 + w > width {
+                    break;
+                }
+                words_width += w;
+                count += 1;
+            }
+
+            if area.text_section(&context.font_cache, Position::default(), style).is_none() {
+                result.has_more = true;
+                break;
+            }
+
+            let is_last = count == self.words.len();
+            let gap = if is_last || count == 1 || self.alignment.is_some() {
+                space
+            } else {
+                (width - words_width) / (count - 1) as f64
+            };
+
+            let line_width = words_width + gap * (count - 1) as f64;
+            let mut x = indent + match self.alignment {
+                Some(genpdf::Alignment::Center) => (width - line_width) / 2.0,
+                Some(genpdf::Alignment::Right) => width - line_width,
+                _ => Mm::default(),
+            };
+            self.first_line_indent = Mm::default();
+            for word in self.words.drain(..count) {
+                let w = Self::word_width(context, &word);
+                if let Some(mut section) = area.text_section(&context.font_cache, Position::new(x, 0), style) {
+                    for (piece, _) in &word {
+                        section.print_str(&piece.s, piece.style)?;
+                    }
+                }
+                let mut piece_x = x;
+                for (piece, link) in &word {
+                    let piece_width = piece.width(&context.font_cache);
+                    if let Some(index) = link {
+                        let corners = vec![Position::new(piece_x, 0), Position::new(piece_x + piece_width, height)];
+                        area.draw_line(corners, links::marker_style(*index));
+                    }
+                    piece_x += piece_width;
+                }
+                x += w + gap;
+            }
+
+            result.size = result.size.stack_vertical(Size::new(full_width, height));
+            area.add_offset(Position::new(0, height));
+        }
+
+        Ok(result)
+    }
+}
+
+/// A paragraph with right-to-left text, wrapped in logical order and reordered line by line.
+///
+/// genpdf's `Paragraph` wraps the text it is given as it is, so reordering a whole paragraph
+/// before wrapping it would print the lines of a long right-to-left paragraph bottom to top.
+/// This element breaks the logical text into lines first and then reorders each line with the
+/// Unicode bidirectional algorithm.
+pub struct BidiParagraph {
+    text: String,
+    /// The style of each span of `text`, in order, such as the fallback font of characters the
+    /// main font lacks.
+    styles: Vec<(Range<usize>, style::Style)>,
+    /// The words of `text` not printed yet, in logical order.
+    words: VecDeque<Range<usize>>,
+    alignment: genpdf::Alignment,
+}
+
+impl BidiParagraph {
+    pub fn new(spans: Vec<style::StyledString>, alignment: genpdf::Alignment) -> Self {
+        let mut text = String::new();
+        let mut styles = Vec::new();
+        for span in spans {
+            let start = text.len();
+            text.push_str(&span.s);
+            styles.push((start..text.len(), span.style));
+        }
+        let mut words = VecDeque::new();
+        let mut start = None;
+        for (i, c) in text.char_indices() {
+            match (c.is_whitespace(), start) {
+                (true, Some(s)) => {
+                    words.push_back(s..i);
+                    start = None;
+                }
+                (false, None) => start = Some(i),
+                _ => {}
+            }
+        }
+        if let Some(s) = start {
+            words.push_back(s..text.len());
+        }
+        Self { text, styles, words, alignment }
+    }
+
+    /// The text of `range` split at the spans it crosses, each with its style on top of `style`.
+    fn pieces(&self, range: Range<usize>, style: style::Style) -> impl Iterator<Item = (&str, style::Style)> + '_ {
+        self.styles.iter()
+            .filter(move |(span, _)| span.start < range.end && range.start < span.end)
+            .map(move |(span, span_style)| (&self.text[span.start.max(range.start)..span.end.min(range.end)], style.and(*span_style)))
+    }
+
+    fn width(&self, context: &Context, range: Range<usize>, style: style::Style) -> Mm {
+        self.pieces(range, style).map(|(s, style)| style.str_width(&context.font_cache, s)).sum()
+    }
+
+    /// The pieces of the logical `line` in visual order, each with its style.
+    fn visual_pieces(&self, info: &BidiInfo<'_>, para: &ParagraphInfo, line: Range<usize>, style: style::Style) -> Vec<(String, style::Style)> {
+        let (levels, runs) = info.visual_runs(para, line);
+        let mut pieces: Vec<(String, style::Style)> = Vec::new();
+        for run in runs {
+            let mut chars: Vec<(usize, char)> = self.text[run.clone()].char_indices().map(|(i, c)| (run.start + i, c)).collect();
+            if levels[run.start].is_rtl() {
+                chars.reverse();
+            }
+            for (i, c) in chars {
+                let span = self.styles.partition_point(|(span, _)| span.end <= i);
+                let char_style = style.and(self.styles[span].1);
+                match pieces.last_mut() {
+                    Some((s, last)) if *last == char_style => s.push(c),
+                    _ => pieces.push((c.to_string(), char_style)),
+                }
+            }
+        }
+        pieces
+    }
+}
+
+impl Element for BidiParagraph {
+    fn render(
+        &mut self,
+        context: &Context,
+        mut area: render::Area<'_>,
+        style: style::Style,
+    ) -> Result<RenderResult, Error> {
+        let mut result = RenderResult::default();
+        let width = area.size().width;
+        let height = style.line_height(&context.font_cache);
+        let info = BidiInfo::new(&self.text, None);
+
+        while let Some(first) = self.words.front().cloned() {
+            let Some(para) = info.paragraphs.iter().find(|para| para.range.contains(&first.start)) else {
+                break;
+            };
+            // Greedily fill the line in logical order, always taking at least one word and
+            // never running on into the next bidi paragraph.
+            let mut count = 1;
+            let mut line_width = self.width(context, first.clone(), style);
+            for word in self.words.iter().skip(1) {
+                if !para.range.contains(&word.start) {
+                    break;
+                }
+                let end = self.words[count - 1].end;
+                let w = self.width(context, end..word.end, style);
+                if line_width + w > width {
+                    break;
+                }
+                line_width += w;
+                count += 1;
+            }
+
+            if area.text_section(&context.font_cache, Position::default(), style).is_none() {
+                result.has_more = true;
+                break;
+            }
+
+            let line = first.start..self.words[count - 1].end;
+            let x = match self.alignment {
+                genpdf::Alignment::Center => (width - line_width) / 2.0,
+                genpdf::Alignment::Right => width - line_width,
+                genpdf::Alignment::Left => Mm::default(),
+            };
+            if let Some(mut section) = area.text_section(&context.font_cache, Position::new(x, 0), style) {
+                for (s, piece_style) in self.visual_pieces(&info, para, line, style) {
+                    section.print_str(&s, piece_style)?;
+                }
+            }
+            self.words.drain(..count);
+
+            result.size = result.size.stack_vertical(Size::new(width, height));
+            area.add_offset(Position::new(0, height));
+        }
+
+        Ok(result)
+    }
+}
@@ -0,0 +1,181 @@
+//! Clickable URLs in plain text.
+//!
+//! genpdf cannot write link annotations and does not say where it placed a piece of text.  So
+//! [`crate::layout::JustifiedParagraph`] follows every printed link with a stroke in a reserved
+//! color that encodes the link's index, and [`annotate`] later replaces those strokes with link
+//! annotations covering the same rectangles.
+
+use std::ops::Range;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result};
+use genpdf::style;
+use linkify::{LinkFinder, LinkKind};
+use lopdf::content::{Content, Operation};
+use lopdf::{Dictionary, Object, StringFormat};
+
+/// Color of link text.
+pub const LINK_COLOR: style::Color = style::Color::Rgb(0x1e, 0x66, 0xc8);
+
+/// printpdf writes color components with two decimals, so a component can carry one of 101
+/// values.  Marker strokes have full red; green and blue are the two base-100 digits of the
+/// link index.
+const MARKER_LEVELS: usize = 100;
+
+/// Links beyond this many in one document are printed as plain text.
+pub const MAX_LINKS: usize = MARKER_LEVELS * MARKER_LEVELS;
+
+/// What a URL starts with, in any case.  Other schemes, and bare domains like `readme.md`,
+/// stay plain text.
+const URL_PREFIXES: &[&str] = &["http://", "https://", "ftp://", "www."];
+
+fn url_finder() -> &'static LinkFinder {
+    static FINDER: OnceLock<LinkFinder> = OnceLock::new();
+    FINDER.get_or_init(|| {
+        let mut finder = LinkFinder::new();
+        // Without a scheme for `www.` addresses; the prefixes above filter out the rest.
+        finder.kinds(&[LinkKind::Url]).url_must_have_scheme(false);
+        finder
+    })
+}
+
+/// Byte ranges of the URLs in `line`.  [`linkify`] decides where each ends, leaving out
+/// trailing punctuation and brackets that the URL did not open.
+pub fn find_urls(line: &str) -> Vec<Range<usize>> {
+    url_finder()
+        .links(line)
+        .filter(|link| {
+            URL_PREFIXES.iter().any(|prefix| link.as_str().get(..prefix.len()).is_some_and(|start| start.eq_ignore_ascii_case(prefix)))
+        })
+        .map(|link| link.start()..link.end())
+        .collect()
+}
+
+/// The URI a detected URL opens; `www.` addresses get an `http://` scheme.  PDF URIs are
+/// 7-bit ASCII, so other characters are percent-encoded as UTF-8.
+pub fn href(url: &str) -> String {
+    let scheme = if url.get(..4).is_some_and(|prefix| prefix.eq_ignore_ascii_case("www.")) { "http://" } else { "" };
+    let mut uri = String::from(scheme);
+    for byte in url.bytes() {
+        if byte.is_ascii() {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    uri
+}
+
+/// Stroke style that marks the rectangle of link `index`.
+pub fn marker_style(index: usize) -> style::Style {
+    let level = |digit: usize| (digit as f64 * 255.0 / MARKER_LEVELS as f64).round() as u8;
+    style::Style::new().with_color(style::Color::Rgb(255, level(index / MARKER_LEVELS), level(index % MARKER_LEVELS)))
+}
+
+/// The link index a stroke color (`RG` operands) encodes, if it is a marker.
+fn marker_index(operands: &[Object]) -> Option<usize> {
+    let levels: Vec<usize> = operands.iter()
+        .map(|o| number(o).map(|c| (c * MARKER_LEVELS as f64).round().clamp(0.0, MARKER_LEVELS as f64) as usize))
+        .collect::<Option<_>>()?;
+    match levels.as_slice() {
+        &[MARKER_LEVELS, high, low] if high < MARKER_LEVELS && low < MARKER_LEVELS => Some(high * MARKER_LEVELS + low),
+        _ => None,
+    }
+}
+
+fn number(object: &Object) -> Option<f64> {
+    object.as_f64().ok().or_else(|| object.as_i64().ok().map(|i| i as f64))
+}
+
+/// Replaces the marker strokes in the PDF at `path` with annotations that open `urls`, indexed
+/// by the marker color.
+pub fn annotate(path: &Path, urls: &[String]) -> Result<()> {
+    let mut pdf = lopdf::Document::load(path).context("Failed to reopen PDF for link annotations")?;
+    let pages: Vec<_> = pdf.page_iter().collect();
+    let mut annotated = 0;
+    for page in pages {
+        let content = Content::decode(&pdf.get_page_content(page)?).context("Failed to read page content")?;
+        let mut operations = Vec::with_capacity(content.operations.len());
+        let mut links = Vec::new();
+        let mut ops = content.operations.into_iter().peekable();
+        while let Some(op) = ops.next() {
+            let index = (op.operator == "RG").then(|| marker_index(&op.operands)).flatten();
+            let Some(index) = index else {
+                operations.push(op);
+                continue;
+            };
+            // draw_line writes `m`, `l` and `S` after the color.
+            let path: Vec<Operation> = std::iter::from_fn(|| ops.next_if(|op| matches!(op.operator.as_str(), "m" | "l" | "S"))).collect();
+            let points: Vec<(f64, f64)> = path.iter()
+                .filter(|op| op.operator != "S")
+                .filter_map(|op| Some((number(op.operands.first()?)?, number(op.operands.get(1)?)?)))
+                .collect();
+            if let (Some(url), [(x0, y0), (x1, y1)]) = (urls.get(index), points.as_slice()) {
+                links.push(link_annotation(url, [x0.min(*x1), y0.min(*y1), x0.max(*x1), y0.max(*y1)]));
+            }
+        }
+        if links.is_empty() {
+            continue;
+        }
+        annotated += links.len();
+        pdf.change_page_content(page, Content { operations }.encode()?)?;
+        let links: Vec<Object> = links.into_iter().map(|link| Object::Reference(pdf.add_object(link))).collect();
+        let dict = pdf.get_object_mut(page).and_then(Object::as_dict_mut)?;
+        match dict.get_mut(b"Annots").and_then(Object::as_array_mut) {
+            Ok(annots) => annots.extend(links),
+            Err(_) => dict.set("Annots", links),
+        }
+    }
+    pdf.save(path).context("Failed to save PDF with links")?;
+    log::info!("Added {} link annotations to {:?}", annotated, path);
+    Ok(())
+}
+
+fn link_annotation(url: &str, rect: [f64; 4]) -> Dictionary {
+    let mut action = Dictionary::new();
+    action.set("S", Object::Name(b"URI".to_vec()));
+    action.set("URI", Object::String(href(url).into_bytes(), StringFormat::Literal));
+    let mut annotation = Dictionary::new();
+    annotation.set("Type", Object::Name(b"Annot".to_vec()));
+    annotation.set("Subtype", Object::Name(b"Link".to_vec()));
+    annotation.set("Rect", rect.iter().map(|&c| Object::Real(c as _)).collect::<Vec<_>>());
+    annotation.set("Border", vec![Object::Integer(0), Object::Integer(0), Object::Integer(0)]);
+    // Printable, which PDF/A requires of every annotation.
+    annotation.set("F", Object::Integer(4));
+    annotation.set("A", action);
+    annotation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls(line: &str) -> Vec<&str> {
+        find_urls(line).into_iter().map(|range| &line[range]).collect()
+    }
+
+    #[test]
+    fn trailing_punctuation_ends_the_url() {
+        assert_eq!(urls("See https://example.com/docs."), ["https://example.com/docs"]);
+        assert_eq!(urls("Try http://example.com, or ftp://example.com/file!"), ["http://example.com", "ftp://example.com/file"]);
+    }
+
+    #[test]
+    fn brackets_belong_to_the_url_only_if_it_opened_them() {
+        assert_eq!(urls("(see https://example.com/a)"), ["https://example.com/a"]);
+        assert_eq!(urls("https://en.wikipedia.org/wiki/Rust_(language)"), ["https://en.wikipedia.org/wiki/Rust_(language)"]);
+    }
+
+    #[test]
+    fn www_addresses_need_no_scheme() {
+        assert_eq!(urls("Visit WWW.example.com today"), ["WWW.example.com"]);
+        assert_eq!(href("www.example.com"), "http://www.example.com");
+    }
+
+    #[test]
+    fn bare_domains_and_other_schemes_stay_text() {
+        assert!(urls("Read README.md and mail me@example.com").is_empty());
+        assert!(urls("file:///etc/hosts and http://").is_empty());
+    }
+}
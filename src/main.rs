@@ -1,23 +1,106 @@
+mod blocks;
+mod clipboard;
+mod config;
 mod converter;
+mod error;
+mod export;
+mod graphviz;
+mod history;
+mod html;
+mod journal;
+mod layout;
+mod links;
+mod markdown;
+mod merge;
+mod mermaid;
+mod org;
+mod page_template;
+mod pdfa;
+mod pipeline;
+mod ranges;
+mod redact;
+mod report;
+mod rst;
+mod scratch;
+#[cfg(feature = "serve")]
+mod serve;
+mod shading;
+mod stats;
+mod styles;
+mod subtitle;
+mod tree;
 mod ui;
 
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
 use iced::Result;
 use ui::App;
 
+/// Directory the log files are written to, relative to the working directory.
+pub const LOG_DIR: &str = "logs";
+
+/// The log file of this run, once the logger is set up.
+pub static LOG_FILE: OnceLock<PathBuf> = OnceLock::new();
+
 fn main() -> Result {
     if let Err(e) = setup_logger() {
         eprintln!("Failed to initialize logger: {}", e);
     }
     log::info!("Application started");
+    let config = config::AppConfig::load();
+    scratch::configure(config.temp_dir.clone());
+    // Removes the intermediate files of this run once the window closes.
+    let scratch = scratch::RunGuard::new();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--serve") {
+        serve(&args, scratch);
+    }
+
+    let mut app = iced::application(App::new, App::update, App::view)
+        .subscription(App::subscription);
+    // Without a Chinese font the labels render as boxes on systems whose default font lacks CJK.
+    if let Some((bytes, family)) = ui::load_ui_font(&config.fonts) {
+        // iced refers to fonts by a static name for the whole run.
+        let family: &'static str = Box::leak(family.into_boxed_str());
+        app = app.font(bytes).default_font(iced::Font::with_name(family));
+    }
+    app.run()
+}
+
+/// Runs the local conversion service for `--serve [--port N]` instead of the window, then exits.
+#[cfg(feature = "serve")]
+fn serve(args: &[String], scratch: scratch::RunGuard) -> ! {
+    let port = match args.iter().position(|arg| arg == "--port") {
+        None => serve::DEFAULT_PORT,
+        Some(i) => match args.get(i + 1).and_then(|port| port.parse().ok()) {
+            Some(port) => port,
+            None => {
+                eprintln!("--port needs a port number");
+                std::process::exit(2);
+            }
+        },
+    };
+    // `exit` skips destructors, so the guard is dropped by hand first.
+    let result = serve::run(port);
+    drop(scratch);
+    if let Err(e) = result {
+        log::error!("Conversion service stopped: {:#}", e);
+        std::process::exit(1);
+    }
+    std::process::exit(0)
+}
 
-    iced::application(App::new, App::update, App::view)
-        .run()
+#[cfg(not(feature = "serve"))]
+fn serve(_args: &[String], _scratch: scratch::RunGuard) -> ! {
+    eprintln!("This build of Topdf has no conversion service; build it with `--features serve`");
+    std::process::exit(2)
 }
 
 fn setup_logger() -> std::result::Result<(), Box<dyn std::error::Error>> {
     let now = chrono::Local::now();
-    std::fs::create_dir_all("logs")?;
-    let log_filename = format!("logs/topdf_{}.log", now.format("%Y-%m-%d_%H-%M-%S"));
+    std::fs::create_dir_all(LOG_DIR)?;
+    let log_filename = format!("{}/topdf_{}.log", LOG_DIR, now.format("%Y-%m-%d_%H-%M-%S"));
 
     fern::Dispatch::new()
         .format(|out, message, record| {
@@ -31,7 +114,8 @@ fn setup_logger() -> std::result::Result<(), Box<dyn std::error::Error>> {
         })
         .level(log::LevelFilter::Info)
         .chain(std::io::stdout())
-        .chain(fern::log_file(log_filename)?)
+        .chain(fern::log_file(&log_filename)?)
         .apply()?;
+    let _ = LOG_FILE.set(PathBuf::from(log_filename));
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,352 @@
+//! Markdown output for DOCX and HTML documents.
+//!
+//! Only inputs whose structure is parsed are supported: DOCX headings and list items from
+//! [`DocxParagraph`], and the headings, lists, tables, emphasis, links and images of an HTML
+//! page, walked in the DOM html2text parses.  Everything else is refused by
+//! [`OutputFormat::applies_to`](crate::converter::OutputFormat::applies_to).
+
+use anyhow::{Context, Result};
+use html2text::{Comment, Document, Element, Handle};
+
+use crate::converter::{DocxParagraph, LoadedContent, LoadedInput};
+use crate::error::ConversionError;
+
+/// `input` as a Markdown document.
+pub fn document(input: &LoadedInput) -> Result<String> {
+    let markdown = match &input.content {
+        LoadedContent::Empty => String::new(),
+        LoadedContent::Docx(paragraphs) => from_docx(paragraphs),
+        LoadedContent::Html { html, .. } => from_html(html)?,
+        _ => {
+            return Err(ConversionError::Unsupported("Only DOCX and HTML files can be converted to Markdown".to_string()).into());
+        }
+    };
+    Ok(markdown.trim_end().to_string() + "\n")
+}
+
+fn from_docx(paragraphs: &[DocxParagraph]) -> String {
+    let mut blocks: Vec<String> = Vec::new();
+    // Consecutive list items form one block, numbered per level.
+    let mut list: Vec<String> = Vec::new();
+    let mut numbers: Vec<usize> = Vec::new();
+    for paragraph in paragraphs {
+        let text: String = paragraph.segments().into_iter()
+            .map(|(text, url)| match url {
+                Some(url) => format!("[{}](<{}>)", escape(text), url),
+                None => escape(text),
+            })
+            .collect();
+        let text = text.trim();
+        match paragraph.list {
+            Some(item) if paragraph.heading.is_none() => {
+                numbers.resize(item.level + 1, 0);
+                numbers[item.level] += 1;
+                let marker = if item.ordered { format!("{}.", numbers[item.level]) } else { "-".to_string() };
+                list.push(format!("{}{} {}", "    ".repeat(item.level), marker, text));
+                continue;
+            }
+            _ => {}
+        }
+        if !list.is_empty() {
+            blocks.push(list.join("\n"));
+            list.clear();
+            numbers.clear();
+        }
+        if text.is_empty() {
+            continue;
+        }
+        match paragraph.heading {
+            Some(level) => blocks.push(format!("{} {}", "#".repeat(level.clamp(1, 6)), text)),
+            None => blocks.push(text.to_string()),
+        }
+    }
+    if !list.is_empty() {
+        blocks.push(list.join("\n"));
+    }
+    blocks.join("\n\n")
+}
+
+fn from_html(html: &str) -> Result<String> {
+    let dom = html2text::config::plain().parse_html(html.as_bytes()).context("Failed to parse HTML")?;
+    let mut blocks = Vec::new();
+    container(&dom.document, &mut blocks);
+    Ok(blocks.join("\n\n"))
+}
+
+/// The local name of an element, or `None` for other nodes.
+fn tag(node: &Handle) -> Option<&str> {
+    match &node.data {
+        Element { name, .. } => Some(&*name.local),
+        _ => None,
+    }
+}
+
+fn attribute(node: &Handle, name: &str) -> Option<String> {
+    match &node.data {
+        Element { attrs, .. } => attrs.borrow().iter().find(|a| &*a.name.local == name).map(|a| a.value.to_string()),
+        _ => None,
+    }
+}
+
+/// The text of a text node, `None` for other nodes.
+///
+/// html2text does not export the `Text` variant of its node data, so the text is taken from
+/// the node's HTML serialization, which escapes only these four characters in text.  A doctype
+/// or processing instruction serializes to a tag, which no escaped text starts with.
+fn text_of(node: &Handle) -> Option<String> {
+    if matches!(node.data, Element { .. } | Comment { .. } | Document) {
+        return None;
+    }
+    let mut serialized = Vec::new();
+    node.serialize(&mut serialized).ok()?;
+    let serialized = String::from_utf8_lossy(&serialized);
+    (!serialized.starts_with('<')).then(|| {
+        serialized.replace("&lt;", "<").replace("&gt;", ">").replace("&nbsp;", "\u{a0}").replace("&amp;", "&")
+    })
+}
+
+/// Elements that are not printed at all.
+fn is_hidden(tag: &str) -> bool {
+    matches!(tag, "head" | "script" | "style" | "template" | "noscript" | "iframe" | "object" | "svg")
+}
+
+fn is_block(tag: &str) -> bool {
+    matches!(
+        tag,
+        "html" | "body" | "main" | "article" | "section" | "header" | "footer" | "nav" | "aside" | "div"
+            | "p" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "ul" | "ol" | "li" | "table" | "pre"
+            | "blockquote" | "hr" | "figure" | "figcaption" | "dl" | "dt" | "dd" | "form" | "fieldset"
+            | "address" | "details" | "summary"
+    )
+}
+
+/// Appends the blocks inside `node`; runs of inline content between blocks are paragraphs.
+fn container(node: &Handle, blocks: &mut Vec<String>) {
+    let mut run = String::new();
+    let flush = |run: &mut String, blocks: &mut Vec<String>| {
+        let paragraph = normalize(run);
+        if !paragraph.is_empty() {
+            blocks.push(paragraph);
+        }
+        run.clear();
+    };
+    for child in node.children.borrow().iter() {
+        match tag(child) {
+            Some(name) if is_hidden(name) => {}
+            Some(name) if is_block(name) => {
+                flush(&mut run, blocks);
+                block(child, name, blocks);
+            }
+            _ => run.push_str(&inline(child)),
+        }
+    }
+    flush(&mut run, blocks);
+}
+
+fn block(node: &Handle, name: &str, blocks: &mut Vec<String>) {
+    match name {
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+            let text = normalize(&inline_children(node));
+            if !text.is_empty() {
+                let level = name[1..].parse().unwrap_or(1);
+                blocks.push(format!("{} {}", "#".repeat(level), text));
+            }
+        }
+        "ul" | "ol" => {
+            let mut lines = Vec::new();
+            list(node, name == "ol", "", &mut lines);
+            if !lines.is_empty() {
+                blocks.push(lines.join("\n"));
+            }
+        }
+        "table" => {
+            if let Some(table) = table(node) {
+                blocks.push(table);
+            }
+        }
+        "pre" => {
+            let code = raw_text(node);
+            let code = code.trim_matches('\n');
+            if !code.trim().is_empty() {
+                let fence = if code.contains("```") { "~~~~" } else { "```" };
+                blocks.push(format!("{}\n{}\n{}", fence, code, fence));
+            }
+        }
+        "blockquote" => {
+            let mut quoted = Vec::new();
+            container(node, &mut quoted);
+            if !quoted.is_empty() {
+                let text = quoted.join("\n\n");
+                blocks.push(text.lines().map(|line| if line.is_empty() { ">".to_string() } else { format!("> {}", line) }).collect::<Vec<_>>().join("\n"));
+            }
+        }
+        "hr" => blocks.push("---".to_string()),
+        _ => container(node, blocks),
+    }
+}
+
+/// Appends the items of a list as lines, nested lists indented under their item.
+fn list(node: &Handle, ordered: bool, indent: &str, lines: &mut Vec<String>) {
+    let start = attribute(node, "start").and_then(|s| s.trim().parse::<i64>().ok()).unwrap_or(1);
+    let items = node.children.borrow();
+    for (number, item) in (start..).zip(items.iter().filter(|child| tag(child) == Some("li"))) {
+        let marker = if ordered { format!("{}.", number) } else { "-".to_string() };
+        let nested_indent = format!("{}{}", indent, " ".repeat(marker.len() + 1));
+        let mut text = String::new();
+        let mut nested = Vec::new();
+        for child in item.children.borrow().iter() {
+            match tag(child) {
+                Some(name @ ("ul" | "ol")) => list(child, name == "ol", &nested_indent, &mut nested),
+                Some(name) if is_hidden(name) => {}
+                Some(name) if is_block(name) => {
+                    text.push(' ');
+                    text.push_str(&inline_children(child));
+                    text.push(' ');
+                }
+                _ => text.push_str(&inline(child)),
+            }
+        }
+        lines.push(format!("{}{} {}", indent, marker, normalize(&text)).trim_end().to_string());
+        lines.append(&mut nested);
+    }
+}
+
+/// A pipe table of the rows of `node`, the first row as the header.
+fn table(node: &Handle) -> Option<String> {
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    collect_rows(node, &mut rows);
+    let columns = rows.iter().map(Vec::len).max().filter(|&n| n > 0)?;
+    let line = |cells: &[String]| -> String {
+        let cells: Vec<&str> = (0..columns).map(|i| cells.get(i).map_or("", String::as_str)).collect();
+        format!("| {} |", cells.join(" | "))
+    };
+    let mut lines = vec![line(&rows[0]), format!("|{}", " --- |".repeat(columns))];
+    lines.extend(rows[1..].iter().map(|row| line(row)));
+    Some(lines.join("\n"))
+}
+
+/// The rows of a table, without descending into tables nested in its cells.
+fn collect_rows(node: &Handle, rows: &mut Vec<Vec<String>>) {
+    for child in node.children.borrow().iter() {
+        match tag(child) {
+            Some("thead" | "tbody" | "tfoot") => collect_rows(child, rows),
+            Some("tr") => rows.push(
+                child.children.borrow().iter()
+                    .filter(|cell| matches!(tag(cell), Some("td" | "th")))
+                    .map(|cell| normalize(&inline_children(cell)).replace('|', "\\|").replace("\\\n", "<br>"))
+                    .collect(),
+            ),
+            _ => {}
+        }
+    }
+}
+
+fn inline_children(node: &Handle) -> String {
+    node.children.borrow().iter().map(inline).collect()
+}
+
+/// Inline Markdown for `node`, with the whitespace of text collapsed.
+fn inline(node: &Handle) -> String {
+    if let Some(text) = text_of(node) {
+        return escape(&collapse_whitespace(&text));
+    }
+    let Some(name) = tag(node) else { return String::new() };
+    match name {
+        _ if is_hidden(name) => String::new(),
+        "strong" | "b" => wrap(&inline_children(node), "**"),
+        "em" | "i" => wrap(&inline_children(node), "*"),
+        "del" | "s" | "strike" => wrap(&inline_children(node), "~~"),
+        "code" | "kbd" | "samp" => {
+            let code = collapse_whitespace(&raw_text(node));
+            let code = code.trim();
+            if code.is_empty() {
+                String::new()
+            } else if code.contains('`') {
+                format!("`` {} ``", code)
+            } else {
+                format!("`{}`", code)
+            }
+        }
+        "a" => {
+            let text = normalize(&inline_children(node));
+            match attribute(node, "href").filter(|href| !href.trim().is_empty()) {
+                Some(href) if !text.is_empty() => format!("[{}]({})", text, href.trim().replace(' ', "%20")),
+                _ => text,
+            }
+        }
+        "img" => match attribute(node, "src").filter(|src| !src.trim().is_empty()) {
+            Some(src) => format!("![{}]({})", escape(&attribute(node, "alt").unwrap_or_default()), src.trim().replace(' ', "%20")),
+            None => String::new(),
+        },
+        "br" => "\\\n".to_string(),
+        // Block elements inside inline content, such as a paragraph in a table cell.
+        _ if is_block(name) => format!(" {} ", inline_children(node)),
+        _ => inline_children(node),
+    }
+}
+
+/// The text inside `node` as written, for preformatted text and code.
+fn raw_text(node: &Handle) -> String {
+    if let Some(text) = text_of(node) {
+        return text;
+    }
+    match tag(node) {
+        Some("br") => "\n".to_string(),
+        Some(name) if is_hidden(name) => String::new(),
+        _ => node.children.borrow().iter().map(raw_text).collect(),
+    }
+}
+
+/// `text` between `marker`s, with the spaces around it kept outside, which emphasis requires.
+fn wrap(text: &str, marker: &str) -> String {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return text.to_string();
+    }
+    let leading = if text.starts_with(char::is_whitespace) { " " } else { "" };
+    let trailing = if text.ends_with(char::is_whitespace) { " " } else { "" };
+    format!("{}{}{}{}{}", leading, marker, trimmed, marker, trailing)
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut space = false;
+    for c in text.chars() {
+        // Non-breaking spaces are kept: they are there to hold words together.
+        if c.is_whitespace() && c != '\u{a0}' {
+            space = true;
+        } else {
+            if space {
+                collapsed.push(' ');
+            }
+            space = false;
+            collapsed.push(c);
+        }
+    }
+    if space {
+        collapsed.push(' ');
+    }
+    collapsed
+}
+
+/// An inline run as a paragraph: single spaces, none at the ends of lines.
+fn normalize(text: &str) -> String {
+    text.split('\n')
+        .map(|line| collapse_whitespace(line).trim().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim_matches('\n')
+        .to_string()
+}
+
+/// Backslash-escapes the characters that would otherwise start Markdown formatting.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '`' | '[' | ']') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
@@ -0,0 +1,98 @@
+//! Appending converted documents to the end of an existing PDF.
+
+use std::path::Path;
+use std::sync::{Mutex, PoisonError};
+
+use anyhow::{Context, Result};
+use lopdf::{Object, ObjectId};
+
+use crate::error::ConversionError;
+
+/// Page attributes a page may inherit from the page tree nodes above it.
+const INHERITED_ATTRIBUTES: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// Files of a batch finish concurrently; each append must see the pages of the previous one.
+static APPEND_LOCK: Mutex<()> = Mutex::new(());
+
+/// Opens `path` as a PDF that pages can be appended to, or explains why it is not one.
+pub fn open_target(path: &Path) -> Result<lopdf::Document> {
+    let pdf = lopdf::Document::load(path).map_err(|e| match e {
+        lopdf::Error::IO(e) => anyhow::Error::new(e).context(format!("Failed to open {}", path.display())),
+        e => ConversionError::Unsupported(format!("{} is not a readable PDF: {}", path.display(), e)).into(),
+    })?;
+    if pdf.trailer.has(b"Encrypt") {
+        return Err(ConversionError::Encrypted(format!("{} is encrypted and cannot be appended to", path.display())).into());
+    }
+    page_tree(&pdf).with_context(|| format!("{} has no page tree", path.display()))?;
+    Ok(pdf)
+}
+
+/// Adds the pages of the PDF at `addition` after the last page of `target` and returns how
+/// many pages were added.
+pub fn append_pdf(target: &Path, addition: &Path) -> Result<usize> {
+    let _guard = APPEND_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    let mut pdf = open_target(target)?;
+    let mut extra = lopdf::Document::load(addition).context("Failed to reopen converted PDF")?;
+    extra.renumber_objects_with(pdf.max_id + 1);
+    pdf.max_id = extra.max_id;
+
+    let root = page_tree(&pdf)?;
+    let pages: Vec<ObjectId> = extra.page_iter().collect();
+    for &page in &pages {
+        let inherited = inherited_attributes(&extra, page);
+        let dict = extra.get_object_mut(page).and_then(Object::as_dict_mut).context("Converted PDF has a broken page")?;
+        for (key, value) in inherited {
+            if !dict.has(key) {
+                dict.set(key, value);
+            }
+        }
+        dict.set("Parent", Object::Reference(root));
+    }
+
+    // The catalog, page tree nodes and document info of the addition are replaced by the
+    // target's own.
+    let catalog = extra.trailer.get(b"Root").and_then(Object::as_reference).ok();
+    let info = extra.trailer.get(b"Info").and_then(Object::as_reference).ok();
+    for (id, object) in std::mem::take(&mut extra.objects) {
+        let tree_node = object.as_dict().is_ok_and(|dict| dict.type_is(b"Pages"));
+        if Some(id) != catalog && Some(id) != info && !tree_node {
+            pdf.objects.insert(id, object);
+        }
+    }
+
+    let tree = pdf.get_object_mut(root).and_then(Object::as_dict_mut).context("Target PDF has a broken page tree")?;
+    let count = tree.get(b"Count").and_then(Object::as_i64).unwrap_or(0);
+    tree.set("Count", count + pages.len() as i64);
+    tree.get_mut(b"Kids")
+        .and_then(Object::as_array_mut)
+        .context("Target PDF has a broken page tree")?
+        .extend(pages.iter().map(|&page| Object::Reference(page)));
+
+    // Save beside the target and swap it in, so a failed write leaves the original intact.
+    let temp = target.with_extension("pdf.tmp");
+    pdf.save(&temp).context("Failed to write merged PDF")?;
+    std::fs::rename(&temp, target).context("Failed to replace target PDF")?;
+    log::info!("Appended {} pages to {:?}", pages.len(), target);
+    Ok(pages.len())
+}
+
+fn page_tree(pdf: &lopdf::Document) -> Result<ObjectId> {
+    Ok(pdf.catalog()?.get(b"Pages")?.as_reference()?)
+}
+
+/// The inheritable attributes `page` does not set itself, taken from the nearest ancestor.
+fn inherited_attributes(pdf: &lopdf::Document, page: ObjectId) -> Vec<(&'static [u8], Object)> {
+    let mut found: Vec<(&'static [u8], Object)> = Vec::new();
+    let mut node = pdf.get_dictionary(page).ok();
+    while let Some(dict) = node {
+        for key in INHERITED_ATTRIBUTES {
+            if let Ok(value) = dict.get(key)
+                && !found.iter().any(|(k, _)| *k == key)
+            {
+                found.push((key, value.clone()));
+            }
+        }
+        node = dict.get(b"Parent").and_then(Object::as_reference).and_then(|id| pdf.get_dictionary(id)).ok();
+    }
+    found
+}
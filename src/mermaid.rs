@@ -0,0 +1,66 @@
+//! Rendering Mermaid diagrams in Markdown with the Mermaid CLI (`mmdc`).
+//!
+//! The CLI is not bundled; when `mmdc` is not on the `PATH`, diagrams are shown as source
+//! instead.
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+use anyhow::{bail, Context, Result};
+
+use crate::scratch;
+
+/// The CLI's name; npm installs it as a batch script on Windows.
+const MMDC: &str = if cfg!(windows) { "mmdc.cmd" } else { "mmdc" };
+
+/// Scale of the rendered image relative to the diagram's CSS size, for a sharp print.
+const RENDER_SCALE: u32 = 2;
+
+/// Resolution the rendered image is printed at: the 96 dpi of CSS pixels times the scale.
+pub const RENDER_DPI: f64 = 96.0 * RENDER_SCALE as f64;
+
+/// Whether the `mmdc` command can be run.  Checked once per run of the program.
+pub fn available() -> bool {
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| {
+        let found = Command::new(MMDC)
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        if found {
+            log::info!("Mermaid CLI found, Mermaid blocks in Markdown will be rendered as diagrams");
+        } else {
+            log::warn!("Mermaid CLI `mmdc` command not found, Mermaid blocks will be shown as source");
+        }
+        found
+    })
+}
+
+/// Lays out the diagram `source` and writes it to `output` as PNG.
+pub fn render_png(source: &str, output: &Path) -> Result<()> {
+    let input = scratch::file("diagram.mmd")?;
+    std::fs::write(&*input, source).context("Failed to write Mermaid source")?;
+    let result = Command::new(MMDC)
+        .arg("--input")
+        .arg(&*input)
+        .arg("--output")
+        .arg(output)
+        .arg("--backgroundColor")
+        .arg("white")
+        .arg("--scale")
+        .arg(RENDER_SCALE.to_string())
+        .arg("--quiet")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .context("Failed to run the Mermaid CLI")?;
+    if !result.status.success() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        bail!("Mermaid could not render the diagram ({}): {}", result.status, stderr.trim());
+    }
+    Ok(())
+}
@@ -0,0 +1,292 @@
+//! A practical subset of Emacs Org mode, translated to Markdown so that it is laid out like
+//! Markdown files.
+//!
+//! Handles headlines, plain lists (with checkboxes), tables, source, example and quote blocks,
+//! fixed-width lines, links and bold, italic, verbatim and code markup.  Comments and property
+//! drawers are dropped; other directives and markup come through as plain text.
+
+/// `content` as Markdown.
+pub fn to_markdown(content: &str) -> String {
+    let mut markdown = String::new();
+    let mut lines = content.lines();
+    let mut in_drawer = false;
+    // Rows of the open table so far.
+    let mut table_rows = 0;
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim();
+        if !trimmed.starts_with('|') {
+            table_rows = 0;
+        }
+        let upper = trimmed.to_uppercase();
+        if in_drawer {
+            in_drawer = upper != ":END:";
+            continue;
+        }
+        if upper == ":PROPERTIES:" || upper == ":LOGBOOK:" {
+            in_drawer = true;
+            continue;
+        }
+        if let Some(kind) = upper.strip_prefix("#+BEGIN_") {
+            let kind = kind.split_whitespace().next().unwrap_or_default().to_string();
+            let end = format!("#+END_{}", kind);
+            let body: Vec<&str> = lines.by_ref().take_while(|l| !l.trim().eq_ignore_ascii_case(&end)).collect();
+            match kind.as_str() {
+                "SRC" | "EXAMPLE" => {
+                    let language = trimmed.split_whitespace().nth(1).filter(|_| kind == "SRC").unwrap_or_default();
+                    push_code(&mut markdown, language, &body);
+                }
+                "QUOTE" | "VERSE" => {
+                    for line in body {
+                        markdown.push_str(&format!("> {}\n", inline(line.trim())));
+                    }
+                    markdown.push('\n');
+                }
+                // Other blocks keep their content as plain paragraphs.
+                _ => {
+                    for line in body {
+                        markdown.push_str(&inline(line.trim()));
+                        markdown.push('\n');
+                    }
+                }
+            }
+            continue;
+        }
+        if let Some(title) = keyword(trimmed, "TITLE") {
+            markdown.push_str(&format!("# {}\n\n", inline(title)));
+            continue;
+        }
+        // Comments, which a space or nothing follows the `#` of.
+        if trimmed == "#" || trimmed.starts_with("# ") {
+            continue;
+        }
+        if let Some((depth, title)) = headline(line) {
+            markdown.push_str(&format!("{} {}\n\n", "#".repeat(depth.min(6)), inline(title)));
+            continue;
+        }
+        if trimmed == ":" || trimmed.starts_with(": ") {
+            let mut body = vec![trimmed.strip_prefix(": ").unwrap_or_default()];
+            // Consecutive fixed-width lines form one block.
+            let mut rest = lines.clone();
+            while let Some(next) = rest.next() {
+                let next = next.trim();
+                if next != ":" && !next.starts_with(": ") {
+                    break;
+                }
+                body.push(next.strip_prefix(": ").unwrap_or_default());
+                lines = rest.clone();
+            }
+            push_code(&mut markdown, "", &body);
+            continue;
+        }
+        if trimmed.starts_with('|') {
+            // Markdown wants the header separator right after the first row, wherever the Org
+            // table draws its rules.
+            if !trimmed.starts_with("|-") {
+                let cells: Vec<String> = trimmed.trim_matches('|').split('|').map(|cell| inline(cell.trim())).collect();
+                if table_rows == 0 {
+                    markdown.push('\n');
+                }
+                markdown.push_str(&format!("| {} |\n", cells.join(" | ")));
+                if table_rows == 0 {
+                    markdown.push_str(&format!("|{}\n", "---|".repeat(cells.len())));
+                }
+                table_rows += 1;
+            }
+            continue;
+        }
+        if let Some((marker, text)) = list_item(trimmed) {
+            let indent = &line[..line.len() - line.trim_start().len()];
+            markdown.push_str(&format!("{}{} {}\n", indent, marker, inline(text)));
+            continue;
+        }
+        if trimmed.len() >= 5 && trimmed.chars().all(|c| c == '-') {
+            markdown.push_str("\n---\n\n");
+            continue;
+        }
+        // Unindented, since Markdown reads indented lines as code; text continuing a list item
+        // stays part of it anyway.
+        markdown.push_str(&inline(trimmed));
+        markdown.push('\n');
+    }
+    markdown
+}
+
+/// The value of an `#+NAME:` keyword line.
+fn keyword<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let rest = line.strip_prefix("#+")?;
+    let (key, value) = rest.split_once(':')?;
+    key.eq_ignore_ascii_case(name).then(|| value.trim())
+}
+
+/// The depth and title of a headline such as `** TODO Title :tag:`, without its tags.
+fn headline(line: &str) -> Option<(usize, &str)> {
+    let depth = line.chars().take_while(|&c| c == '*').count();
+    let title = line[depth..].strip_prefix(' ').filter(|_| depth > 0)?.trim();
+    let title = match title.rsplit_once(' ') {
+        Some((before, tags)) if tags.len() > 2 && tags.starts_with(':') && tags.ends_with(':') => before.trim_end(),
+        _ => title,
+    };
+    Some((depth, title))
+}
+
+/// The Markdown marker and the text of a list item; checkboxes become ballot boxes.
+fn list_item(line: &str) -> Option<(String, &str)> {
+    let (marker, text) = if let Some(text) = line.strip_prefix("- ").or_else(|| line.strip_prefix("+ ")) {
+        ("-".to_string(), text)
+    } else {
+        let digits = line.chars().take_while(char::is_ascii_digit).count();
+        let rest = &line[digits..];
+        let text = rest.strip_prefix(". ").or_else(|| rest.strip_prefix(") ")).filter(|_| digits > 0)?;
+        (format!("{}.", &line[..digits]), text)
+    };
+    let text = text.trim_start();
+    let (box_glyph, text) = if let Some(text) = text.strip_prefix("[ ] ") {
+        ("☐ ", text)
+    } else if let Some(text) = text.strip_prefix("[X] ").or_else(|| text.strip_prefix("[x] ")) {
+        ("☑ ", text)
+    } else if let Some(text) = text.strip_prefix("[-] ") {
+        ("☐ ", text)
+    } else {
+        ("", text)
+    };
+    Some((format!("{} {}", marker, box_glyph).trim_end().to_string(), text))
+}
+
+fn push_code(markdown: &mut String, language: &str, lines: &[&str]) {
+    // A fence longer than any backtick run in the code.
+    let longest = lines.iter().map(|l| backtick_run(l)).max().unwrap_or(0);
+    let fence = "`".repeat(longest.max(2) + 1);
+    markdown.push_str(&format!("{}{}\n", fence, language));
+    for line in lines {
+        markdown.push_str(line);
+        markdown.push('\n');
+    }
+    markdown.push_str(&format!("{}\n\n", fence));
+}
+
+fn backtick_run(text: &str) -> usize {
+    text.split(|c| c != '`').map(str::len).max().unwrap_or(0)
+}
+
+/// Org inline markup as Markdown: `*bold*`, `/italic/`, `=verbatim=`, `~code~` and
+/// `[[target][description]]` links.  Underline and strike-through keep only their text.
+fn inline(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut markdown = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '[' && chars.get(i + 1) == Some(&'[') && let Some((link, end)) = link(&chars, i) {
+            markdown.push_str(&link);
+            i = end;
+            continue;
+        }
+        if matches!(c, '*' | '/' | '=' | '~' | '_' | '+')
+            && (i == 0 || chars[i - 1].is_whitespace() || "-({'\"".contains(chars[i - 1]))
+            && let Some(end) = closing_marker(&chars, i)
+        {
+            let inner: String = chars[i + 1..end].iter().collect();
+            match c {
+                '*' => markdown.push_str(&format!("**{}**", inline(&inner))),
+                '/' => markdown.push_str(&format!("*{}*", inline(&inner))),
+                '=' | '~' => markdown.push_str(&code_span(&inner)),
+                _ => markdown.push_str(&inline(&inner)),
+            }
+            i = end + 1;
+            continue;
+        }
+        push_escaped(&mut markdown, c);
+        i += 1;
+    }
+    markdown
+}
+
+/// The position of the marker closing the one at `start`: it follows a non-blank character
+/// and precedes a blank, punctuation or the end of the text.
+fn closing_marker(chars: &[char], start: usize) -> Option<usize> {
+    let marker = chars[start];
+    if chars.get(start + 1).is_none_or(|c| c.is_whitespace()) {
+        return None;
+    }
+    (start + 2..chars.len()).find(|&i| {
+        chars[i] == marker
+            && !chars[i - 1].is_whitespace()
+            && chars.get(i + 1).is_none_or(|c| c.is_whitespace() || "-.,;:!?')}\"".contains(*c))
+    })
+}
+
+/// A `[[target]]` or `[[target][description]]` link starting at `start` as Markdown, and the
+/// position after it.
+fn link(chars: &[char], start: usize) -> Option<(String, usize)> {
+    let rest: String = chars[start + 2..].iter().collect();
+    let close = rest.find("]]")?;
+    let body = &rest[..close];
+    let (target, description) = match body.split_once("][") {
+        Some((target, description)) => (target, description),
+        None => (body, body),
+    };
+    let end = start + 2 + rest[..close].chars().count() + 2;
+    let mut label = String::new();
+    description.chars().for_each(|c| push_escaped(&mut label, c));
+    Some((format!("[{}](<{}>)", label, target.replace(['<', '>'], "")), end))
+}
+
+fn code_span(code: &str) -> String {
+    let fence = "`".repeat(backtick_run(code) + 1);
+    format!("{} {} {}", fence, code, fence)
+}
+
+/// Pushes `c`, escaped if Markdown would read it as markup.
+fn push_escaped(markdown: &mut String, c: char) {
+    if c.is_ascii_punctuation() && !matches!(c, '.' | ',' | ':' | ';' | '?' | '\'' | '"' | '/' | '(' | ')' | '%' | '@' | '$') {
+        markdown.push('\\');
+    }
+    markdown.push(c);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headlines_become_headings_without_their_tags() {
+        assert_eq!(to_markdown("* Plan\n** TODO Ship it :work:urgent:\n"), "# Plan\n\n## TODO Ship it\n\n");
+        assert_eq!(to_markdown("******** Deep"), "###### Deep\n\n");
+        assert_eq!(to_markdown("#+TITLE: Notes"), "# Notes\n\n");
+        // Not a headline without the space, and not a tag with one inside.
+        assert_eq!(to_markdown("*bold* text"), "**bold** text\n");
+        assert_eq!(to_markdown("* Ratio : 3 :"), "# Ratio : 3 :\n\n");
+    }
+
+    #[test]
+    fn source_blocks_become_fenced_code() {
+        assert_eq!(to_markdown("#+BEGIN_SRC rust\nlet x = *y;\n#+END_SRC\nafter"), "```rust\nlet x = *y;\n```\n\nafter\n");
+        assert_eq!(to_markdown("#+begin_src python :results output\nprint(1)\n#+end_src"), "```python\nprint(1)\n```\n\n");
+        // The fence outgrows backtick runs in the code.
+        assert_eq!(to_markdown("#+BEGIN_EXAMPLE\n```\n#+END_EXAMPLE"), "````\n```\n````\n\n");
+    }
+
+    #[test]
+    fn checkboxes_become_ballot_boxes() {
+        assert_eq!(
+            to_markdown("- [ ] todo\n- [X] done\n  + [-] partly\n1. [x] first"),
+            "- ☐ todo\n- ☑ done\n  - ☐ partly\n1. ☑ first\n"
+        );
+    }
+
+    #[test]
+    fn tables_get_a_header_separator_after_the_first_row() {
+        assert_eq!(
+            to_markdown("|------+-----|\n| Name | Qty |\n|------+-----|\n| a*b  | 2   |\n|------+-----|"),
+            "\n| Name | Qty |\n|---|---|\n| a\\*b | 2 |\n"
+        );
+    }
+
+    #[test]
+    fn inline_markup_is_translated_and_markdown_characters_are_escaped() {
+        assert_eq!(to_markdown("*bold* /it/ =v= _u_ +s+"), "**bold** *it* ` v ` u s\n");
+        assert_eq!(to_markdown("~a`b~"), "`` a`b ``\n");
+        assert_eq!(to_markdown("[[https://example.org][the *site*]]"), "[the \\*site\\*](<https://example.org>)\n");
+        assert_eq!(to_markdown("2 * 3 <x> [y] #1 a_b"), "2 \\* 3 \\<x\\> \\[y\\] \\#1 a\\_b\n");
+    }
+}
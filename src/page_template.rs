@@ -0,0 +1,167 @@
+//! Page headers and footers configured as templates, such as `{filename}` on the left and
+//! `{page} / {pages}` on the right.
+//!
+//! A template has text for the left, center and right of the line.  `{page}`, `{pages}`,
+//! `{filename}`, `{date}` and `{title}` are replaced for every page; other text in braces is
+//! printed as it is written.
+
+use genpdf::{elements, render, style, Alignment, Context, Element, Margins, Mm, PageDecorator, Position};
+use serde::{Deserialize, Serialize};
+
+/// The text at the left, center and right of a page header or footer.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PageTemplate {
+    pub left: String,
+    pub center: String,
+    pub right: String,
+}
+
+impl PageTemplate {
+    /// The `- 3 -` header of [`crate::converter::ConversionOptions::page_numbers`].
+    pub fn page_number() -> Self {
+        Self { center: "- {page} -".to_string(), ..Self::default() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parts().iter().all(|(part, _)| part.trim().is_empty())
+    }
+
+    /// Whether the template shows the page count, which is only known once the whole document
+    /// has been laid out.
+    pub fn uses_page_count(&self) -> bool {
+        self.parts().iter().any(|(part, _)| part.contains("{pages}"))
+    }
+
+    fn parts(&self) -> [(&str, Alignment); 3] {
+        [(&self.left, Alignment::Left), (&self.center, Alignment::Center), (&self.right, Alignment::Right)]
+    }
+}
+
+/// What the tokens of a template stand for, apart from the page number.
+#[derive(Debug, Clone)]
+pub struct PageValues {
+    pub filename: String,
+    pub title: String,
+    pub date: String,
+    /// The page count, or `None` while it is not known yet; `{pages}` then prints `?`.
+    pub pages: Option<usize>,
+}
+
+/// `template` with its tokens replaced for page `page`.
+pub fn fill(template: &str, page: usize, values: &PageValues) -> String {
+    let mut filled = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        let token = &rest[start..];
+        let Some(end) = token.find('}') else {
+            rest = token;
+            break;
+        };
+        match &token[1..end] {
+            "page" => filled.push_str(&page.to_string()),
+            "pages" => filled.push_str(&values.pages.map_or_else(|| "?".to_string(), |pages| pages.to_string())),
+            "filename" => filled.push_str(&values.filename),
+            "date" => filled.push_str(&values.date),
+            "title" => filled.push_str(&values.title),
+            _ => filled.push_str(&token[..=end]),
+        }
+        rest = &token[end + 1..];
+    }
+    filled.push_str(rest);
+    filled
+}
+
+/// Applies the page margins, and prints the header at the top and the footer at the bottom of
+/// the area within them.
+pub struct TemplateDecorator {
+    page: usize,
+    margins: Margins,
+    header: PageTemplate,
+    footer: PageTemplate,
+    values: PageValues,
+}
+
+impl TemplateDecorator {
+    pub fn new(margins: impl Into<Margins>, header: PageTemplate, footer: PageTemplate, values: PageValues) -> Self {
+        Self { page: 0, margins: margins.into(), header, footer, values }
+    }
+
+    /// Prints the three parts of `template` side by side at the top of `area` and returns the
+    /// height of the tallest.
+    fn print(&self, template: &PageTemplate, context: &Context, area: &render::Area<'_>, style: style::Style) -> Result<Mm, genpdf::error::Error> {
+        let mut height = Mm::default();
+        for ((part, alignment), column) in template.parts().into_iter().zip(area.split_horizontally(&[1, 1, 1])) {
+            if part.trim().is_empty() {
+                continue;
+            }
+            let mut paragraph = elements::Paragraph::new(fill(part, self.page, &self.values)).aligned(alignment);
+            let result = paragraph.render(context, column, style)?;
+            height = height.max(result.size.height);
+        }
+        Ok(height)
+    }
+}
+
+impl PageDecorator for TemplateDecorator {
+    fn decorate_page<'a>(&mut self, context: &Context, mut area: render::Area<'a>, style: style::Style) -> Result<render::Area<'a>, genpdf::error::Error> {
+        self.page += 1;
+        area.add_margins(self.margins);
+        let style = style.with_font_size(9).with_color(style::Color::Rgb(128, 128, 128));
+        if !self.header.is_empty() {
+            let height = self.print(&self.header, context, &area, style)?;
+            area.add_offset(Position::new(0, height));
+        }
+        if !self.footer.is_empty() {
+            // The footer gets one line; a part too long for its third of the page runs on into
+            // the margin.
+            let height = style.line_height(&context.font_cache);
+            let mut footer = area.clone();
+            footer.add_offset(Position::new(0, area.size().height - height));
+            self.print(&self.footer, context, &footer, style)?;
+            area.set_height(area.size().height - height);
+        }
+        Ok(area)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pages: Option<usize>) -> PageValues {
+        PageValues {
+            filename: "report.md".to_string(),
+            title: "季度报告".to_string(),
+            date: "2026-10-15".to_string(),
+            pages,
+        }
+    }
+
+    #[test]
+    fn tokens_are_replaced() {
+        let values = values(Some(12));
+        assert_eq!(fill("{page} / {pages}", 3, &values), "3 / 12");
+        assert_eq!(fill("{filename}", 1, &values), "report.md");
+        assert_eq!(fill("{date}", 1, &values), "2026-10-15");
+        assert_eq!(fill("《{title}》 第 {page} 页", 7, &values), "《季度报告》 第 7 页");
+    }
+
+    #[test]
+    fn unknown_page_count_prints_a_question_mark() {
+        assert_eq!(fill("{page} / {pages}", 3, &values(None)), "3 / ?");
+    }
+
+    #[test]
+    fn unknown_tokens_are_printed_as_written() {
+        assert_eq!(fill("{foo} {page}", 2, &values(None)), "{foo} 2");
+        assert_eq!(fill("{}", 2, &values(None)), "{}");
+    }
+
+    #[test]
+    fn unterminated_brace_is_printed_as_written() {
+        assert_eq!(fill("{page} of {pages", 2, &values(Some(5))), "2 of {pages");
+        assert_eq!(fill("{", 1, &values(None)), "{");
+    }
+}
@@ -0,0 +1,226 @@
+//! Completes printpdf's PDF/A-2b output.
+//!
+//! printpdf only writes the conformance identifier and a CMYK print output intent.  PDF/A also
+//! needs XMP metadata with the `pdfaid` schema and, because genpdf draws in DeviceRGB, an RGB
+//! output intent.  Fonts are already embedded by genpdf.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use lopdf::{Dictionary, Object, Stream, StringFormat};
+
+const PRODUCER: &str = "Topdf";
+
+struct XmpFields {
+    title: String,
+    created: Option<String>,
+    modified: Option<String>,
+    trapped: Option<String>,
+}
+
+/// Rewrites the PDF at `path` so that it carries the metadata PDF/A-2b requires.
+pub fn finalize_pdfa2b(path: &Path) -> Result<()> {
+    let mut pdf = lopdf::Document::load(path).context("Failed to reopen PDF for PDF/A metadata")?;
+
+    // Entries of the document information dictionary must be mirrored in the XMP metadata.
+    let info_id = pdf.trailer.get(b"Info").and_then(Object::as_reference).context("PDF has no document info")?;
+    let info = pdf.get_object_mut(info_id).and_then(Object::as_dict_mut).context("PDF has no document info")?;
+    info.set("Producer", Object::String(PRODUCER.as_bytes().to_vec(), StringFormat::Literal));
+    let info_text = |key: &[u8]| {
+        info.get(key).ok()
+            .and_then(|value| value.as_str().or_else(|_| value.as_name()).ok())
+            .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+    };
+    let xmp = xmp_metadata(&XmpFields {
+        title: info_text(b"Title").unwrap_or_default(),
+        created: info_text(b"CreationDate").as_deref().and_then(xmp_date),
+        modified: info_text(b"ModDate").as_deref().and_then(xmp_date),
+        trapped: info_text(b"Trapped"),
+    });
+
+    let metadata_id = pdf.add_object(
+        Stream::new(
+            Dictionary::from_iter(vec![("Type", "Metadata".into()), ("Subtype", "XML".into())]),
+            xmp.into_bytes(),
+        )
+        .with_compression(false),
+    );
+
+    let mut profile = Stream::new(
+        Dictionary::from_iter(vec![("N", Object::Integer(3)), ("Alternate", "DeviceRGB".into())]),
+        srgb_icc_profile(),
+    );
+    profile.compress()?;
+    let profile_id = pdf.add_object(profile);
+    let output_intent = Dictionary::from_iter(vec![
+        ("Type", "OutputIntent".into()),
+        ("S", "GTS_PDFA1".into()),
+        ("OutputConditionIdentifier", Object::String(b"sRGB IEC61966-2.1".to_vec(), StringFormat::Literal)),
+        ("RegistryName", Object::String(b"http://www.color.org".to_vec(), StringFormat::Literal)),
+        ("DestinationOutputProfile", Object::Reference(profile_id)),
+    ]);
+
+    let catalog_id = pdf.trailer.get(b"Root").and_then(Object::as_reference).context("PDF has no catalog")?;
+    let catalog = pdf.get_object_mut(catalog_id).and_then(Object::as_dict_mut).context("PDF has no catalog")?;
+    catalog.set("Metadata", Object::Reference(metadata_id));
+    catalog.set("OutputIntents", Object::Array(vec![Object::Dictionary(output_intent)]));
+
+    // PDF/A forbids image interpolation, which printpdf enables for every image.
+    for object in pdf.objects.values_mut() {
+        if let Object::Stream(stream) = object
+            && stream.dict.get(b"Subtype").and_then(Object::as_name_str).ok() == Some("Image")
+        {
+            stream.dict.remove(b"Interpolate");
+        }
+    }
+    // Drops printpdf's replaced CMYK profile.
+    pdf.prune_objects();
+
+    pdf.save(path).context("Failed to save PDF/A document")?;
+    log::info!("Wrote PDF/A-2b metadata to {:?}", path);
+    Ok(())
+}
+
+/// Converts a PDF date (`D:20170505150224+02'00'`) to the XMP form (`2017-05-05T15:02:24+02:00`).
+fn xmp_date(date: &str) -> Option<String> {
+    let digits = date.strip_prefix("D:")?;
+    if digits.len() < 14 || !digits[..14].bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let zone = match digits[14..].replace('\'', "").as_str() {
+        "" | "Z" => "Z".to_string(),
+        offset if offset.len() == 5 => format!("{}:{}", &offset[..3], &offset[3..]),
+        _ => return None,
+    };
+    Some(format!(
+        "{}-{}-{}T{}:{}:{}{}",
+        &digits[0..4], &digits[4..6], &digits[6..8], &digits[8..10], &digits[10..12], &digits[12..14], zone
+    ))
+}
+
+fn xmp_metadata(fields: &XmpFields) -> String {
+    let escape = |s: &str| s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+    let mut properties = format!(
+        "   <dc:title><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:title>\n\
+         \x20  <pdf:Producer>{}</pdf:Producer>\n",
+        escape(&fields.title),
+        PRODUCER
+    );
+    if let Some(created) = &fields.created {
+        properties.push_str(&format!("   <xmp:CreateDate>{}</xmp:CreateDate>\n", created));
+    }
+    if let Some(modified) = &fields.modified {
+        properties.push_str(&format!("   <xmp:ModifyDate>{}</xmp:ModifyDate>\n", modified));
+    }
+    if let Some(trapped) = &fields.trapped {
+        properties.push_str(&format!("   <pdf:Trapped>{}</pdf:Trapped>\n", trapped));
+    }
+    format!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+         <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n\
+         \x20<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n\
+         \x20 <rdf:Description rdf:about=\"\"\n\
+         \x20   xmlns:dc=\"http://purl.org/dc/elements/1.1/\"\n\
+         \x20   xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\"\n\
+         \x20   xmlns:pdf=\"http://ns.adobe.com/pdf/1.3/\"\n\
+         \x20   xmlns:pdfaid=\"http://www.aiim.org/pdfa/ns/id/\">\n\
+         {}\
+         \x20  <pdfaid:part>2</pdfaid:part>\n\
+         \x20  <pdfaid:conformance>B</pdfaid:conformance>\n\
+         \x20 </rdf:Description>\n\
+         \x20</rdf:RDF>\n\
+         </x:xmpmeta>\n\
+         <?xpacket end=\"w\"?>",
+        properties
+    )
+}
+
+/// Builds a minimal ICC v2 display profile for sRGB (D50-adapted primaries, sampled tone curve).
+fn srgb_icc_profile() -> Vec<u8> {
+    fn s15f16(v: f64) -> [u8; 4] {
+        ((v * 65536.0).round() as i32).to_be_bytes()
+    }
+    fn xyz(x: f64, y: f64, z: f64) -> Vec<u8> {
+        let mut tag = b"XYZ \0\0\0\0".to_vec();
+        for v in [x, y, z] {
+            tag.extend(s15f16(v));
+        }
+        tag
+    }
+    fn curve() -> Vec<u8> {
+        const POINTS: u32 = 1024;
+        let mut tag = b"curv\0\0\0\0".to_vec();
+        tag.extend(POINTS.to_be_bytes());
+        for i in 0..POINTS {
+            let v = i as f64 / (POINTS - 1) as f64;
+            let linear = if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) };
+            tag.extend(((linear * 65535.0).round() as u16).to_be_bytes());
+        }
+        tag
+    }
+    fn description(text: &str) -> Vec<u8> {
+        let mut tag = b"desc\0\0\0\0".to_vec();
+        tag.extend((text.len() as u32 + 1).to_be_bytes());
+        tag.extend(text.as_bytes());
+        tag.push(0);
+        // Empty Unicode and ScriptCode descriptions.
+        tag.extend([0u8; 8]);
+        tag.extend([0u8; 3]);
+        tag.extend([0u8; 67]);
+        tag
+    }
+    fn copyright(text: &str) -> Vec<u8> {
+        let mut tag = b"text\0\0\0\0".to_vec();
+        tag.extend(text.as_bytes());
+        tag.push(0);
+        tag
+    }
+
+    let tags: Vec<(&[u8; 4], Vec<u8>)> = vec![
+        (b"desc", description("sRGB IEC61966-2.1")),
+        (b"cprt", copyright("No copyright, use freely")),
+        (b"wtpt", xyz(0.9642, 1.0, 0.8249)),
+        (b"rXYZ", xyz(0.4361, 0.2225, 0.0139)),
+        (b"gXYZ", xyz(0.3851, 0.7169, 0.0971)),
+        (b"bXYZ", xyz(0.1431, 0.0606, 0.7141)),
+        (b"rTRC", curve()),
+        (b"gTRC", curve()),
+        (b"bTRC", curve()),
+    ];
+
+    let table_len = 4 + 12 * tags.len();
+    let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+    let mut data = Vec::new();
+    for (signature, tag) in &tags {
+        let offset = 128 + table_len + data.len();
+        table.extend(*signature);
+        table.extend((offset as u32).to_be_bytes());
+        table.extend((tag.len() as u32).to_be_bytes());
+        data.extend(tag);
+        // Tag data is 4-byte aligned.
+        data.resize(data.len().next_multiple_of(4), 0);
+    }
+
+    let size = 128 + table_len + data.len();
+    let mut header = Vec::with_capacity(128);
+    header.extend((size as u32).to_be_bytes());
+    header.extend([0u8; 4]); // preferred CMM
+    header.extend([0x02, 0x10, 0x00, 0x00]); // version 2.1
+    header.extend(b"mntrRGB XYZ ");
+    for part in [2026u16, 1, 1, 0, 0, 0] {
+        header.extend(part.to_be_bytes());
+    }
+    header.extend(b"acsp");
+    header.extend([0u8; 24]); // platform, flags, manufacturer, model, attributes
+    header.extend([0u8; 4]); // perceptual rendering intent
+    for v in [0.9642, 1.0, 0.8249] {
+        header.extend(s15f16(v));
+    }
+    header.extend([0u8; 4]); // creator
+    header.resize(128, 0);
+
+    let mut profile = header;
+    profile.extend(table);
+    profile.extend(data);
+    profile
+}
@@ -0,0 +1,175 @@
+//! Threading model for conversions.
+//!
+//! A conversion is split into three phases:
+//!
+//! 1. **Load** ([`converter::load_input`]): reading the file, extracting DOCX text, parsing CSV
+//!    and Excel rows and decoding images.  This runs on the worker thread that the UI spawns for
+//!    each file, so I/O- and parse-heavy batches proceed in parallel.
+//! 2. **Render** ([`converter::render_document`]): building the genpdf document, laying it out
+//!    and writing the PDF.  All rendering goes through one dedicated render thread fed by a
+//!    queue, one document at a time.  genpdf documents hold boxed elements that are not `Send`,
+//!    and neither genpdf nor printpdf guarantees that concurrent renders are safe, so this phase
+//!    is never run in parallel.
+//! 3. **Post-process** (JPEG image encoding, PDF/A metadata): rewrites the written file with
+//!    lopdf and runs back on the worker.
+//!
+//! Only [`LoadedInput`] crosses from a worker to the render thread; whatever is placed in it must
+//! therefore be `Send`.
+//!
+//! With a timeout (see [`with_timeout`]) the whole conversion runs on a thread of its own, which
+//! the worker abandons when time runs out.
+
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::converter::{self, ConversionOptions, FontSet, LoadedInput, RenderedDocument};
+use crate::error::ConversionError;
+
+struct RenderJob {
+    input: LoadedInput,
+    output: PathBuf,
+    fonts: FontSet,
+    options: ConversionOptions,
+    /// Set once the conversion has been abandoned, so a job still queued is skipped.
+    cancel: Option<Arc<AtomicBool>>,
+    reply: mpsc::Sender<Result<RenderedDocument>>,
+}
+
+thread_local! {
+    /// The cancel flag of the conversion running on this thread, if it has a timeout.
+    static CANCEL: RefCell<Option<Arc<AtomicBool>>> = const { RefCell::new(None) };
+}
+
+static RENDER_QUEUE: OnceLock<mpsc::Sender<RenderJob>> = OnceLock::new();
+
+fn render_queue() -> &'static mpsc::Sender<RenderJob> {
+    RENDER_QUEUE.get_or_init(|| {
+        let (tx, rx) = mpsc::channel::<RenderJob>();
+        std::thread::Builder::new()
+            .name("pdf-render".to_string())
+            .spawn(move || {
+                log::info!("Render thread started");
+                for job in rx {
+                    if job.cancel.as_ref().is_some_and(|c| c.load(Ordering::Relaxed)) {
+                        log::info!("Skipping render of {:?}, its conversion was abandoned", job.input.path);
+                        let _ = job.reply.send(Err(ConversionError::Cancelled.into()));
+                        continue;
+                    }
+                    // A panic inside genpdf must fail only this document, not every later one.
+                    let source = job.input.path.clone();
+                    let result = converter::catch_stage("rendering", &source, || {
+                        converter::render_document(job.input, &job.output, &job.fonts, &job.options)
+                    });
+                    let _ = job.reply.send(result);
+                }
+            })
+            .expect("Failed to spawn render thread");
+        tx
+    })
+}
+
+/// A fixed number of worker threads for the load and post-process phases of a batch.
+///
+/// Jobs queue up until a worker is free, so a large batch does not start a thread per file.
+/// The workers exit once the pool is dropped and the queue has drained.
+pub struct WorkerPool {
+    jobs: mpsc::Sender<Box<dyn FnOnce() + Send>>,
+}
+
+impl WorkerPool {
+    /// Starts `workers` threads that each rest for `pause` after every job.
+    pub fn new(workers: usize, pause: Duration) -> Self {
+        let (jobs, queue) = mpsc::channel::<Box<dyn FnOnce() + Send>>();
+        let queue = Arc::new(Mutex::new(queue));
+        for n in 0..workers.max(1) {
+            let queue = queue.clone();
+            std::thread::Builder::new()
+                .name(format!("convert-worker-{}", n))
+                .spawn(move || loop {
+                    // Holding the lock only while waiting lets the other workers take the
+                    // next job as soon as this one has its own.
+                    let job = queue.lock().unwrap_or_else(PoisonError::into_inner).recv();
+                    let Ok(job) = job else { break };
+                    job();
+                    if !pause.is_zero() {
+                        std::thread::sleep(pause);
+                    }
+                })
+                .expect("Failed to spawn conversion worker");
+        }
+        log::info!("Started {} conversion workers (pause {:?})", workers.max(1), pause);
+        Self { jobs }
+    }
+
+    pub fn execute(&self, job: impl FnOnce() + Send + 'static) {
+        if self.jobs.send(Box::new(job)).is_err() {
+            log::error!("Conversion workers stopped, dropping job");
+        }
+    }
+}
+
+/// Queues `input` on the render thread and blocks until its PDF has been written.
+pub fn render(
+    input: LoadedInput,
+    output: &Path,
+    fonts: &FontSet,
+    options: &ConversionOptions,
+) -> Result<RenderedDocument> {
+    let (reply, result) = mpsc::channel();
+    let job = RenderJob {
+        input,
+        output: output.to_path_buf(),
+        fonts: fonts.clone(),
+        options: options.clone(),
+        cancel: CANCEL.with(|c| c.borrow().clone()),
+        reply,
+    };
+    render_queue().send(job).map_err(|_| {
+        log::error!("Render thread is not running");
+        ConversionError::Cancelled
+    })?;
+    result.recv().map_err(|_| {
+        log::error!("Render thread stopped before finishing");
+        ConversionError::Cancelled
+    })?
+}
+
+/// Runs `convert` on a thread of its own and gives up waiting for it after `timeout`.
+///
+/// The abandoned conversion sees [`cancelled`] turn true and stops at its next phase.  A phase
+/// that never returns keeps its thread busy; for rendering, that is the shared render thread.
+pub fn with_timeout<T: Send + 'static>(
+    timeout: Duration,
+    convert: impl FnOnce() -> Result<T, ConversionError> + Send + 'static,
+) -> Result<T, ConversionError> {
+    let cancel = Arc::new(AtomicBool::new(false));
+    let flag = cancel.clone();
+    let (reply, result) = mpsc::channel();
+    std::thread::Builder::new()
+        .name("convert-timed".to_string())
+        .spawn(move || {
+            CANCEL.with(|c| *c.borrow_mut() = Some(flag));
+            let _ = reply.send(convert());
+        })
+        .map_err(|e| ConversionError::Io(format!("Failed to start conversion thread: {}", e)))?;
+    match result.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(mpsc::RecvTimeoutError::Timeout) => {
+            log::warn!("Conversion did not finish within {:?}, abandoning it", timeout);
+            cancel.store(true, Ordering::Relaxed);
+            Err(ConversionError::TimedOut(timeout.as_secs()))
+        }
+        Err(mpsc::RecvTimeoutError::Disconnected) => Err(ConversionError::Cancelled),
+    }
+}
+
+/// Whether the conversion running on this thread has been abandoned by [`with_timeout`].
+pub fn cancelled() -> bool {
+    CANCEL.with(|c| c.borrow().as_ref().is_some_and(|c| c.load(Ordering::Relaxed)))
+}
@@ -0,0 +1,64 @@
+//! Ranges of the parts of an input to convert, such as `1-3,5`.
+//!
+//! Parts are worksheets of an Excel workbook and tables of a SQLite database, numbered from 1 in
+//! the order the file lists them.
+
+use std::ops::RangeInclusive;
+
+/// A parsed range string; an empty string selects every part.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PartRange {
+    /// `None` for "all".  An open range such as `5-` ends at `usize::MAX`.
+    spans: Option<Vec<RangeInclusive<usize>>>,
+}
+
+impl PartRange {
+    /// Parses comma-separated part numbers and `from-to` spans, where `to` may be left out to
+    /// mean the last part.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        if text.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        let number = |part: &str, item: &str| -> Result<usize, String> {
+            match part.trim().parse::<usize>() {
+                Ok(0) => Err(format!("invalid range {:?}: parts are numbered from 1", item)),
+                Ok(n) => Ok(n),
+                Err(_) => Err(format!("invalid range {:?}: expected numbers such as 1-3,5", item)),
+            }
+        };
+        let spans = text.split([',', '，'])
+            .map(str::trim)
+            .map(|item| {
+                if item.is_empty() {
+                    return Err(format!("invalid range {:?}: empty item between commas", text.trim()));
+                }
+                match item.split_once('-') {
+                    Some((from, to)) => {
+                        let from = number(from, item)?;
+                        let to = if to.trim().is_empty() { usize::MAX } else { number(to, item)? };
+                        if from > to {
+                            return Err(format!("invalid range {:?}: the start is after the end", item));
+                        }
+                        Ok(from..=to)
+                    }
+                    None => number(item, item).map(|n| n..=n),
+                }
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Self { spans: Some(spans) })
+    }
+
+    pub fn is_all(&self) -> bool {
+        self.spans.is_none()
+    }
+
+    /// Whether part `number`, counted from 1, is selected.
+    pub fn contains(&self, number: usize) -> bool {
+        self.spans.as_ref().is_none_or(|spans| spans.iter().any(|span| span.contains(&number)))
+    }
+
+    /// The items of `parts` the range selects, in their original order.
+    pub fn select<T>(&self, parts: Vec<T>) -> Vec<T> {
+        parts.into_iter().enumerate().filter(|(i, _)| self.contains(i + 1)).map(|(_, part)| part).collect()
+    }
+}
@@ -0,0 +1,50 @@
+//! Replacing sensitive text (email addresses, phone numbers, user patterns) before rendering.
+
+use std::borrow::Cow;
+
+use anyhow::{anyhow, Result};
+use regex::Regex;
+
+use crate::converter::Redaction;
+
+/// What every match is replaced with.
+pub const MASK: &str = "████";
+
+const EMAIL_PATTERN: &str = r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}";
+/// International or local numbers with separators, and 11-digit mainland China mobile numbers.
+const PHONE_PATTERN: &str = r"(?:\+\d{1,3}[ -]?)?(?:\b1[3-9]\d{9}\b|(?:\(\d{2,4}\)[ -]?|\b\d{2,4}[ -])?\b\d{3,4}[ -]\d{4}\b)";
+
+/// The compiled patterns of a [`Redaction`] setting.
+pub struct Redactor {
+    patterns: Vec<Regex>,
+}
+
+impl Redactor {
+    /// Compiles the enabled patterns, or returns `None` when nothing is to be redacted.
+    pub fn new(redaction: &Redaction) -> Result<Option<Self>> {
+        let mut patterns = Vec::new();
+        if redaction.emails {
+            patterns.push(Regex::new(EMAIL_PATTERN).expect("built-in email pattern is valid"));
+        }
+        if redaction.phone_numbers {
+            patterns.push(Regex::new(PHONE_PATTERN).expect("built-in phone pattern is valid"));
+        }
+        for pattern in &redaction.patterns {
+            // The regex error explains what is wrong, so keep it in the message shown to the user.
+            let regex = Regex::new(pattern).map_err(|e| anyhow!("Invalid redaction pattern {:?}: {}", pattern, e))?;
+            patterns.push(regex);
+        }
+        Ok((!patterns.is_empty()).then_some(Self { patterns }))
+    }
+
+    /// `text` with every match of every pattern replaced by [`MASK`].
+    pub fn redact<'t>(&self, text: &'t str) -> Cow<'t, str> {
+        let mut text = Cow::Borrowed(text);
+        for pattern in &self.patterns {
+            if let Cow::Owned(replaced) = pattern.replace_all(&text, MASK) {
+                text = Cow::Owned(replaced);
+            }
+        }
+        text
+    }
+}
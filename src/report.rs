@@ -0,0 +1,37 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::converter::FileType;
+
+pub const REPORT_FILE_NAME: &str = "topdf_report.csv";
+
+/// One line of the conversion report.
+pub struct ReportRow<'a> {
+    pub source: &'a Path,
+    pub outputs: &'a [PathBuf],
+    pub status: &'a str,
+    pub error: Option<String>,
+}
+
+/// Writes a CSV summary of a batch to `<dir>/topdf_report.csv` and returns its path.
+///
+/// Files with several outputs (e.g. ZIP archives) list them separated by `;`.
+pub fn write_report(dir: &Path, rows: &[ReportRow<'_>]) -> Result<PathBuf> {
+    let path = dir.join(REPORT_FILE_NAME);
+    let mut writer = csv::Writer::from_path(&path).context("Failed to create report file")?;
+    writer.write_record(["source", "type", "output", "status", "error"])?;
+    for row in rows {
+        let outputs = row.outputs.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(";");
+        writer.write_record([
+            row.source.display().to_string(),
+            format!("{:?}", FileType::from_path(row.source)),
+            outputs,
+            row.status.to_string(),
+            row.error.clone().unwrap_or_default(),
+        ])?;
+    }
+    writer.flush().context("Failed to write report file")?;
+    log::info!("Conversion report written to {:?}", path);
+    Ok(path)
+}
@@ -0,0 +1,319 @@
+//! A practical subset of reStructuredText.
+//!
+//! Handles section titles (underline, optionally with a matching overline), bullet and
+//! enumerated lists, literal blocks (`::` and the code directives) and inline markup.  Other
+//! directives, tables and field lists come through as plain text.
+
+/// A block-level element of an RST document.
+#[derive(Debug, PartialEq)]
+pub enum Block {
+    /// A section title; level 1 is the first adornment style used in the document.
+    Heading { level: usize, text: String },
+    Paragraph(String),
+    /// A list item with its marker (`•`, `1.`, `a)`...) and nesting depth, starting at 0.
+    ListItem { marker: String, depth: usize, text: String },
+    /// Preformatted lines, with the block's common indentation removed.
+    Literal(Vec<String>),
+}
+
+/// Splits an RST document into blocks, with inline markup already removed.
+pub fn parse(content: &str) -> Vec<Block> {
+    Parser { lines: content.lines().collect(), pos: 0, adornments: Vec::new(), blocks: Vec::new() }.run()
+}
+
+struct Parser<'a> {
+    lines: Vec<&'a str>,
+    pos: usize,
+    /// Adornment styles in order of first use: the character and whether it has an overline.
+    adornments: Vec<(char, bool)>,
+    blocks: Vec<Block>,
+}
+
+impl<'a> Parser<'a> {
+    fn run(mut self) -> Vec<Block> {
+        // Indentation of the open lists, outermost first, and how many enumerated items each has
+        // so far, for auto-numbered (`#.`) items.
+        let mut list_indents: Vec<usize> = Vec::new();
+        let mut item_counts: Vec<usize> = Vec::new();
+        while self.pos < self.lines.len() {
+            let line = self.lines[self.pos];
+            if line.trim().is_empty() {
+                self.pos += 1;
+                continue;
+            }
+            if self.heading() {
+                list_indents.clear();
+                item_counts.clear();
+                continue;
+            }
+            // A transition (a lone `----` line) between sections.
+            if adornment(line).is_some() {
+                self.pos += 1;
+                continue;
+            }
+
+            let indent = indentation(line);
+            let trimmed = line.trim();
+            if let Some(rest) = explicit_markup(trimmed) {
+                self.directive(rest, indent);
+                continue;
+            }
+            if let Some((marker, text)) = list_marker(trimmed) {
+                while list_indents.last().is_some_and(|&i| i > indent) {
+                    list_indents.pop();
+                    item_counts.pop();
+                }
+                if list_indents.last() != Some(&indent) {
+                    list_indents.push(indent);
+                    item_counts.push(0);
+                }
+                let depth = list_indents.len() - 1;
+                // A bullet between enumerated items starts a new list.
+                item_counts[depth] = if marker == "•" { 0 } else { item_counts[depth] + 1 };
+                let marker = marker.replacen('#', &item_counts[depth].to_string(), 1);
+                let text_indent = indent + trimmed.len() - text.len();
+                let text = self.gather(text, text_indent);
+                self.push_text(text, |text| Block::ListItem { marker, depth, text });
+                continue;
+            }
+            if list_indents.last().is_some_and(|&i| indent <= i) {
+                let open = list_indents.iter().filter(|&&i| i < indent).count();
+                list_indents.truncate(open);
+                item_counts.truncate(open);
+            }
+            let text = self.gather(trimmed, indent);
+            self.push_text(text, Block::Paragraph);
+        }
+        self.blocks
+    }
+
+    /// Consumes a section title at the current line, if there is one.
+    fn heading(&mut self) -> bool {
+        let line = self.lines[self.pos];
+        let next = self.lines.get(self.pos + 1).copied();
+        let after = self.lines.get(self.pos + 2).copied();
+
+        // Overline, title, underline.
+        if let (Some(c), Some(title), Some(under)) = (adornment(line), next, after)
+            && !title.trim().is_empty()
+            && adornment(title).is_none()
+            && adornment(under) == Some(c)
+        {
+            self.push_heading(c, true, title.trim());
+            self.pos += 3;
+            return true;
+        }
+        // Title, underline.
+        if indentation(line) == 0
+            && adornment(line).is_none()
+            && let Some(c) = next.and_then(adornment)
+            && next.is_some_and(|under| under.trim().chars().count() >= line.trim().chars().count().min(4))
+        {
+            self.push_heading(c, false, line.trim());
+            self.pos += 2;
+            return true;
+        }
+        false
+    }
+
+    fn push_heading(&mut self, c: char, overline: bool, title: &str) {
+        let level = match self.adornments.iter().position(|&a| a == (c, overline)) {
+            Some(index) => index + 1,
+            None => {
+                self.adornments.push((c, overline));
+                self.adornments.len()
+            }
+        };
+        self.blocks.push(Block::Heading { level, text: inline_text(title) });
+    }
+
+    /// Handles a line starting with `..`: code directives become literal blocks, comments and
+    /// link targets are dropped and anything else is kept as text.
+    fn directive(&mut self, rest: &str, indent: usize) {
+        self.pos += 1;
+        let body = self.indented_block(indent);
+        match rest.split_once("::") {
+            Some((name, _)) if matches!(name.trim(), "code" | "code-block" | "sourcecode") => {
+                // Directive options (`:linenos:`) come before the code.
+                let code: Vec<String> = body.into_iter()
+                    .skip_while(|line| line.trim_start().starts_with(':') || line.trim().is_empty())
+                    .collect();
+                self.blocks.push(Block::Literal(code));
+            }
+            Some((name, argument)) if !name.trim().is_empty() && !name.contains(' ') => {
+                let name = name.trim();
+                log::debug!("Rendering unsupported RST directive as text: {}", name);
+                let mut label = capitalize(name);
+                if !argument.trim().is_empty() {
+                    label = format!("{}: {}", label, inline_text(argument.trim()));
+                }
+                self.blocks.push(Block::Paragraph(label));
+                for paragraph in body.split(|line| line.trim().is_empty()).filter(|p| !p.is_empty()) {
+                    let text = paragraph.iter().map(|line| line.trim()).collect::<Vec<_>>().join(" ");
+                    self.blocks.push(Block::Paragraph(inline_text(&text)));
+                }
+            }
+            // Comments, hyperlink targets (`.. _name: url`) and substitution definitions.
+            _ => {}
+        }
+    }
+
+    /// Joins the continuation lines, indented at least `text_indent`, of a paragraph or list
+    /// item that starts with `first`.
+    fn gather(&mut self, first: &str, text_indent: usize) -> String {
+        let mut text = first.to_string();
+        self.pos += 1;
+        while let Some(&line) = self.lines.get(self.pos) {
+            if line.trim().is_empty() || indentation(line) < text_indent {
+                break;
+            }
+            let trimmed = line.trim();
+            if list_marker(trimmed).is_some() || explicit_markup(trimmed).is_some() || adornment(line).is_some() {
+                break;
+            }
+            text.push(' ');
+            text.push_str(trimmed);
+            self.pos += 1;
+        }
+        text
+    }
+
+    /// Pushes a paragraph-like block, splitting off the literal block a trailing `::` announces.
+    fn push_text(&mut self, text: String, block: impl FnOnce(String) -> Block) {
+        let (text, literal) = match text.strip_suffix("::") {
+            // `Paragraph::` keeps one colon, `Paragraph ::` and a lone `::` keep none.
+            Some(stripped) if stripped.ends_with(' ') || stripped.is_empty() => (stripped.trim_end().to_string(), true),
+            Some(stripped) => (format!("{}:", stripped), true),
+            None => (text, false),
+        };
+        if !text.is_empty() {
+            self.blocks.push(block(inline_text(&text)));
+        }
+        if literal {
+            let base = self.lines[..self.pos].iter().rev()
+                .find(|line| !line.trim().is_empty())
+                .map_or(0, |line| indentation(line));
+            let lines = self.indented_block(base);
+            if !lines.is_empty() {
+                self.blocks.push(Block::Literal(lines));
+            }
+        }
+    }
+
+    /// Reads the lines indented deeper than `base`, blank lines included, and removes their
+    /// common indentation.
+    fn indented_block(&mut self, base: usize) -> Vec<String> {
+        while self.lines.get(self.pos).is_some_and(|line| line.trim().is_empty()) {
+            self.pos += 1;
+        }
+        let start = self.pos;
+        let mut end = start;
+        while let Some(&line) = self.lines.get(self.pos) {
+            if !line.trim().is_empty() {
+                if indentation(line) <= base {
+                    break;
+                }
+                end = self.pos + 1;
+            }
+            self.pos += 1;
+        }
+        self.pos = end.max(start);
+        let block = &self.lines[start..end];
+        let common = block.iter().filter(|l| !l.trim().is_empty()).map(|l| indentation(l)).min().unwrap_or(0);
+        block.iter().map(|line| line.chars().skip(common).collect()).collect()
+    }
+}
+
+/// The rest of an explicit markup line (`.. something`), which starts directives and comments.
+fn explicit_markup(line: &str) -> Option<&str> {
+    if line == ".." {
+        return Some("");
+    }
+    line.strip_prefix(".. ").map(str::trim)
+}
+
+fn indentation(line: &str) -> usize {
+    line.chars().take_while(|c| c.is_whitespace()).count()
+}
+
+/// The character a title adornment line repeats, such as `=` in `=====`.
+fn adornment(line: &str) -> Option<char> {
+    let line = line.trim_end();
+    let c = line.chars().next()?;
+    (line.chars().count() >= 2 && c.is_ascii_punctuation() && line.chars().all(|x| x == c)).then_some(c)
+}
+
+/// Splits a list item into its display marker and text.
+fn list_marker(line: &str) -> Option<(String, &str)> {
+    for bullet in ["- ", "* ", "+ ", "• "] {
+        if let Some(text) = line.strip_prefix(bullet) {
+            return Some(("•".to_string(), text.trim_start()));
+        }
+    }
+    let (marker, text) = line.split_once(' ')?;
+    let enumerator = marker.strip_prefix('(').and_then(|m| m.strip_suffix(')'))
+        .or_else(|| marker.strip_suffix('.'))
+        .or_else(|| marker.strip_suffix(')'))?;
+    let valid = enumerator == "#"
+        || (!enumerator.is_empty() && enumerator.len() <= 3 && enumerator.chars().all(|c| c.is_ascii_digit()))
+        || (enumerator.len() == 1 && enumerator.chars().all(|c| c.is_ascii_alphabetic()));
+    valid.then(|| (marker.to_string(), text.trim_start()))
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars.next().map(|c| c.to_uppercase().chain(chars).collect()).unwrap_or_default()
+}
+
+/// Removes inline markup: emphasis, literals, interpreted text roles and hyperlink references.
+/// Named links keep their URL in parentheses.
+pub fn inline_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if let Some((inner, after)) = delimited(rest, "``", "``") {
+            out.push_str(inner);
+            rest = after;
+        } else if let Some((inner, after)) = delimited(rest, "**", "**") {
+            out.push_str(inner);
+            rest = after;
+        } else if let Some((inner, after)) = delimited(rest, "*", "*") {
+            out.push_str(inner);
+            rest = after;
+        } else if let Some((inner, after)) = delimited(rest, "`", "`") {
+            // `text <url>`_ and `text`_ references, or interpreted text.
+            match inner.rsplit_once(" <").and_then(|(label, url)| Some((label, url.strip_suffix('>')?))) {
+                Some((label, url)) => out.push_str(&format!("{} ({})", label.trim(), url)),
+                None => out.push_str(inner.strip_prefix('<').and_then(|u| u.strip_suffix('>')).unwrap_or(inner)),
+            }
+            rest = after.strip_prefix("__").or_else(|| after.strip_prefix('_')).unwrap_or(after);
+        } else if let Some(after) = role_prefix(rest) {
+            rest = after;
+        } else {
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    out
+}
+
+/// Text between `open` and `close` at the start of `text`, if `text` starts with a complete,
+/// non-empty span.
+fn delimited<'t>(text: &'t str, open: &str, close: &str) -> Option<(&'t str, &'t str)> {
+    let body = text.strip_prefix(open)?;
+    if body.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let end = body.find(close)?;
+    (end > 0).then(|| (&body[..end], &body[end + close.len()..]))
+}
+
+/// Skips a `:role:` that directly precedes interpreted text, as in ``:func:`name` ``.
+fn role_prefix(text: &str) -> Option<&str> {
+    let body = text.strip_prefix(':')?;
+    let end = body.find(':')?;
+    let role = &body[..end];
+    let after = &body[end + 1..];
+    (!role.is_empty() && role.chars().all(|c| c.is_ascii_alphanumeric() || "-_.+".contains(c)) && after.starts_with('`'))
+        .then_some(after)
+}
@@ -0,0 +1,171 @@
+//! Scratch space for intermediate files: extracted ZIP entries, clipboard contents, rendered
+//! graphs and PDFs waiting to be appended.
+//!
+//! Each run gets a folder of its own in the configured temporary folder (the system's by
+//! default), created by `tempfile` under a random `topdf_` name that only the user can open.
+//! Every path handed out is removed when its [`Scratch`] guard is dropped, so a failed
+//! conversion cleans up after itself, and the run's folder is removed when the [`RunGuard`]
+//! created at startup is dropped.  Folders left behind by runs that crashed are removed by the
+//! next run once they are a day old.
+
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tempfile::TempDir;
+
+/// The start of the names of the runs' folders.
+const PREFIX: &str = "topdf_";
+
+/// How long a folder of another run may go unchanged before it counts as left behind.
+const STALE_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+
+static BASE: OnceLock<PathBuf> = OnceLock::new();
+
+/// The run's folder, created when something first asks for scratch space.
+static RUN_DIR: Mutex<Option<TempDir>> = Mutex::new(None);
+
+/// Sets the folder the run's scratch folder is created in; `None` uses the system's temporary
+/// folder.  Only the first call counts, and only before anything has asked for scratch space.
+pub fn configure(base: Option<PathBuf>) {
+    let _ = BASE.set(base.unwrap_or_else(std::env::temp_dir));
+}
+
+fn base() -> &'static Path {
+    BASE.get_or_init(std::env::temp_dir)
+}
+
+fn run_dir() -> Result<PathBuf> {
+    let mut run_dir = RUN_DIR.lock().unwrap_or_else(PoisonError::into_inner);
+    // Created again if something removed it while the app was open.
+    if let Some(dir) = run_dir.as_ref()
+        && dir.path().is_dir()
+    {
+        return Ok(dir.path().to_path_buf());
+    }
+    let base = base();
+    std::fs::create_dir_all(base).with_context(|| format!("Failed to create temporary folder {}", base.display()))?;
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(PREFIX);
+    // Others must not read the intermediate files of a shared temporary folder.
+    #[cfg(unix)]
+    builder.permissions(std::os::unix::fs::PermissionsExt::from_mode(0o700));
+    let dir = builder
+        .tempdir_in(base)
+        .with_context(|| format!("Failed to create a temporary folder in {}", base.display()))?;
+    let path = dir.path().to_path_buf();
+    *run_dir = Some(dir);
+    Ok(path)
+}
+
+/// A unique path in the run's scratch folder ending in `name`.
+fn unique_path(name: &str) -> Result<PathBuf> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    Ok(run_dir()?.join(format!("{}_{}", n, name)))
+}
+
+/// A scratch file or folder, removed with everything in it when dropped.
+#[derive(Debug)]
+pub struct Scratch(PathBuf);
+
+/// A path for a scratch file ending in `name`; the file itself is left to the caller to write.
+pub fn file(name: &str) -> Result<Scratch> {
+    Ok(Scratch(unique_path(name)?))
+}
+
+/// A new, empty scratch folder ending in `name`.
+pub fn dir(name: &str) -> Result<Scratch> {
+    let path = unique_path(name)?;
+    std::fs::create_dir_all(&path).with_context(|| format!("Failed to create temporary folder {}", path.display()))?;
+    Ok(Scratch(path))
+}
+
+impl Deref for Scratch {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let result = if self.0.is_dir() { std::fs::remove_dir_all(&self.0) } else { std::fs::remove_file(&self.0) };
+        if let Err(e) = result
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            log::warn!("Failed to remove temporary {:?}: {}", self.0, e);
+        }
+    }
+}
+
+/// Removes the run's scratch folder, and whatever guards left behind in it, when dropped.
+pub struct RunGuard(());
+
+impl RunGuard {
+    /// Also removes the folders that earlier runs left behind.
+    pub fn new() -> Self {
+        remove_stale(base());
+        RunGuard(())
+    }
+}
+
+impl Drop for RunGuard {
+    fn drop(&mut self) {
+        let dir = RUN_DIR.lock().unwrap_or_else(PoisonError::into_inner).take();
+        if let Some(dir) = dir {
+            let path = dir.path().to_path_buf();
+            if let Err(e) = dir.close()
+                && e.kind() != std::io::ErrorKind::NotFound
+            {
+                log::warn!("Failed to remove temporary folder {:?}: {}", path, e);
+            }
+        }
+    }
+}
+
+/// Removes the run folders in `base` that have not changed for [`STALE_AFTER`].
+fn remove_stale(base: &Path) {
+    let Ok(entries) = std::fs::read_dir(base) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let stale = entry.metadata().ok()
+            .filter(|metadata| metadata.is_dir())
+            .and_then(|metadata| metadata.modified().ok())
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > STALE_AFTER);
+        if stale && entry.file_name().to_string_lossy().starts_with(PREFIX) {
+            log::info!("Removing the temporary folder {:?} of an earlier run", entry.path());
+            if let Err(e) = std::fs::remove_dir_all(entry.path()) {
+                log::warn!("Failed to remove temporary folder {:?}: {}", entry.path(), e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scratch_folders_live_in_a_private_run_folder_until_dropped() {
+        let scratch = dir("test").unwrap();
+        let run = scratch.parent().unwrap().to_path_buf();
+        assert!(run.file_name().unwrap().to_string_lossy().starts_with(PREFIX));
+        assert_ne!(run.file_name().unwrap().to_string_lossy(), format!("{}{}", PREFIX, std::process::id()));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&run).unwrap().permissions().mode() & 0o077, 0);
+        }
+        let path = scratch.to_path_buf();
+        assert!(path.is_dir());
+        drop(scratch);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,351 @@
+//! Local HTTP service that lets scripts and other programs use the converter (`--serve`).
+//!
+//! `POST /convert` takes a `multipart/form-data` upload with a `file` part, whose file name
+//! decides how it is read, and an optional `options` part holding [`ConversionOptions`] as JSON
+//! (the same keys as a preset; unset keys use the defaults).  The response body is the converted
+//! file.  Failures are answered with a status code and a plain-text reason.
+//!
+//! The service listens on localhost only, and refuses requests that a browser sends on behalf of
+//! a web page from anywhere else, as told by their `Origin` header.  Requests are handled on a
+//! [`WorkerPool`] sized by the configured performance mode; further requests wait until a worker
+//! is free.
+
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use crate::config::AppConfig;
+use crate::converter::{self, ConversionOptions, FileType, FontSet, OutputFormat};
+use crate::error::ConversionError;
+use crate::pipeline::WorkerPool;
+use crate::scratch;
+
+pub const DEFAULT_PORT: u16 = 8080;
+/// Uploads larger than this are refused.
+const MAX_BODY: usize = 200 * 1024 * 1024;
+const MAX_HEAD: usize = 64 * 1024;
+/// How long a client may take to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Serves conversions on `127.0.0.1:port` until the process is stopped.
+pub fn run(port: u16) -> Result<()> {
+    let config = AppConfig::load();
+    crate::styles::install(config.styles.clone());
+    let fonts = Arc::new(crate::ui::load_fonts(&config.fonts)?);
+    let listener = TcpListener::bind(("127.0.0.1", port)).with_context(|| format!("Cannot listen on port {}", port))?;
+    let pool = WorkerPool::new(config.performance.workers(), config.performance.pause());
+    log::info!("Serving conversions on http://127.0.0.1:{}/convert", port);
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let fonts = fonts.clone();
+                pool.execute(move || handle(stream, &fonts));
+            }
+            Err(e) => log::warn!("Failed to accept a connection: {}", e),
+        }
+    }
+    Ok(())
+}
+
+struct Request {
+    method: String,
+    /// The request target without its query string.
+    path: String,
+    /// Header names in lowercase.
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+}
+
+struct Response {
+    status: u16,
+    reason: &'static str,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn error(status: u16, reason: &'static str, message: impl Into<String>) -> Self {
+        let mut body = message.into().into_bytes();
+        body.push(b'\n');
+        Self { status, reason, content_type: "text/plain; charset=utf-8", body }
+    }
+
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self::error(400, "Bad Request", message)
+    }
+}
+
+fn handle(mut stream: TcpStream, fonts: &FontSet) {
+    let peer = stream.peer_addr().map_or_else(|_| "unknown client".to_string(), |addr| addr.to_string());
+    let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+    let response = match read_request(&mut stream) {
+        Ok(request) => {
+            log::info!("{} {} from {} ({} bytes)", request.method, request.path, peer, request.body.len());
+            respond(&request, fonts)
+        }
+        Err(response) => response,
+    };
+    log::info!("Answering {} with {} {}", peer, response.status, response.reason);
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status, response.reason, response.content_type, response.body.len(),
+    );
+    let written = stream.write_all(head.as_bytes())
+        .and_then(|()| stream.write_all(&response.body))
+        .and_then(|()| stream.flush());
+    if let Err(e) = written {
+        log::warn!("Failed to send the response to {}: {}", peer, e);
+    }
+}
+
+/// Reads the request line, headers and a `Content-Length` body.  Chunked uploads are not
+/// supported.
+fn read_request(stream: &mut TcpStream) -> Result<Request, Response> {
+    let mut reader = BufReader::new(stream);
+    let mut head_size = 0;
+    let mut read_line = |reader: &mut BufReader<&mut TcpStream>| -> Result<String, Response> {
+        let mut line = Vec::new();
+        reader.by_ref().take((MAX_HEAD - head_size) as u64).read_until(b'\n', &mut line)
+            .map_err(|e| Response::bad_request(format!("Failed to read the request: {}", e)))?;
+        head_size += line.len();
+        if !line.ends_with(b"\n") {
+            return Err(if head_size >= MAX_HEAD {
+                Response::error(431, "Request Header Fields Too Large", "The request headers are too large")
+            } else {
+                Response::bad_request("The request ended early")
+            });
+        }
+        String::from_utf8(line).map(|line| line.trim_end().to_string())
+            .map_err(|_| Response::bad_request("The request headers are not UTF-8"))
+    };
+
+    let request_line = read_line(&mut reader)?;
+    let mut words = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (words.next(), words.next()) else {
+        return Err(Response::bad_request("Malformed request line"));
+    };
+    let (method, path) = (method.to_string(), target.split('?').next().unwrap_or_default().to_string());
+    let mut headers = Vec::new();
+    loop {
+        let line = read_line(&mut reader)?;
+        if line.is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(Response::bad_request(format!("Malformed header line {:?}", line)));
+        };
+        headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+    }
+
+    let mut request = Request { method, path, headers, body: Vec::new() };
+    if request.header("transfer-encoding").is_some() {
+        return Err(Response::error(411, "Length Required", "Send the upload with a Content-Length"));
+    }
+    let length = match request.header("content-length") {
+        None => 0,
+        Some(value) => value.parse::<usize>().map_err(|_| Response::bad_request("Invalid Content-Length"))?,
+    };
+    if length > MAX_BODY {
+        return Err(Response::error(413, "Content Too Large", format!("Uploads are limited to {} MB", MAX_BODY / 1024 / 1024)));
+    }
+    request.body = vec![0; length];
+    reader.read_exact(&mut request.body)
+        .map_err(|e| Response::bad_request(format!("Failed to read the request body: {}", e)))?;
+    Ok(request)
+}
+
+fn respond(request: &Request, fonts: &FontSet) -> Response {
+    // Browsers send cross-site form uploads without asking first, so any web page could
+    // otherwise post to the service.
+    if let Some(origin) = request.header("origin")
+        && !is_local_origin(origin)
+    {
+        return Response::error(403, "Forbidden", "Requests from web pages are only accepted from localhost");
+    }
+    if request.path != "/convert" {
+        return Response::error(404, "Not Found", "Only POST /convert is served");
+    }
+    if request.method != "POST" {
+        return Response::error(405, "Method Not Allowed", "Use POST to upload the file to convert");
+    }
+    let Some(boundary) = request.header("content-type").and_then(multipart_boundary) else {
+        return Response::bad_request("Expected a multipart/form-data upload");
+    };
+    let parts = match multipart_parts(&request.body, boundary) {
+        Ok(parts) => parts,
+        Err(e) => return Response::bad_request(format!("Malformed upload: {:#}", e)),
+    };
+    let Some(file) = parts.iter().find(|part| part.name == "file") else {
+        return Response::bad_request("The upload has no \"file\" part");
+    };
+    let Some(file_name) = file.file_name.as_deref() else {
+        return Response::bad_request("The \"file\" part has no file name");
+    };
+    let Some(input_name) = input_name(file_name) else {
+        return Response::error(415, "Unsupported Media Type", format!("{:?} is not a file type Topdf converts", file_name));
+    };
+    let options = match parts.iter().find(|part| part.name == "options") {
+        Some(part) => match serde_json::from_slice::<ConversionOptions>(part.body) {
+            Ok(options) => options,
+            Err(e) => return Response::bad_request(format!("Invalid options: {}", e)),
+        },
+        None => ConversionOptions::default(),
+    };
+    convert(file_name, &input_name, file.body, fonts, &options)
+}
+
+/// `input.` and the extension of the uploaded `file_name`, if it is one Topdf converts.  The
+/// rest of the client's name is not used for the stored file, as it could be a path.
+fn input_name(file_name: &str) -> Option<String> {
+    let (_, extension) = file_name.rsplit_once('.')?;
+    if extension.is_empty() || !extension.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    let name = format!("input.{}", extension);
+    (!matches!(FileType::from_path(Path::new(&name)), FileType::Unknown)).then_some(name)
+}
+
+/// Whether `origin`, the `Origin` header of a request, is a page served from this computer.
+fn is_local_origin(origin: &str) -> bool {
+    let Some(host) = origin.strip_prefix("http://").or_else(|| origin.strip_prefix("https://")) else {
+        return false;
+    };
+    let host = match host.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    matches!(host.to_ascii_lowercase().as_str(), "localhost" | "127.0.0.1" | "::1")
+}
+
+/// Converts the uploaded `data` through temporary files, storing it as `input_name`, and
+/// answers with the output.
+fn convert(file_name: &str, input_name: &str, data: &[u8], fonts: &FontSet, options: &ConversionOptions) -> Response {
+    let result = scratch::dir("serve")
+        .map_err(|e| ConversionError::Io(format!("{:#}", e)))
+        .and_then(|dir| {
+            let input = dir.join(input_name);
+            let output = dir.join(format!("output.{}", options.output_format.extension()));
+            fs::write(&input, data).map_err(|e| ConversionError::Io(format!("Failed to store the upload: {}", e)))?;
+            converter::convert(&input, &output, fonts, options)?;
+            fs::read(&output).map_err(|e| ConversionError::Io(format!("Failed to read the output: {}", e)))
+        });
+
+    match result {
+        Ok(body) => Response { status: 200, reason: "OK", content_type: content_type(options.output_format), body },
+        Err(e) => {
+            log::error!("Conversion failed for uploaded {:?}: {}", file_name, e);
+            match e {
+                ConversionError::Io(_) | ConversionError::Render(_) | ConversionError::RenderPanic { .. } => {
+                    Response::error(500, "Internal Server Error", e.to_string())
+                }
+                _ => Response::error(422, "Unprocessable Content", e.to_string()),
+            }
+        }
+    }
+}
+
+fn content_type(format: OutputFormat) -> &'static str {
+    match format {
+        OutputFormat::Pdf => "application/pdf",
+        OutputFormat::Txt => "text/plain; charset=utf-8",
+        OutputFormat::Png => "image/png",
+        OutputFormat::Markdown => "text/markdown; charset=utf-8",
+    }
+}
+
+fn multipart_boundary(content_type: &str) -> Option<&str> {
+    let (mime, params) = content_type.split_once(';')?;
+    if !mime.trim().eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    params.split(';')
+        .filter_map(|param| param.trim().split_once('='))
+        .find(|(key, _)| key.eq_ignore_ascii_case("boundary"))
+        .map(|(_, value)| value.trim_matches('"'))
+        .filter(|boundary| !boundary.is_empty())
+}
+
+struct Part<'a> {
+    name: String,
+    file_name: Option<String>,
+    body: &'a [u8],
+}
+
+/// Splits a `multipart/form-data` body into its parts.
+fn multipart_parts<'a>(body: &'a [u8], boundary: &str) -> Result<Vec<Part<'a>>> {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let mut position = find(body, &delimiter, 0).context("the boundary does not occur in the body")? + delimiter.len();
+    let mut parts = Vec::new();
+    // Each delimiter is followed by "--" after the last part, or by a line break and a part.
+    while !body[position..].starts_with(b"--") {
+        let start = position + 2;
+        let end = find(body, &[b"\r\n", delimiter.as_slice()].concat(), start).context("a part is not terminated")?;
+        let part = &body[start.min(end)..end];
+        let split = find(part, b"\r\n\r\n", 0).context("a part has no header section")?;
+        let headers = String::from_utf8_lossy(&part[..split]);
+        let mut name = None;
+        let mut file_name = None;
+        for line in headers.lines() {
+            let Some((key, value)) = line.split_once(':') else { continue };
+            if !key.trim().eq_ignore_ascii_case("content-disposition") {
+                continue;
+            }
+            for param in value.split(';').filter_map(|param| param.trim().split_once('=')) {
+                match param.0 {
+                    "name" => name = Some(param.1.trim_matches('"').to_string()),
+                    "filename" => file_name = Some(param.1.trim_matches('"').to_string()),
+                    _ => {}
+                }
+            }
+        }
+        parts.push(Part {
+            name: name.context("a part has no name")?,
+            file_name,
+            body: &part[split + 4..],
+        });
+        position = end + 2 + delimiter.len();
+    }
+    Ok(parts)
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack.get(from..)?.windows(needle.len()).position(|window| window == needle).map(|i| i + from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uploads_are_stored_under_their_extension_only() {
+        assert_eq!(input_name("notes.md").as_deref(), Some("input.md"));
+        assert_eq!(input_name("C:\\Users\\me\\Report.DOCX").as_deref(), Some("input.DOCX"));
+        assert_eq!(input_name("../../etc/data.csv").as_deref(), Some("input.csv"));
+        assert_eq!(input_name("C:evil.bat"), None);
+        assert_eq!(input_name("D:x"), None);
+        assert_eq!(input_name("notes"), None);
+        assert_eq!(input_name("notes.md/"), None);
+    }
+
+    #[test]
+    fn only_local_pages_may_post() {
+        assert!(is_local_origin("http://localhost:3000"));
+        assert!(is_local_origin("http://127.0.0.1"));
+        assert!(is_local_origin("https://[::1]:8443"));
+        assert!(!is_local_origin("https://example.com"));
+        assert!(!is_local_origin("http://localhost.example.com"));
+        assert!(!is_local_origin("null"));
+    }
+}
@@ -0,0 +1,222 @@
+//! Shaded table rows and page backgrounds.
+//!
+//! genpdf can only draw lines, and it decorates a table cell after printing the cell's text.
+//! So [`StripeDecorator`] outlines every shaded cell, and [`BackgroundDecorator`] every page,
+//! with a stroke in a reserved color, and [`fill`] later replaces those outlines with filled
+//! rectangles painted below the page's text.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use genpdf::elements::{CellDecorator, FrameCellDecorator};
+use genpdf::{render, style, Context as PdfContext, PageDecorator, Position};
+use lopdf::content::{Content, Operation};
+use lopdf::Object;
+
+use crate::converter::RgbColor;
+
+/// Background of every other body row.
+const ROW_FILL: [f64; 3] = [0.94, 0.94, 0.94];
+/// Background of the header row, a little darker than the body rows.
+const HEADER_FILL: [f64; 3] = [0.85, 0.85, 0.85];
+
+/// Marker strokes have full red and green, which no link marker has (see
+/// [`crate::links`]); blue tells the fills apart.
+const HEADER_MARKER: u8 = 0;
+const ROW_MARKER: u8 = 26;
+const PAGE_MARKER: u8 = 52;
+
+/// Frames every cell like [`FrameCellDecorator`] and shades the header row and every other
+/// body row.
+pub struct StripeDecorator {
+    frame: FrameCellDecorator,
+    header: bool,
+}
+
+impl StripeDecorator {
+    /// `header` says whether the first row of the table is a header row.
+    pub fn new(header: bool) -> Self {
+        Self { frame: FrameCellDecorator::new(true, true, false), header }
+    }
+}
+
+impl CellDecorator for StripeDecorator {
+    fn set_table_size(&mut self, num_columns: usize, num_rows: usize) {
+        self.frame.set_table_size(num_columns, num_rows);
+    }
+
+    fn decorate_cell(&mut self, column: usize, row: usize, has_more: bool, area: render::Area<'_>, style: style::Style) {
+        let marker = match row.checked_sub(self.header as usize) {
+            None => Some(HEADER_MARKER),
+            Some(body_row) if body_row % 2 == 1 => Some(ROW_MARKER),
+            Some(_) => None,
+        };
+        if let Some(marker) = marker {
+            outline(&area, marker);
+        }
+        self.frame.decorate_cell(column, row, has_more, area, style);
+    }
+}
+
+/// Outlines all of `area` in the color of `marker`.
+fn outline(area: &render::Area<'_>, marker: u8) {
+    let size = area.size();
+    let corners = vec![
+        Position::new(0, 0),
+        Position::new(size.width, 0),
+        Position::new(size.width, size.height),
+        Position::new(0, size.height),
+        Position::new(0, 0),
+    ];
+    area.draw_line(corners, style::Style::new().with_color(style::Color::Rgb(255, 255, marker)));
+}
+
+/// Decorates pages like the decorator it wraps, after outlining the whole page so that [`fill`]
+/// paints its background.
+pub struct BackgroundDecorator<D> {
+    inner: D,
+}
+
+impl<D: PageDecorator> BackgroundDecorator<D> {
+    pub fn new(inner: D) -> Self {
+        Self { inner }
+    }
+}
+
+impl<D: PageDecorator> PageDecorator for BackgroundDecorator<D> {
+    fn decorate_page<'a>(&mut self, context: &PdfContext, area: render::Area<'a>, style: style::Style) -> Result<render::Area<'a>, genpdf::error::Error> {
+        outline(&area, PAGE_MARKER);
+        self.inner.decorate_page(context, area, style)
+    }
+}
+
+/// The fill color a stroke color (`RG` operands) stands for, if it is a marker.
+fn marker_fill(operands: &[Object], background: RgbColor) -> Option<[f64; 3]> {
+    let components: Vec<f64> = operands.iter().map(number).collect::<Option<_>>()?;
+    // printpdf writes two decimals, so compare in hundredths.
+    let hundredths: Vec<i64> = components.iter().map(|c| (c * 100.0).round() as i64).collect();
+    match hundredths.as_slice() {
+        [100, 100, blue] if *blue == hundredths_of(HEADER_MARKER) => Some(HEADER_FILL),
+        [100, 100, blue] if *blue == hundredths_of(ROW_MARKER) => Some(ROW_FILL),
+        [100, 100, blue] if *blue == hundredths_of(PAGE_MARKER) => {
+            Some([background.r, background.g, background.b].map(|c| c as f64 / 255.0))
+        }
+        _ => None,
+    }
+}
+
+fn hundredths_of(component: u8) -> i64 {
+    (component as f64 * 100.0 / 255.0).round() as i64
+}
+
+fn number(object: &Object) -> Option<f64> {
+    object.as_f64().ok().or_else(|| object.as_i64().ok().map(|i| i as f64))
+}
+
+/// Replaces the marker outlines in the PDF at `path` with filled rectangles drawn before
+/// anything else on their page; page outlines are filled with `background`.
+pub fn fill(path: &Path, background: RgbColor) -> Result<()> {
+    let mut pdf = lopdf::Document::load(path).context("Failed to reopen PDF for table shading")?;
+    let pages: Vec<_> = pdf.page_iter().collect();
+    let mut filled = 0;
+    for page in pages {
+        let content = Content::decode(&pdf.get_page_content(page)?).context("Failed to read page content")?;
+        let mut fills = Vec::new();
+        let mut operations = Vec::with_capacity(content.operations.len());
+        let mut ops = content.operations.into_iter().peekable();
+        while let Some(op) = ops.next() {
+            let color = (op.operator == "RG").then(|| marker_fill(&op.operands, background)).flatten();
+            let Some(color) = color else {
+                operations.push(op);
+                continue;
+            };
+            // draw_line writes `m`, one `l` per further corner and `S` after the color.
+            let path: Vec<Operation> = std::iter::from_fn(|| ops.next_if(|op| matches!(op.operator.as_str(), "m" | "l" | "S"))).collect();
+            let points: Vec<(f64, f64)> = path.iter()
+                .filter(|op| op.operator != "S")
+                .filter_map(|op| Some((number(op.operands.first()?)?, number(op.operands.get(1)?)?)))
+                .collect();
+            let (Some(x0), Some(y0), Some(x1), Some(y1)) = (
+                points.iter().map(|p| p.0).reduce(f64::min),
+                points.iter().map(|p| p.1).reduce(f64::min),
+                points.iter().map(|p| p.0).reduce(f64::max),
+                points.iter().map(|p| p.1).reduce(f64::max),
+            ) else {
+                continue;
+            };
+            let real = |value: f64| Object::Real(value as _);
+            fills.push(Operation::new("q", vec![]));
+            fills.push(Operation::new("rg", color.into_iter().map(real).collect()));
+            fills.push(Operation::new("re", vec![real(x0), real(y0), real(x1 - x0), real(y1 - y0)]));
+            fills.push(Operation::new("f", vec![]));
+            fills.push(Operation::new("Q", vec![]));
+        }
+        if fills.is_empty() {
+            continue;
+        }
+        filled += fills.len() / 5;
+        fills.extend(operations);
+        pdf.change_page_content(page, Content { operations: fills }.encode()?)?;
+    }
+    pdf.save(path).context("Failed to save PDF with table shading")?;
+    log::info!("Shaded {} table cells in {:?}", filled, path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::{self, ConversionOptions};
+
+    /// The color and bottom edge of each filled rectangle on the first page of `path`.
+    fn fills(path: &Path) -> Vec<([f64; 3], f64)> {
+        let pdf = lopdf::Document::load(path).unwrap();
+        let page = pdf.page_iter().next().unwrap();
+        let content = Content::decode(&pdf.get_page_content(page).unwrap()).unwrap();
+        content.operations.windows(2)
+            .filter(|ops| ops[0].operator == "rg" && ops[1].operator == "re")
+            .map(|ops| {
+                let color: Vec<f64> = ops[0].operands.iter().map(|c| (number(c).unwrap() * 100.0).round() / 100.0).collect();
+                ([color[0], color[1], color[2]], number(&ops[1].operands[1]).unwrap())
+            })
+            .collect()
+    }
+
+    /// The distinct bottom edges of the fills in `color`, top row first.
+    fn rows(fills: &[([f64; 3], f64)], color: [f64; 3]) -> Vec<f64> {
+        let mut rows: Vec<f64> = fills.iter().filter(|(c, _)| *c == color).map(|(_, y)| *y).collect();
+        rows.sort_by(|a, b| b.total_cmp(a));
+        rows.dedup_by(|a, b| (*a - *b).abs() < 0.01);
+        rows
+    }
+
+    #[test]
+    fn header_and_every_other_body_row_are_shaded() {
+        let dir = crate::scratch::dir("stripes").unwrap();
+        let input = dir.join("table.csv");
+        std::fs::write(&input, "id,name\n1,a\n2,b\n3,c\n4,d\n5,e\n").unwrap();
+        let output = dir.join("table.pdf");
+        let options = ConversionOptions { table_stripes: true, ..ConversionOptions::default() };
+        converter::convert(&input, &output, &converter::bundled_fonts(), &options).unwrap();
+
+        let fills = fills(&output);
+        // One fill per cell: both header cells, and both cells of body rows 2 and 4.
+        assert_eq!(fills.iter().filter(|(c, _)| *c == HEADER_FILL).count(), 2);
+        assert_eq!(fills.iter().filter(|(c, _)| *c == ROW_FILL).count(), 4);
+        let header = rows(&fills, HEADER_FILL);
+        let striped = rows(&fills, ROW_FILL);
+        assert_eq!(header.len(), 1);
+        assert_eq!(striped.len(), 2);
+        // Rows are equally tall, so the shaded rows are two rows apart, as the header is from
+        // the first of them.
+        let step = header[0] - striped[0];
+        assert!(step > 0.0);
+        assert!((striped[0] - striped[1] - step).abs() < 0.5, "rows at {:?} and {:?}", header, striped);
+
+        // No marker strokes are left behind.
+        let pdf = lopdf::Document::load(&output).unwrap();
+        let page = pdf.page_iter().next().unwrap();
+        let content = Content::decode(&pdf.get_page_content(page).unwrap()).unwrap();
+        assert!(content.operations.iter().all(|op| op.operator != "RG" || marker_fill(&op.operands, RgbColor::WHITE).is_none()));
+    }
+}
@@ -0,0 +1,59 @@
+//! Figures for the statistics page appended to a document, counted while it is rendered.
+
+/// What a document printed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DocumentStats {
+    pub words: usize,
+    /// Characters other than whitespace.
+    pub characters: usize,
+    /// Paragraphs, lines of code and table rows.
+    pub lines: usize,
+    pub tables: usize,
+    pub images: usize,
+}
+
+impl DocumentStats {
+    /// Whether anything has been printed yet.
+    pub fn has_content(&self) -> bool {
+        self.characters > 0 || self.tables > 0 || self.images > 0
+    }
+
+    /// Counts the words and characters of printed `text`.
+    pub fn add_text(&mut self, text: &str) {
+        let mut in_word = false;
+        for c in text.chars() {
+            if c.is_whitespace() {
+                in_word = false;
+                continue;
+            }
+            self.characters += 1;
+            // Like word processors, count every CJK character as a word of its own.
+            if is_cjk(c) {
+                self.words += 1;
+                in_word = false;
+            } else if !in_word {
+                self.words += 1;
+                in_word = true;
+            }
+        }
+    }
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF      // Hiragana and Katakana
+        | 0x3400..=0x4DBF    // CJK Extension A
+        | 0x4E00..=0x9FFF    // CJK Unified Ideographs
+        | 0xAC00..=0xD7AF    // Hangul syllables
+        | 0xF900..=0xFAFF    // CJK Compatibility Ideographs
+        | 0x20000..=0x2FFFF) // CJK Extensions B and later
+}
+
+/// `bytes` in B, KB or MB, for people.
+pub fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1_048_576 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
@@ -0,0 +1,70 @@
+//! The style sheet: size, color and weight of each kind of element in the PDF output, from the
+//! `[styles]` table of the configuration file.
+//!
+//! The sheet is installed once at startup.  Elements and keys it leaves out keep the built-in
+//! look, so an empty sheet changes nothing.
+
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::converter::RgbColor;
+
+static SHEET: OnceLock<StyleSheet> = OnceLock::new();
+
+/// Overrides per element type.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StyleSheet {
+    pub heading1: ElementStyle,
+    pub heading2: ElementStyle,
+    /// Level 3 and deeper headings.
+    pub heading3: ElementStyle,
+    /// Paragraphs of text, Markdown, reStructuredText, DOCX and subtitles.
+    pub body: ElementStyle,
+    /// Code blocks and the lines of JSON, XML, YAML and TOML files.
+    pub code: ElementStyle,
+    /// The header row of CSV, Excel and SQLite tables.
+    #[serde(alias = "table-header")]
+    pub table_header: ElementStyle,
+}
+
+impl StyleSheet {
+    pub fn heading(&self, level: usize) -> &ElementStyle {
+        match level {
+            1 => &self.heading1,
+            2 => &self.heading2,
+            _ => &self.heading3,
+        }
+    }
+}
+
+/// The parts of an element's look the user overrides.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ElementStyle {
+    /// Font size in points at the default 12 pt base size; it scales with the base size like
+    /// the built-in sizes do.
+    pub size: Option<u8>,
+    pub color: Option<RgbColor>,
+    pub weight: Option<Weight>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Weight {
+    Normal,
+    Bold,
+}
+
+/// Makes `sheet` the style sheet of every later conversion.  Only the first call has an effect.
+pub fn install(sheet: StyleSheet) {
+    if SHEET.set(sheet).is_err() {
+        log::warn!("Style sheet is already installed, ignoring the new one");
+    }
+}
+
+/// The installed style sheet, or an empty one.
+pub fn current() -> &'static StyleSheet {
+    SHEET.get_or_init(StyleSheet::default)
+}
@@ -0,0 +1,116 @@
+//! SubRip (`.srt`) and WebVTT (`.vtt`) subtitle cues.
+//!
+//! Cues are separated by blank lines: an optional identifier line, a `start --> end` timing line
+//! and the cue text.  Blocks that do not look like a cue are kept verbatim, so nothing in a
+//! damaged file is lost.
+
+/// One block of a subtitle file.
+#[derive(Debug, PartialEq)]
+pub enum Item {
+    Cue(Cue),
+    /// A block that could not be read as a cue, with its original lines.
+    Verbatim(Vec<String>),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Cue {
+    /// The SRT counter or VTT cue identifier, if the cue has one.
+    pub id: Option<String>,
+    /// Start and end as `hh:mm:ss.mmm`.
+    pub start: String,
+    pub end: String,
+    /// Text lines with formatting tags removed.
+    pub lines: Vec<String>,
+}
+
+/// Splits a subtitle file into cues.
+pub fn parse(content: &str) -> Vec<Item> {
+    let mut items = Vec::new();
+    let mut block: Vec<&str> = Vec::new();
+    let mut first = true;
+    for line in content.lines().chain(std::iter::once("")) {
+        if !line.trim().is_empty() {
+            block.push(line);
+            continue;
+        }
+        if block.is_empty() {
+            continue;
+        }
+        // The WebVTT header and comment, style and region blocks carry no cue text.
+        let header = first && block[0].trim_start_matches('\u{feff}').starts_with("WEBVTT");
+        let metadata = ["NOTE", "STYLE", "REGION"].iter()
+            .any(|keyword| block[0] == *keyword || block[0].starts_with(&format!("{} ", keyword)));
+        if !header && !metadata {
+            items.push(match cue(&block) {
+                Some(cue) => Item::Cue(cue),
+                None => {
+                    log::debug!("Keeping malformed subtitle block verbatim: {:?}", block[0]);
+                    Item::Verbatim(block.iter().map(|line| line.to_string()).collect())
+                }
+            });
+        }
+        block.clear();
+        first = false;
+    }
+    items
+}
+
+fn cue(block: &[&str]) -> Option<Cue> {
+    let (id, timing, text) = match block {
+        [timing, text @ ..] if timing.contains("-->") => (None, *timing, text),
+        [id, timing, text @ ..] if timing.contains("-->") => (Some(id.trim().to_string()), *timing, text),
+        _ => return None,
+    };
+    let (start, rest) = timing.split_once("-->")?;
+    // VTT cue settings (`align:start position:10%`) follow the end time.
+    let end = rest.split_whitespace().next()?;
+    Some(Cue {
+        id,
+        start: timestamp(start.trim())?,
+        end: timestamp(end)?,
+        lines: text.iter().map(|line| cue_text(line)).collect(),
+    })
+}
+
+/// Normalizes `hh:mm:ss,mmm` (SRT) or `[hh:]mm:ss.mmm` (VTT) to `hh:mm:ss.mmm`.
+fn timestamp(text: &str) -> Option<String> {
+    let (clock, millis) = text.split_once([',', '.'])?;
+    let parts: Vec<&str> = clock.split(':').collect();
+    let (hours, minutes, seconds) = match parts.as_slice() {
+        [h, m, s] => (*h, *m, *s),
+        [m, s] => ("0", *m, *s),
+        _ => return None,
+    };
+    let number = |s: &str| (!s.is_empty() && s.chars().all(|c| c.is_ascii_digit())).then(|| s.parse::<u32>().ok()).flatten();
+    let (hours, minutes, seconds, millis) = (number(hours)?, number(minutes)?, number(seconds)?, number(millis)?);
+    (minutes < 60 && seconds < 60 && millis < 1000).then(|| format!("{:02}:{:02}:{:02}.{:03}", hours, minutes, seconds, millis))
+}
+
+/// Removes `<i>`-style tags and `{\an8}` positioning codes; a VTT voice tag (`<v Ann>`)
+/// becomes a `Ann: ` prefix.
+fn cue_text(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        let close = match c {
+            '<' => '>',
+            '{' if rest.starts_with("{\\") => '}',
+            _ => {
+                out.push(c);
+                rest = &rest[c.len_utf8()..];
+                continue;
+            }
+        };
+        let Some(end) = rest.find(close) else {
+            out.push_str(rest);
+            break;
+        };
+        let tag = &rest[1..end];
+        if let Some(voice) = tag.strip_prefix("v ").or_else(|| tag.strip_prefix("v.").and_then(|t| t.split_once(' ').map(|(_, v)| v))) {
+            out.push_str(voice.trim());
+            out.push_str(": ");
+        }
+        rest = &rest[end + close.len_utf8()..];
+    }
+    out.replace("&lt;", "<").replace("&gt;", ">").replace("&nbsp;", "\u{a0}").replace("&amp;", "&")
+}
@@ -0,0 +1,181 @@
+//! JSON, YAML and TOML documents laid out as an indented tree.
+//!
+//! YAML and TOML values are converted to JSON values first, so all three share one layout.  Each
+//! entry gets a line of its own, with connector glyphs showing where it sits in the tree.  JSON
+//! can instead be laid out compactly, a line per top-level record.
+
+use serde_json::Value;
+
+/// What a tree line shows after its key.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Leaf {
+    /// A string, quoted and escaped as in JSON.
+    String(String),
+    Number(String),
+    Bool(bool),
+    Null,
+    /// An empty object or array, as `{}` or `[]`.
+    Empty(&'static str),
+    /// A non-empty object or array; its entries follow on the next lines.
+    Branch,
+}
+
+/// One entry of the tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeLine {
+    /// Connector glyphs for the entry's depth, such as `│  ├─ `.
+    pub prefix: String,
+    /// The object key, or `[n]` for an array item; `None` for a top-level scalar.
+    pub key: Option<String>,
+    pub leaf: Leaf,
+}
+
+/// The lines of `value`.  The entries of a top-level object or array start at the left edge.
+pub fn tree_lines(value: &Value) -> Vec<TreeLine> {
+    let mut lines = Vec::new();
+    if children(value).is_empty() {
+        lines.push(TreeLine { prefix: String::new(), key: None, leaf: leaf(value) });
+    } else {
+        push_children(value, "", &mut lines);
+    }
+    lines
+}
+
+fn push_children(value: &Value, indent: &str, lines: &mut Vec<TreeLine>) {
+    let entries = children(value);
+    let count = entries.len();
+    for (i, (key, child)) in entries.into_iter().enumerate() {
+        let last = i + 1 == count;
+        lines.push(TreeLine {
+            prefix: format!("{}{}", indent, if last { "└─ " } else { "├─ " }),
+            key: Some(key),
+            leaf: leaf(child),
+        });
+        let indent = format!("{}{}", indent, if last { "   " } else { "│  " });
+        push_children(child, &indent, lines);
+    }
+}
+
+/// The keys and values of an object, or the numbered items of an array.
+fn children(value: &Value) -> Vec<(String, &Value)> {
+    match value {
+        Value::Object(map) => map.iter().map(|(k, v)| (k.clone(), v)).collect(),
+        Value::Array(items) => items.iter().enumerate().map(|(i, v)| (format!("[{}]", i), v)).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn leaf(value: &Value) -> Leaf {
+    match value {
+        Value::String(_) => Leaf::String(value.to_string()),
+        Value::Number(n) => Leaf::Number(n.to_string()),
+        Value::Bool(b) => Leaf::Bool(*b),
+        Value::Null => Leaf::Null,
+        Value::Object(map) if map.is_empty() => Leaf::Empty("{}"),
+        Value::Array(items) if items.is_empty() => Leaf::Empty("[]"),
+        Value::Object(_) | Value::Array(_) => Leaf::Branch,
+    }
+}
+
+/// A line per item of a top-level array or entry of a top-level object, each written on one
+/// line; a scalar is a single line.
+pub fn compact_lines(value: &Value) -> Vec<String> {
+    match value {
+        Value::Array(items) if !items.is_empty() => items.iter().map(one_line).collect(),
+        Value::Object(map) if !map.is_empty() => map.iter().map(|(k, v)| format!("{}: {}", Value::from(k.as_str()), one_line(v))).collect(),
+        _ => vec![one_line(value)],
+    }
+}
+
+/// `value` as JSON on one line, with a space after each comma and colon so long lines can wrap.
+fn one_line(value: &Value) -> String {
+    match value {
+        Value::Array(items) => format!("[{}]", items.iter().map(one_line).collect::<Vec<_>>().join(", ")),
+        Value::Object(map) => {
+            let entries: Vec<String> = map.iter().map(|(k, v)| format!("{}: {}", Value::from(k.as_str()), one_line(v))).collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+        scalar => scalar.to_string(),
+    }
+}
+
+/// `value` as JSON; dates and times become strings.
+pub fn from_toml(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(n) => Value::from(n),
+        toml::Value::Float(f) => Value::from(f),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(items) => Value::Array(items.into_iter().map(from_toml).collect()),
+        toml::Value::Table(table) => Value::Object(table.into_iter().map(|(k, v)| (k, from_toml(v))).collect()),
+    }
+}
+
+/// `value` as JSON, or `None` if it has keys JSON cannot represent, such as sequences.
+pub fn from_yaml(value: &serde_yaml::Value) -> Option<Value> {
+    serde_json::to_value(value).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn line(prefix: &str, key: &str, leaf: Leaf) -> TreeLine {
+        TreeLine { prefix: prefix.to_string(), key: Some(key.to_string()), leaf }
+    }
+
+    #[test]
+    fn nested_maps_indent_under_their_key() {
+        let value = json!({ "a": { "b": { "c": 1 } }, "d": 2 });
+        assert_eq!(tree_lines(&value), [
+            line("├─ ", "a", Leaf::Branch),
+            line("│  └─ ", "b", Leaf::Branch),
+            line("│     └─ ", "c", Leaf::Number("1".to_string())),
+            line("└─ ", "d", Leaf::Number("2".to_string())),
+        ]);
+    }
+
+    #[test]
+    fn arrays_of_maps_number_their_items() {
+        let value = json!([{ "id": 1, "tags": [] }, { "id": 2, "tags": ["x"] }]);
+        assert_eq!(tree_lines(&value), [
+            line("├─ ", "[0]", Leaf::Branch),
+            line("│  ├─ ", "id", Leaf::Number("1".to_string())),
+            line("│  └─ ", "tags", Leaf::Empty("[]")),
+            line("└─ ", "[1]", Leaf::Branch),
+            line("   ├─ ", "id", Leaf::Number("2".to_string())),
+            line("   └─ ", "tags", Leaf::Branch),
+            line("      └─ ", "[0]", Leaf::String("\"x\"".to_string())),
+        ]);
+    }
+
+    #[test]
+    fn scalars_keep_their_type() {
+        let value = json!({ "bool": true, "empty": {}, "null": null, "number": 1.5, "string": "a \"b\"" });
+        let leaves: Vec<Leaf> = tree_lines(&value).into_iter().map(|line| line.leaf).collect();
+        assert_eq!(leaves, [
+            Leaf::Bool(true),
+            Leaf::Empty("{}"),
+            Leaf::Null,
+            Leaf::Number("1.5".to_string()),
+            Leaf::String("\"a \\\"b\\\"\"".to_string()),
+        ]);
+        assert_eq!(tree_lines(&json!(42)), [TreeLine { prefix: String::new(), key: None, leaf: Leaf::Number("42".to_string()) }]);
+    }
+
+    #[test]
+    fn toml_and_yaml_share_the_json_tree() {
+        let toml: toml::Value = toml::from_str("when = 1979-05-27\n[server]\nport = 8080\nsecure = false\n").unwrap();
+        let yaml: serde_yaml::Value = serde_yaml::from_str("server:\n  port: 8080\n  secure: false\nwhen: \"1979-05-27\"\n").unwrap();
+        let expected = [
+            line("├─ ", "server", Leaf::Branch),
+            line("│  ├─ ", "port", Leaf::Number("8080".to_string())),
+            line("│  └─ ", "secure", Leaf::Bool(false)),
+            line("└─ ", "when", Leaf::String("\"1979-05-27\"".to_string())),
+        ];
+        assert_eq!(tree_lines(&from_toml(toml)), expected);
+        assert_eq!(tree_lines(&from_yaml(&yaml).unwrap()), expected);
+    }
+}
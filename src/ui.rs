@@ -1,15 +1,240 @@
-use iced::{Element, Length, Task, Theme, Renderer};
-use iced::widget::{button, column, container, progress_bar, row, scrollable, text, Column};
-use std::path::PathBuf;
+// Widget styles are built by mutating a `Default` style, which reads better than struct update
+// syntax for the status-dependent overrides below.
+#![allow(clippy::field_reassign_with_default)]
+
+use iced::{Element, Length, Subscription, Task, Theme, Renderer};
+use iced::widget::{button, checkbox, column, container, mouse_area, pick_list, progress_bar, row, scrollable, slider, stack, svg, text, text_input, Column};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use genpdf::fonts::FontData;
-use crate::converter;
+use crate::converter::{self, Conformance, ConversionOptions, Density, ErrorPolicy, ImageCompression, ImagePlacement, JsonLayout, OutputFormat, PageSize, RgbColor, TextAlign, TextEncoding, TrackedChanges};
+use crate::config::{AccentColor, AppConfig, FontConfig, PerformanceMode, Preset, UiTheme};
+use crate::error::ConversionError;
+use crate::clipboard;
+use crate::merge;
+use crate::ranges;
+use crate::pipeline::{self, WorkerPool};
+use crate::history::{self, History};
+use crate::journal::{Journal, JournalEntry};
+use crate::report;
+use crate::scratch;
 use log::{info, warn};
 
+impl fmt::Display for TextAlign {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TextAlign::Left => "左对齐",
+            TextAlign::Center => "居中",
+            TextAlign::Right => "右对齐",
+            TextAlign::Justify => "两端对齐",
+        })
+    }
+}
+
+impl fmt::Display for PageSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PageSize::A4 => "A4",
+            PageSize::A5 => "A5",
+            PageSize::Letter => "Letter",
+            PageSize::Legal => "Legal",
+        })
+    }
+}
+
+impl fmt::Display for Density {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Density::Compact => "紧凑",
+            Density::Normal => "标准",
+            Density::Loose => "宽松",
+        })
+    }
+}
+
+impl fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TextEncoding::Auto => "自动",
+            TextEncoding::Utf8 => "UTF-8",
+            TextEncoding::Gbk => "GBK",
+            TextEncoding::Gb18030 => "GB18030",
+            TextEncoding::Big5 => "Big5",
+            TextEncoding::ShiftJis => "Shift-JIS",
+            TextEncoding::Latin1 => "Latin-1",
+        })
+    }
+}
+
+impl fmt::Display for TrackedChanges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TrackedChanges::Final => "最终版本（接受所有修订）",
+            TrackedChanges::Original => "原始版本（拒绝所有修订）",
+        })
+    }
+}
+
+impl fmt::Display for ImagePlacement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ImagePlacement::Inline => "随文排版（原始尺寸）",
+            ImagePlacement::Fit => "缩放至页面大小并居中",
+            ImagePlacement::FullPage => "每张图片单独一页",
+        })
+    }
+}
+
+impl fmt::Display for JsonLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            JsonLayout::Expanded => "展开（树形）",
+            JsonLayout::Compact => "紧凑（每条记录一行）",
+        })
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OutputFormat::Pdf => "PDF",
+            OutputFormat::Txt => "TXT 文本",
+            OutputFormat::Png => "PNG 图片",
+            OutputFormat::Markdown => "Markdown (.md)",
+        })
+    }
+}
+
+impl fmt::Display for Conformance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Conformance::Minimal => "标准 PDF",
+            Conformance::PdfA2b => "PDF/A-2b (归档)",
+        })
+    }
+}
+
+impl fmt::Display for PerformanceMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PerformanceMode::PowerSaving => "省电",
+            PerformanceMode::Balanced => "平衡",
+            PerformanceMode::Performance => "高性能",
+        })
+    }
+}
+
+impl fmt::Display for AccentColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AccentColor::Blue => "蓝色",
+            AccentColor::Teal => "青色",
+            AccentColor::Green => "绿色",
+            AccentColor::Purple => "紫色",
+            AccentColor::Orange => "橙色",
+            AccentColor::Pink => "粉色",
+        })
+    }
+}
+
+/// Where files added with a folder are written when an output folder is chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FolderLayout {
+    /// Straight into the output folder.
+    #[default]
+    Flatten,
+    /// Into the subfolder of the output folder that matches their place in the added folder.
+    Mirror,
+}
+
+impl FolderLayout {
+    const ALL: [FolderLayout; 2] = [FolderLayout::Flatten, FolderLayout::Mirror];
+}
+
+impl fmt::Display for FolderLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FolderLayout::Flatten => "展平",
+            FolderLayout::Mirror => "保持结构",
+        })
+    }
+}
+
+impl fmt::Display for UiTheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            UiTheme::Dark => "深色",
+            UiTheme::HighContrast => "高对比度",
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct FileEntry {
     pub path: PathBuf,
     pub status: ConversionStatus,
+    /// Convertible entries of a ZIP archive and whether each one is selected.
+    /// Empty for anything that is not an archive.
+    pub zip_entries: Vec<(String, bool)>,
+    /// Files produced by the last successful conversion.
+    pub outputs: Vec<PathBuf>,
+    /// Output format for this file only; `None` follows the global setting.
+    pub format_override: Option<OutputFormat>,
+    /// Ticked in the list, for converting only some of the files.
+    pub selected: bool,
+    /// The folder the file was found in when a whole folder was added.
+    pub root: Option<PathBuf>,
+    /// Hash of the file's content when it was added, `None` if it could not be read.
+    pub hash: Option<String>,
+    /// What the content looks like when that is not what the extension says.
+    pub content_type: Option<converter::FileType>,
+    /// Whether the content differs from its last conversion; `None` if it was never converted
+    /// or has been converted since it was added.
+    pub changed: Option<bool>,
+}
+
+impl FileEntry {
+    /// Reads what the file list shows about `path`, found in the added folder `root`.  For a ZIP
+    /// archive that means listing its entries, which takes a while for large archives, so this
+    /// runs off the UI thread.
+    fn scan(path: PathBuf, root: Option<PathBuf>) -> Self {
+        let mut entry = FileEntry {
+            path,
+            root,
+            status: ConversionStatus::Pending,
+            zip_entries: Vec::new(),
+            outputs: Vec::new(),
+            format_override: None,
+            selected: false,
+            hash: None,
+            changed: None,
+            content_type: None,
+        };
+        entry.content_type = sniff_mismatch(&entry.path);
+        entry.hash = history::content_hash(&entry.path)
+            .map_err(|e| warn!("Failed to hash {:?}: {}", entry.path, e))
+            .ok();
+        if entry.is_zip() {
+            match converter::list_zip_entries(&entry.path) {
+                Ok(names) => {
+                    info!("ZIP archive contains {} convertible entries", names.len());
+                    entry.zip_entries = names.into_iter().map(|n| (n, true)).collect();
+                }
+                Err(e) => {
+                    warn!("Failed to list ZIP archive {:?}: {}", entry.path, e);
+                    entry.status = ConversionStatus::Error(ConversionError::while_loading(e, converter::FileType::Zip));
+                }
+            }
+        }
+        entry
+    }
+
+    fn is_zip(&self) -> bool {
+        matches!(converter::FileType::from_path(&self.path), converter::FileType::Zip)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -17,73 +242,812 @@ pub enum ConversionStatus {
     Pending,
     Converting,
     Success,
-    Error(String),
+    /// Skipped because its output was written after the file last changed.
+    Unchanged,
+    Error(ConversionError),
+}
+
+/// Results of the last batch, shown in the summary dialog.
+struct RunSummary {
+    succeeded: usize,
+    /// File name and error of each failed file.
+    failures: Vec<(String, ConversionError)>,
+    /// Files left unconverted because the batch stopped early.
+    cancelled: usize,
+    elapsed: Duration,
+}
+
+/// The running batch and which of its conversions have not reported back yet.
+///
+/// Every scheduled file gets a task id of its own, and the batch is over once no id is
+/// outstanding.  Results with an id the batch does not know, such as late ones from a stopped
+/// batch, are ignored.
+struct BatchState {
+    /// Task ids of the conversions still running, with the index of their file.
+    outstanding: HashMap<u64, usize>,
+    total: usize,
+    completed: usize,
+    /// Files of this batch that converted, and the name and error of those that failed.
+    succeeded: usize,
+    failures: Vec<(String, ConversionError)>,
+    started: Instant,
+    /// Workers of this batch.  Files dropped while it runs join the same queue, and the workers
+    /// exit once the batch is over and the queue has drained.
+    pool: WorkerPool,
+    /// Abort the batch's tasks when the error policy says to stop.
+    handles: Vec<iced::task::Handle>,
 }
 
 pub struct App {
     files: Vec<FileEntry>,
     output_dir: Option<PathBuf>,
-    is_converting: bool,
-    font: Arc<FontData>,
-    total_files: usize,
-    completed_files: usize,
+    /// An existing PDF that converted PDFs are appended to instead of written as new files.
+    append_target: Option<PathBuf>,
+    /// `None` while no batch is running.
+    batch: Option<BatchState>,
+    /// The task id the next scheduled conversion gets.
+    next_task_id: u64,
+    /// Journal of the running batch, kept on disk until the batch ends.
+    journal: Option<Journal>,
+    /// An interrupted batch found at startup, until the user resumes or dismisses it.
+    resume_offer: Option<Journal>,
+    /// Content hashes of the files at their last conversion.
+    history: History,
+    /// Files picked together in the file dialog, until the user merges them or adds them to
+    /// the list.
+    merge_offer: Option<Vec<PathBuf>>,
+    /// Whether picked files are being merged into one PDF.
+    merging: bool,
+    /// `None` until the startup font loading task has finished.
+    fonts: Option<Arc<converter::FontSet>>,
+    /// The fonts configured in the configuration file, or picked after loading them failed.
+    font_config: FontConfig,
+    /// Why no font could be loaded; shown instead of the main window.
+    font_error: Option<String>,
     show_about: bool,
+    show_settings: bool,
+    show_summary: bool,
+    summary: Option<RunSummary>,
+    zip_picker: Option<usize>,
+    options: ConversionOptions,
+    presets: Vec<Preset>,
+    /// Custom redaction patterns from the configuration file.
+    redaction_patterns: Vec<String>,
+    write_report: bool,
+    /// Files dropped onto the window are converted straight away.
+    convert_on_drop: bool,
+    /// Convert every file even when its output is newer than it.
+    force_convert: bool,
+    folder_layout: FolderLayout,
+    /// Dropped files still being scanned that convert as soon as they join the list.
+    dropped: HashSet<PathBuf>,
+    /// How many files a batch converts at once; saved to the configuration file.
+    performance: PerformanceMode,
+    /// Saved to the configuration file.
+    accent: AccentColor,
+    theme: UiTheme,
+    status_message: Option<String>,
+    /// Only files whose name contains this text (ignoring case) are listed.
+    filter: String,
+    /// Added files still being scanned before they join the list.
+    scanning: usize,
+    /// Rows highlighted because the user added the same file again.
+    flashing: Vec<PathBuf>,
+    /// Index of the row being dragged to a new place in the list.
+    dragging: Option<usize>,
+    /// Short notice floating over the window, cleared together with `flashing`.
+    toast: Option<String>,
+    /// Counts flashes, so an older flash ending leaves a newer one alone.
+    flash_id: u64,
+    /// Advances while files are converting and drives the spinner of their rows.
+    spinner_frame: usize,
+}
+
+/// Colors of the window, derived from the accent color and theme.
+#[derive(Debug, Clone, Copy)]
+struct Palette {
+    accent: iced::Color,
+    accent_hover: iced::Color,
+    accent_pressed: iced::Color,
+    success: iced::Color,
+    success_hover: iced::Color,
+    success_pressed: iced::Color,
+    error: iced::Color,
+    text: iced::Color,
+    muted: iced::Color,
+    background: iced::Color,
+    panel: iced::Color,
+    card: iced::Color,
+    /// Text on accent and success backgrounds.
+    on_accent: iced::Color,
+    /// Points added to secondary text: hints, statuses and counts.
+    small_boost: u32,
+    theme: UiTheme,
+}
+
+impl Palette {
+    fn new(accent: AccentColor, theme: UiTheme) -> Self {
+        let [r, g, b] = accent.rgb();
+        let shade = |delta: f32| iced::Color::from_rgb((r + delta).clamp(0.0, 1.0), (g + delta).clamp(0.0, 1.0), (b + delta).clamp(0.0, 1.0));
+        // Mixed with white so dark text on it, and it as text on black, stay readable.
+        let tint = |amount: f32| iced::Color::from_rgb(r + (1.0 - r) * amount, g + (1.0 - g) * amount, b + (1.0 - b) * amount);
+        match theme {
+            UiTheme::Dark => Self {
+                accent: shade(0.0),
+                accent_hover: shade(0.1),
+                accent_pressed: shade(-0.1),
+                success: iced::Color::from_rgb(0.2, 0.8, 0.4),
+                success_hover: iced::Color::from_rgb(0.3, 0.9, 0.5),
+                success_pressed: iced::Color::from_rgb(0.1, 0.7, 0.3),
+                error: iced::Color::from_rgb(0.9, 0.3, 0.3),
+                text: iced::Color::from_rgb(0.9, 0.9, 0.9),
+                muted: iced::Color::from_rgb(0.6, 0.6, 0.6),
+                background: iced::Color::from_rgb(0.08, 0.08, 0.08),
+                panel: iced::Color::from_rgb(0.12, 0.12, 0.12),
+                card: iced::Color::from_rgb(0.18, 0.18, 0.18),
+                on_accent: iced::Color::WHITE,
+                small_boost: 0,
+                theme,
+            },
+            UiTheme::HighContrast => Self {
+                accent: tint(0.45),
+                accent_hover: tint(0.6),
+                accent_pressed: tint(0.3),
+                success: iced::Color::from_rgb(0.45, 1.0, 0.6),
+                success_hover: iced::Color::from_rgb(0.6, 1.0, 0.7),
+                success_pressed: iced::Color::from_rgb(0.35, 0.9, 0.5),
+                error: iced::Color::from_rgb(1.0, 0.55, 0.55),
+                text: iced::Color::WHITE,
+                muted: iced::Color::from_rgb(0.85, 0.85, 0.85),
+                background: iced::Color::BLACK,
+                panel: iced::Color::from_rgb(0.04, 0.04, 0.04),
+                card: iced::Color::from_rgb(0.1, 0.1, 0.1),
+                on_accent: iced::Color::BLACK,
+                small_boost: 2,
+                theme,
+            },
+        }
+    }
+
+    /// Size of secondary text designed at `size` points.
+    fn small(&self, size: u32) -> u32 {
+        size + self.small_boost
+    }
+
+    /// A mid-tone background for [`Palette::on_accent`] text, lightened for high contrast.
+    fn badge(&self, [r, g, b]: [f32; 3]) -> iced::Color {
+        let lift = match self.theme {
+            UiTheme::Dark => 0.0,
+            UiTheme::HighContrast => 0.4,
+        };
+        iced::Color::from_rgb(r + (1.0 - r) * lift, g + (1.0 - g) * lift, b + (1.0 - b) * lift)
+    }
+}
+
+/// Dots of the spinner shown on converting rows, and how often the lit one moves on.
+const SPINNER_DOTS: usize = 3;
+const SPINNER_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long re-added files stay highlighted.
+const FLASH_DURATION: Duration = Duration::from_millis(2500);
+
+/// Colors offered for headings and body text.
+const TEXT_COLORS: [RgbColor; 10] = [
+    RgbColor::BLACK,
+    RgbColor::WHITE,
+    RgbColor::new(0xdd, 0xdd, 0xdd),
+    RgbColor::new(0x44, 0x44, 0x44),
+    RgbColor::new(0x1f, 0x3a, 0x6e),
+    RgbColor::new(0x1e, 0x66, 0xc8),
+    RgbColor::new(0x1b, 0x6e, 0x3c),
+    RgbColor::new(0x9b, 0x1c, 0x1c),
+    RgbColor::new(0xc0, 0x5c, 0x00),
+    RgbColor::new(0x5b, 0x2c, 0x86),
+];
+
+/// Page backgrounds offered: white, paper tones and dark ones for reading at night.
+const BACKGROUND_COLORS: [RgbColor; 6] = [
+    RgbColor::WHITE,
+    RgbColor::new(0xfd, 0xf6, 0xe3),
+    RgbColor::new(0xf2, 0xf2, 0xf2),
+    RgbColor::new(0x1e, 0x1e, 0x1e),
+    RgbColor::new(0x0d, 0x1b, 0x2a),
+    RgbColor::BLACK,
+];
+
+/// Which part of the page a color applies to.
+#[derive(Debug, Clone, Copy)]
+pub enum ColorTarget {
+    Heading,
+    Body,
+    Background,
+}
+
+/// A side of the page, for editing margins.
+#[derive(Debug, Clone, Copy)]
+pub enum MarginSide {
+    Top,
+    Right,
+    Bottom,
+    Left,
+}
+
+/// Where the outputs of one listed file ended up after moving them to another folder.
+#[derive(Debug, Clone)]
+pub struct MovedOutputs {
+    source: PathBuf,
+    /// The outputs at their new place, or where they stayed if they could not be moved.
+    outputs: Vec<PathBuf>,
+    /// Outputs that could not be moved, with the reason.
+    failures: Vec<(PathBuf, String)>,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     AddFiles,
+    AddFolder,
+    /// Files picked in the file dialog, in the order they were selected.
+    FilesPicked(Vec<PathBuf>),
     FilesSelected(Vec<PathBuf>),
+    MergeSelection,
+    QueueSelection,
+    SelectionMerged(Option<(PathBuf, Result<(), ConversionError>)>),
+    FileScanned(FileEntry),
+    FileDropped(PathBuf),
+    /// The duplicate highlight with this id has been shown long enough.
+    FlashEnded(u64),
+    /// Copies the paths of the file's outputs to the clipboard.
+    CopyOutputPath(usize),
+    /// Copies the file's error, with what a bug report needs, to the clipboard.
+    CopyError(usize),
+    SpinnerTick,
     RemoveFile(usize),
+    /// The row's drag handle was pressed.
+    DragFile(usize),
+    /// The pointer entered the row, which the dragged row takes the place of.
+    DragOver(usize),
+    /// The mouse button was released, ending a drag.
+    DropFile,
+    FilterChanged(String),
+    FileFormatChanged(usize, OutputFormat),
+    RemoveCompleted,
+    ToggleSelect(usize),
+    /// Ticks or clears every file the filter shows.
+    SelectAll(bool),
+    ConvertSelected,
+    ConvertClipboard,
+    ClipboardConverted(Result<Option<PathBuf>, String>),
     SelectOutputDir,
     OutputDirSelected(PathBuf),
+    /// Asks for a folder to move the outputs of converted files to.
+    MoveOutputs,
+    MoveOutputsTo(PathBuf),
+    OutputsMoved(PathBuf, Vec<MovedOutputs>),
+    SelectAppendTarget,
+    AppendTargetSelected(Result<PathBuf, String>),
+    ClearAppendTarget,
     ConvertAll,
-    ConversionFinished(usize, Result<(), String>),
+    /// Converts every file again, including those already converted, so changed options apply.
+    ReconvertAll,
+    /// A conversion of the running batch ended, by task id, with the hash of the content it
+    /// converted.
+    ConversionFinished(u64, Result<Vec<PathBuf>, ConversionError>, Option<String>),
+    ToggleReport(bool),
+    ToggleConvertOnDrop(bool),
+    ToggleForceConvert(bool),
+    FolderLayoutChanged(FolderLayout),
+    ToggleStopOnError(bool),
     ToggleAbout,
+    ResumeBatch,
+    DismissResume,
+    ShowSummary,
+    CloseSummary,
+    OpenOutputFolder,
+    OpenLogsFolder,
+    ExportReport,
+    ToggleSettings,
+    FontsLoaded(Result<Arc<converter::FontSet>, String>),
+    /// Asks for a text font after no font could be loaded.
+    PickFont,
+    FontPicked(Option<PathBuf>),
+    ApplyPreset(String),
+    PageSizeChanged(PageSize),
+    FontSizeChanged(u8),
+    MarginChanged(MarginSide, String),
+    TogglePageNumbers(bool),
+    ToggleAutoLandscape(bool),
+    ToggleDetectLinks(bool),
+    ToggleSmartPunctuation(bool),
+    TogglePresentation(bool),
+    TextAlignChanged(TextAlign),
+    DensityChanged(Density),
+    JsonLayoutChanged(JsonLayout),
+    TrackedChangesChanged(TrackedChanges),
+    TextEncodingChanged(TextEncoding),
+    ConformanceChanged(Conformance),
+    PerformanceModeChanged(PerformanceMode),
+    AccentChanged(AccentColor),
+    ThemeChanged(UiTheme),
+    OutputFormatChanged(OutputFormat),
+    ColorChanged(ColorTarget, RgbColor),
+    SqliteRowLimitChanged(String),
+    ToggleExcelSheetFiles(bool),
+    ToggleSplitByHeading(bool),
+    ToggleLogLineNumbers(bool),
+    ToggleTableStripes(bool),
+    ToggleStatsPage(bool),
+    MaxPagesChanged(String),
+    LongLineCharsChanged(String),
+    TimeoutChanged(String),
+    PartRangeChanged(String),
+    ToggleRedactEmails(bool),
+    ToggleRedactPhones(bool),
+    ToggleRedactCustom(bool),
+    ToggleImageCompression(bool),
+    ImageQualityChanged(u8),
+    ToggleKeepLossless(bool),
+    ImageDpiChanged(u16),
+    FirstLineIndentChanged(String),
+    ParagraphSpacingChanged(f64),
+    ImagePlacementChanged(ImagePlacement),
+    ImageMaxMegapixelsChanged(String),
+    TabWidthChanged(u8),
+    OpenZipPicker(usize),
+    ToggleZipEntry(usize, bool),
+    CloseZipPicker,
     OpenLink(String),
     None,
 }
 
+/// Runs `job` on its own thread, keeping the UI responsive while it works.
+async fn run_blocking<T, F>(job: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce() -> anyhow::Result<T> + Send + 'static,
+{
+    let (tx, rx) = futures::channel::oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(job());
+    });
+    match rx.await {
+        Ok(res) => res.map_err(|e| e.to_string()),
+        Err(_) => Err("Task cancelled or panicked".to_string()),
+    }
+}
+
+/// How `file` is recorded in the batch journal.
+fn journal_entry(file: &FileEntry, done: bool) -> JournalEntry {
+    JournalEntry {
+        source: file.path.clone(),
+        zip_entries: file.zip_entries.clone(),
+        format_override: file.format_override,
+        root: file.root.clone(),
+        outputs: file.outputs.clone(),
+        done,
+    }
+}
+
+/// `paths` with each folder replaced by the convertible files in it and its subfolders, each paired
+/// with the folder it was found in.  Files picked directly have no folder.
+fn expand_folders(paths: Vec<PathBuf>) -> Vec<(PathBuf, Option<PathBuf>)> {
+    fn walk(dir: &Path, root: &Path, found: &mut Vec<(PathBuf, Option<PathBuf>)>) {
+        let mut entries: Vec<PathBuf> = match std::fs::read_dir(dir) {
+            Ok(entries) => entries.flatten().map(|e| e.path()).collect(),
+            Err(e) => {
+                warn!("Failed to list folder {:?}: {}", dir, e);
+                return;
+            }
+        };
+        entries.sort();
+        for path in entries {
+            if path.is_dir() {
+                walk(&path, root, found);
+            } else if !matches!(converter::FileType::from_path(&path), converter::FileType::Unknown | converter::FileType::Pdf) {
+                // PDFs in a folder are most likely earlier outputs, not inputs.
+                found.push((path, Some(root.to_path_buf())));
+            }
+        }
+    }
+
+    let mut found = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let before = found.len();
+            walk(&path, &path, &mut found);
+            info!("Folder {:?} has {} convertible files", path, found.len() - before);
+        } else {
+            found.push((path, None));
+        }
+    }
+    found
+}
+
+/// Moves the `outputs` of each listed file into `dir`.  An output shared by several files, such
+/// as a PDF they were appended to, is moved once.
+fn move_outputs(files: Vec<(PathBuf, Vec<PathBuf>)>, dir: &Path) -> Vec<MovedOutputs> {
+    let mut moved: HashMap<PathBuf, PathBuf> = HashMap::new();
+    files.into_iter().map(|(source, outputs)| {
+        let mut failures = Vec::new();
+        let outputs = outputs.into_iter().map(|output| {
+            if let Some(target) = moved.get(&output) {
+                return target.clone();
+            }
+            match move_file(&output, dir) {
+                Ok(target) => {
+                    moved.insert(output, target.clone());
+                    target
+                }
+                Err(e) => {
+                    warn!("Failed to move {:?} to {:?}: {}", output, dir, e);
+                    failures.push((output.clone(), e.to_string()));
+                    output
+                }
+            }
+        }).collect();
+        MovedOutputs { source, outputs, failures }
+    }).collect()
+}
+
+/// Moves `file` into `dir` and returns its new path.  Where renaming is not possible, such as
+/// across drives, the file is copied and the original deleted.  Existing files are not replaced.
+fn move_file(file: &Path, dir: &Path) -> std::io::Result<PathBuf> {
+    let name = file.file_name().ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "not a file"))?;
+    let target = dir.join(name);
+    if target == file {
+        return Ok(target);
+    }
+    if target.exists() {
+        return Err(std::io::Error::new(std::io::ErrorKind::AlreadyExists, "目标文件夹中已有同名文件"));
+    }
+    if std::fs::rename(file, &target).is_err() {
+        std::fs::copy(file, &target)?;
+        std::fs::remove_file(file)?;
+    }
+    Ok(target)
+}
+
+/// Whether `output` exists and was written after `source` last changed.
+fn is_up_to_date(source: &Path, output: &Path) -> bool {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified());
+    matches!((modified(source), modified(output)), (Ok(source), Ok(output)) if output >= source)
+}
+
+/// The failure of converting `path` as copied for a bug report: the message shown in the list,
+/// the untranslated detail, and the version of the program.
+fn error_report(path: &Path, error: &ConversionError) -> String {
+    format!(
+        "文件: {}\n错误: {}\n详情: {}\nTopdf {} ({})",
+        path.display(),
+        error_message(error),
+        error,
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+    )
+}
+
+/// A failure explained for the file list and the summary dialog.
+fn error_message(error: &ConversionError) -> String {
+    match error {
+        ConversionError::Io(detail) => format!("读写文件失败：{}", detail),
+        ConversionError::Parse(file_type, detail) => format!("无法解析 {:?} 文件，文件可能已损坏：{}", file_type, detail),
+        ConversionError::Unsupported(detail) => format!("不支持的文件或输出格式：{}", detail),
+        ConversionError::Encrypted(_) => "文件已加密，请先移除密码".to_string(),
+        ConversionError::EmptyInput(detail) => format!("没有可转换的内容：{}", detail),
+        ConversionError::InvalidOption(detail) => format!("设置有误：{}", detail),
+        ConversionError::RenderPanic { stage, reason } => {
+            let stage = match stage.as_str() {
+                "loading" => "读取",
+                "rendering" => "排版",
+                "exporting" => "导出",
+                "re-encoding" => "重新编码",
+                "post-processing" => "后期处理",
+                "appending" => "追加",
+                other => other,
+            };
+            format!("{}时程序出错：{}", stage, reason)
+        }
+        ConversionError::Render(detail) => format!("生成文档失败：{}", detail),
+        ConversionError::Entries { failed, total } => {
+            let entries: Vec<String> = failed.iter().map(|(name, e)| format!("{}：{}", name, error_message(e))).collect();
+            format!("{} 个条目中有 {} 个失败。{}", total, failed.len(), entries.join("；"))
+        }
+        ConversionError::Cancelled => "转换已取消".to_string(),
+        ConversionError::TimedOut(secs) => format!("超过 {} 秒仍未完成，已放弃", secs),
+    }
+}
+
+/// The heading a failure is grouped under in the summary dialog.
+fn error_cause(error: &ConversionError) -> &'static str {
+    match error {
+        ConversionError::Io(_) => "读写错误",
+        ConversionError::Parse(..) => "无法解析",
+        ConversionError::Unsupported(_) => "不支持",
+        ConversionError::Encrypted(_) => "已加密",
+        ConversionError::EmptyInput(_) => "没有内容",
+        ConversionError::InvalidOption(_) => "设置有误",
+        ConversionError::RenderPanic { .. } => "程序出错",
+        ConversionError::Render(_) => "生成失败",
+        ConversionError::Entries { .. } => "压缩包部分失败",
+        ConversionError::Cancelled => "已取消",
+        ConversionError::TimedOut(_) => "转换超时",
+    }
+}
+
+/// Three dots of which one, moving on with `frame`, is lit: shows that a conversion is
+/// still running.
+fn spinner<'a>(frame: usize, palette: Palette) -> Element<'a, Message> {
+    row((0..SPINNER_DOTS).map(|dot| {
+        let color = if dot == frame { palette.accent } else { palette.muted.scale_alpha(0.4) };
+        container(Column::new())
+            .width(6)
+            .height(6)
+            .style(move |_theme| container::Style {
+                background: Some(color.into()),
+                border: iced::Border { radius: 3.0.into(), ..iced::Border::default() },
+                ..container::Style::default()
+            })
+            .into()
+    }))
+    .spacing(3)
+    .into()
+}
+
+/// The type the content of `path` looks like, if that is not the type its extension gives.
+fn sniff_mismatch(path: &Path) -> Option<converter::FileType> {
+    let mut head = [0u8; 16];
+    let read = std::fs::File::open(path).and_then(|mut file| std::io::Read::read(&mut file, &mut head)).ok()?;
+    let sniffed = converter::FileType::sniff(&head[..read])?;
+    let extension = converter::FileType::from_path(path);
+    (!extension.fits_content(sniffed)).then(|| {
+        warn!("{:?} looks like {:?} although its extension says {:?}", path, sniffed, extension);
+        sniffed
+    })
+}
+
+/// What the file list calls a file type.
+fn type_label(file_type: converter::FileType) -> &'static str {
+    use converter::FileType;
+    match file_type {
+        FileType::Markdown => "Markdown",
+        FileType::Json => "JSON",
+        FileType::Xml => "XML",
+        FileType::Txt => "纯文本",
+        FileType::Code => "源代码",
+        FileType::Docx => "Word 文档",
+        FileType::Html => "HTML",
+        FileType::Csv => "CSV",
+        FileType::Tsv => "TSV",
+        FileType::Image => "图片",
+        FileType::Yaml => "YAML",
+        FileType::Toml => "TOML",
+        FileType::Excel => "Excel 工作簿",
+        FileType::Ods => "ODS 工作簿",
+        FileType::Eml => "邮件",
+        FileType::Rst => "reStructuredText",
+        FileType::Org => "Org",
+        FileType::Subtitle => "字幕",
+        FileType::Log => "日志",
+        FileType::Dot => "Graphviz",
+        FileType::Sqlite => "SQLite 数据库",
+        FileType::Zip => "ZIP 压缩包",
+        FileType::Pdf => "PDF",
+        FileType::Unknown => "未知类型",
+    }
+}
+
+/// The bundled icon at the left of a file row, telling documents, tables, images, code and
+/// archives apart; other files get a blank page.  Tinted per kind of file.
+fn file_icon<'a>(file_type: converter::FileType, palette: Palette) -> Element<'a, Message> {
+    use converter::FileType;
+    let (icon, color): (&'static [u8], _) = match file_type {
+        FileType::Markdown | FileType::Txt | FileType::Docx | FileType::Html | FileType::Rst | FileType::Org | FileType::Subtitle | FileType::Eml | FileType::Pdf => {
+            (include_bytes!("../assets/icons/document.svg"), [0.25, 0.5, 0.85])
+        }
+        FileType::Csv | FileType::Tsv | FileType::Excel | FileType::Ods | FileType::Sqlite => {
+            (include_bytes!("../assets/icons/table.svg"), [0.2, 0.6, 0.35])
+        }
+        FileType::Image | FileType::Dot => (include_bytes!("../assets/icons/image.svg"), [0.85, 0.5, 0.2]),
+        FileType::Code | FileType::Json | FileType::Xml | FileType::Yaml | FileType::Toml | FileType::Log => {
+            (include_bytes!("../assets/icons/code.svg"), [0.55, 0.4, 0.8])
+        }
+        FileType::Zip => (include_bytes!("../assets/icons/archive.svg"), [0.7, 0.6, 0.2]),
+        FileType::Unknown => (include_bytes!("../assets/icons/file.svg"), [0.4, 0.4, 0.4]),
+    };
+    let tint = palette.badge(color);
+    container(
+        svg(svg::Handle::from_memory(icon))
+            .width(24)
+            .height(24)
+            .style(move |_theme, _status| svg::Style { color: Some(tint) }),
+    )
+    .center_x(36)
+    .center_y(36)
+    .into()
+}
+
+/// Loads a font file, logging why it was rejected if it can't be used.
+fn load_font_file(path: &Path) -> Option<Arc<FontData>> {
+    let bytes = std::fs::read(path).ok()?;
+    match converter::prepare_font(Arc::new(bytes)) {
+        Ok(font) => Some(font),
+        Err(e) => {
+            warn!("Failed to parse font {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
+/// System fonts tried for the output text and the interface, in order.
+// Only use known-good .ttf files or fallback. Avoid .ttc for now as they may cause hangs in genpdf.
+const SYSTEM_FONTS: [&str; 5] = [
+    "/usr/share/fonts/truetype/droid/DroidSansFallbackFull.ttf",
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "C:\\Windows\\Fonts\\msyh.ttc", // Microsoft YaHei
+    "C:\\Windows\\Fonts\\simhei.ttf", // SimHei
+    "C:\\Windows\\Fonts\\arial.ttf",
+];
+
+/// Picks the interface font: the configured one, then the first system font that covers Chinese,
+/// then the bundled one, which only has the characters of the interface's own strings.
+/// Returns the font file and its family name, or `None` to keep iced's default font.
+pub fn load_ui_font(config: &FontConfig) -> Option<(Vec<u8>, String)> {
+    let configured = config.ui.as_deref().into_iter();
+    let found = configured.chain(SYSTEM_FONTS.iter().map(Path::new)).find_map(|path| {
+        let bytes = std::fs::read(path).ok()?;
+        match cjk_family_name(&bytes) {
+            Some(family) => {
+                info!("Using {:?} ({}) as the interface font", path, family);
+                Some((bytes, family))
+            }
+            None => {
+                if config.ui.as_deref() == Some(path) {
+                    warn!("Configured interface font {:?} cannot be parsed or lacks Chinese characters", path);
+                }
+                None
+            }
+        }
+    });
+    found.or_else(|| {
+        let bytes = include_bytes!("../assets/LXGWWenKai-UI.ttf").to_vec();
+        let family = cjk_family_name(&bytes)?;
+        info!("Using the bundled {} as the interface font", family);
+        Some((bytes, family))
+    })
+}
+
+/// The family name of the first font in `bytes`, if it has Chinese glyphs.  English names are
+/// preferred, since that is what the font database matches by.
+fn cjk_family_name(bytes: &[u8]) -> Option<String> {
+    use ttf_parser::{name_id, Language};
+
+    let face = ttf_parser::Face::parse(bytes, 0).ok()?;
+    face.glyph_index('中')?;
+    let mut names: Vec<_> = face.names().into_iter()
+        .filter(|name| matches!(name.name_id, name_id::TYPOGRAPHIC_FAMILY | name_id::FAMILY) && name.is_unicode())
+        .collect();
+    names.sort_by_key(|name| (name.name_id != name_id::TYPOGRAPHIC_FAMILY, name.language() != Language::English_UnitedStates));
+    names.into_iter().find_map(|name| name.to_string())
+}
+
+/// Picks the output fonts: configured overrides first, then system fonts, then the bundled ones.
+///
+/// Fails only if no font at all can be used for text.  A bundled monospace or fallback font that
+/// cannot be parsed is replaced by the text font, or left out.
+pub fn load_fonts(config: &FontConfig) -> anyhow::Result<converter::FontSet> {
+
+    let configured_text = config.text.as_deref().and_then(|path| {
+        let font = load_font_file(path);
+        if font.is_some() {
+            info!("Loaded configured text font: {:?}", path);
+        }
+        font
+    });
+    let text = configured_text.or_else(|| {
+        SYSTEM_FONTS.iter().find_map(|path| {
+            let font = load_font_file(Path::new(path))?;
+            info!("Successfully loaded system font: {}", path);
+            Some(font)
+        })
+    });
+    let embedded_text = || {
+        let bytes = include_bytes!("../assets/DejaVuSans.ttf").to_vec();
+        converter::prepare_font(Arc::new(bytes))
+    };
+
+    // The bundled DejaVu Sans covers Latin, Greek, Cyrillic, Arabic, Hebrew and many symbols,
+    // which complements CJK system fonts well.
+    let fallback = config.fallback.as_deref()
+        .and_then(|path| {
+            let font = load_font_file(path);
+            if font.is_some() {
+                info!("Loaded configured fallback font: {:?}", path);
+            }
+            font
+        })
+        .or_else(|| {
+            text.as_ref()?;
+            embedded_text()
+                .inspect_err(|e| warn!("Embedded fallback font cannot be used, going without: {:#}", e))
+                .ok()
+        });
+
+    let text = match text {
+        Some(text) => text,
+        None => {
+            warn!("Loading embedded fallback font (DejaVu Sans).");
+            embedded_text().map_err(|e| {
+                let configured = config.text.as_ref().map(|p| format!("the configured font {}, ", p.display())).unwrap_or_default();
+                anyhow::anyhow!("{}the system fonts and the embedded DejaVu Sans all failed to load: {:#}", configured, e)
+            })?
+        }
+    };
+
+    let mono = config.mono.as_deref()
+        .and_then(|path| {
+            let font = load_font_file(path);
+            if font.is_some() {
+                info!("Loaded configured monospace font: {:?}", path);
+            }
+            font
+        })
+        .unwrap_or_else(|| {
+            let bytes = include_bytes!("../assets/DejaVuSansMono.ttf").to_vec();
+            converter::prepare_font(Arc::new(bytes)).unwrap_or_else(|e| {
+                warn!("Embedded monospace font cannot be used, using the text font for code: {:#}", e);
+                text.clone()
+            })
+        });
+
+    Ok(converter::FontSet { text, mono, fallback })
+}
+
 impl App {
     pub fn new() -> (Self, Task<Message>) {
-        // Only use known-good .ttf files or fallback. Avoid .ttc for now as they may cause hangs in genpdf.
-        let system_fonts = [
-            "/usr/share/fonts/truetype/droid/DroidSansFallbackFull.ttf",
-            "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
-            "C:\\Windows\\Fonts\\msyh.ttc", // Microsoft YaHei
-            "C:\\Windows\\Fonts\\simhei.ttf", // SimHei
-            "C:\\Windows\\Fonts\\arial.ttf",
-        ];
-
-        let mut selected_font = None;
-        for path in system_fonts {
-            if let Ok(bytes) = std::fs::read(path) {
-                let bytes_arc = Arc::new(bytes);
-                if let Ok(font) = converter::prepare_font(bytes_arc) {
-                    info!("Successfully loaded system font: {}", path);
-                    selected_font = Some(font);
-                    break;
-                }
-            }
-        }
-
-        let font = selected_font.unwrap_or_else(|| {
-             warn!("Loading embedded fallback font (Roboto).");
-             let bytes = include_bytes!("../assets/Roboto-Regular.ttf").to_vec();
-             converter::prepare_font(Arc::new(bytes)).expect("Failed to load embedded font")
-        });
+        let config = AppConfig::load();
+        crate::styles::install(config.styles.clone());
+        let load_fonts = Self::load_fonts_task(config.fonts.clone());
 
         (
             Self {
                 files: Vec::new(),
                 output_dir: None,
-                is_converting: false,
-                font,
-                total_files: 0,
-                completed_files: 0,
+                append_target: None,
+                batch: None,
+                next_task_id: 0,
+                journal: None,
+                resume_offer: Journal::load(),
+                history: History::load(),
+                merge_offer: None,
+                merging: false,
+                fonts: None,
+                font_config: config.fonts.clone(),
+                font_error: None,
                 show_about: false,
+                show_settings: false,
+                show_summary: false,
+                summary: None,
+                zip_picker: None,
+                options: ConversionOptions::default(),
+                presets: config.presets(),
+                redaction_patterns: config.redaction.patterns.clone(),
+                write_report: false,
+                convert_on_drop: false,
+                force_convert: false,
+                folder_layout: FolderLayout::default(),
+                dropped: HashSet::new(),
+                performance: config.performance,
+                accent: config.accent,
+                theme: config.theme,
+                status_message: None,
+                filter: String::new(),
+                scanning: 0,
+                flashing: Vec::new(),
+                dragging: None,
+                toast: None,
+                flash_id: 0,
+                spinner_frame: 0,
             },
-            Task::none(),
+            load_fonts,
         )
     }
 
@@ -92,7 +1056,7 @@ impl App {
             Message::AddFiles => {
                 return Task::perform(async {
                     let files = rfd::AsyncFileDialog::new()
-                        .add_filter("Documents", &["md", "json", "xml", "txt", "docx", "html", "htm", "csv", "png", "jpg", "jpeg", "bmp", "rs", "py", "js", "c", "cpp", "yaml", "yml", "toml", "xlsx", "xls"])
+                        .add_filter("Documents", &["md", "json", "xml", "txt", "docx", "html", "htm", "csv", "tsv", "tab", "png", "jpg", "jpeg", "bmp", "rs", "py", "js", "c", "cpp", "yaml", "yml", "toml", "xlsx", "xls", "ods", "eml", "rst", "org", "srt", "vtt", "dot", "gv", "sqlite", "sqlite3", "db", "zip", "pdf", "log"])
                         .pick_files()
                         .await;
                     
@@ -101,21 +1065,168 @@ impl App {
                     } else {
                         Vec::new()
                     }
+                }, Message::FilesPicked);
+            }
+            Message::FilesPicked(paths) => {
+                // Several files picked at once can also be merged into one PDF right away.
+                if paths.len() > 1 {
+                    self.merge_offer = Some(paths);
+                    return Task::none();
+                }
+                return self.update(Message::FilesSelected(paths));
+            }
+            Message::QueueSelection => {
+                if let Some(paths) = self.merge_offer.take() {
+                    return self.update(Message::FilesSelected(paths));
+                }
+            }
+            Message::MergeSelection => {
+                let (Some(fonts), Some(inputs)) = (self.fonts.clone(), self.merge_offer.take()) else {
+                    return Task::none();
+                };
+                let options = self.options.clone();
+                let output_dir = self.output_dir.clone().or_else(|| inputs[0].parent().map(Path::to_path_buf));
+                self.merging = true;
+                self.status_message = None;
+                return Task::perform(async move {
+                    let mut dialog = rfd::AsyncFileDialog::new()
+                        .set_title("合并后的 PDF 文件名")
+                        .set_file_name("合并.pdf")
+                        .add_filter("PDF", &["pdf"]);
+                    if let Some(dir) = &output_dir {
+                        dialog = dialog.set_directory(dir);
+                    }
+                    let output = dialog.save_file().await?.path().with_extension("pdf");
+                    let target = output.clone();
+                    let result = run_blocking(move || Ok(converter::convert_merged(&inputs, &target, &fonts, &options)))
+                        .await
+                        .unwrap_or_else(|e| Err(ConversionError::Render(e)));
+                    Some((output, result))
+                }, Message::SelectionMerged);
+            }
+            Message::SelectionMerged(result) => {
+                self.merging = false;
+                match result {
+                    Some((path, Ok(()))) => {
+                        info!("Merged picked files into {:?}", path);
+                        self.status_message = Some(format!("已合并为: {}", path.display()));
+                    }
+                    Some((path, Err(ConversionError::Entries { failed, total }))) if failed.len() < total => {
+                        warn!("Merged {:?} without {} of {} files", path, failed.len(), total);
+                        let failures: Vec<String> = failed.iter().map(|(name, e)| format!("{}：{}", name, error_message(e))).collect();
+                        self.status_message = Some(format!(
+                            "已合并为: {}，{} 个文件未能合并：{}", path.display(), failed.len(), failures.join("；")
+                        ));
+                    }
+                    Some((_, Err(e))) => {
+                        log::error!("Merging picked files failed: {}", e);
+                        self.status_message = Some(format!("合并失败: {}", error_message(&e)));
+                    }
+                    None => info!("Merging picked files cancelled"),
+                }
+            }
+            Message::AddFolder => {
+                return Task::perform(async {
+                    let folders = rfd::AsyncFileDialog::new().pick_folders().await;
+                    folders.unwrap_or_default().into_iter().map(|f| f.path().to_path_buf()).collect()
                 }, Message::FilesSelected);
             }
             Message::FilesSelected(paths) => {
                 info!("Selected {} files", paths.len());
-                for path in paths {
-                    if !self.files.iter().any(|f| f.path == path) {
-                        info!("Adding file: {:?}", path);
-                        self.files.push(FileEntry {
-                            path,
-                            status: ConversionStatus::Pending,
-                        });
-                    } else {
-                        info!("Skipping duplicate file: {:?}", path);
+                let (duplicates, paths): (Vec<_>, Vec<_>) = expand_folders(paths).into_iter()
+                    .partition(|(path, _)| self.files.iter().any(|f| &f.path == path));
+                let duplicates: Vec<PathBuf> = duplicates.into_iter().map(|(path, _)| path).collect();
+                for path in &duplicates {
+                    info!("Skipping duplicate file: {:?}", path);
+                }
+                let flash = if duplicates.is_empty() { Task::none() } else { self.flash_duplicates(duplicates) };
+                if paths.is_empty() {
+                    return flash;
+                }
+                // Entries arrive one by one, so the list fills while large archives are read.
+                self.scanning += paths.len();
+                let (tx, rx) = futures::channel::mpsc::unbounded();
+                std::thread::spawn(move || {
+                    for (path, root) in paths {
+                        if tx.unbounded_send(FileEntry::scan(path, root)).is_err() {
+                            break;
+                        }
+                    }
+                });
+                return Task::batch([flash, Task::run(rx, Message::FileScanned)]);
+            }
+            Message::FlashEnded(id) => {
+                if id == self.flash_id {
+                    self.flashing.clear();
+                    self.toast = None;
+                }
+            }
+            Message::CopyOutputPath(index) => {
+                let Some(file) = self.files.get(index).filter(|f| matches!(f.status, ConversionStatus::Success)) else {
+                    return Task::none();
+                };
+                let paths: Vec<String> = file.outputs.iter().map(|p| p.display().to_string()).collect();
+                match clipboard::copy_text(&paths.join("\n")) {
+                    Ok(()) => {
+                        info!("Copied output paths of {:?} to the clipboard", file.path);
+                        return self.show_toast(if paths.len() == 1 { "已复制路径".to_string() } else { format!("已复制 {} 个路径", paths.len()) });
+                    }
+                    Err(e) => {
+                        warn!("Failed to copy output paths: {:#}", e);
+                        self.status_message = Some(format!("无法复制路径: {}", e));
+                    }
+                }
+            }
+            Message::CopyError(index) => {
+                let Some((file, ConversionStatus::Error(e))) = self.files.get(index).map(|f| (f, &f.status)) else {
+                    return Task::none();
+                };
+                match clipboard::copy_text(&error_report(&file.path, e)) {
+                    Ok(()) => {
+                        info!("Copied the error of {:?} to the clipboard", file.path);
+                        return self.show_toast("已复制错误信息".to_string());
                     }
+                    Err(e) => {
+                        warn!("Failed to copy error: {:#}", e);
+                        self.status_message = Some(format!("无法复制错误信息: {}", e));
+                    }
+                }
+            }
+            Message::SpinnerTick => {
+                self.spinner_frame = (self.spinner_frame + 1) % SPINNER_DOTS;
+            }
+            Message::FileScanned(mut entry) => {
+                self.scanning = self.scanning.saturating_sub(1);
+                let dropped = self.dropped.remove(&entry.path);
+                // The same file may have been picked twice before its first scan finished.
+                if !self.files.iter().any(|f| f.path == entry.path) {
+                    info!("Adding file: {:?}", entry.path);
+                    entry.changed = entry.hash.as_deref()
+                        .and_then(|hash| self.history.last_hash(&entry.path).map(|last| last != hash));
+                    self.files.push(entry);
+                    if dropped {
+                        return self.convert_dropped(self.files.len() - 1);
+                    }
+                }
+            }
+            Message::FileDropped(path) => {
+                if self.convert_on_drop {
+                    // Dropping a listed file again converts it again.
+                    if let Some(index) = self.files.iter().position(|f| f.path == path) {
+                        return self.convert_dropped(index);
+                    }
+                    self.dropped.insert(path.clone());
                 }
+                return self.update(Message::FilesSelected(vec![path]));
+            }
+            Message::FileFormatChanged(index, format) => {
+                if let Some(file) = self.files.get_mut(index) {
+                    info!("Output format for {:?} set to: {:?}", file.path, format);
+                    file.format_override = (format != self.options.output_format).then_some(format);
+                }
+            }
+            Message::FilterChanged(filter) => {
+                self.filter = filter;
             }
             Message::RemoveFile(index) => {
                 if index < self.files.len() {
@@ -125,6 +1236,125 @@ impl App {
                     self.files.remove(index);
                 }
             }
+            Message::DragFile(index) => {
+                if !self.is_converting() && index < self.files.len() {
+                    self.dragging = Some(index);
+                }
+            }
+            Message::DragOver(index) => {
+                // The list follows the pointer, so it shows the new order while dragging.
+                if let Some(from) = self.dragging
+                    && from != index
+                    && index < self.files.len()
+                    && !self.is_converting()
+                {
+                    let file = self.files.remove(from);
+                    self.files.insert(index, file);
+                    self.dragging = Some(index);
+                }
+            }
+            Message::DropFile => {
+                if let Some(index) = self.dragging.take() {
+                    info!("Moved {:?} to position {}", self.files[index].path, index + 1);
+                }
+            }
+            Message::ToggleSelect(index) => {
+                if let Some(file) = self.files.get_mut(index) {
+                    file.selected = !file.selected;
+                }
+            }
+            Message::SelectAll(selected) => {
+                let visible: Vec<usize> = self.visible_files().map(|(i, _)| i).collect();
+                for i in visible {
+                    self.files[i].selected = selected;
+                }
+            }
+            Message::RemoveCompleted => {
+                if !self.is_converting() {
+                    let before = self.files.len();
+                    self.files.retain(|f| !matches!(f.status, ConversionStatus::Success | ConversionStatus::Unchanged));
+                    info!("Removed {} completed files from the list", before - self.files.len());
+                }
+            }
+            Message::FontsLoaded(result) => match result {
+                Ok(fonts) => {
+                    info!("Fonts loaded");
+                    self.fonts = Some(fonts);
+                }
+                Err(e) => {
+                    log::error!("Failed to load fonts: {}", e);
+                    self.font_error = Some(e);
+                }
+            },
+            Message::PickFont => {
+                return Task::perform(async {
+                    rfd::AsyncFileDialog::new()
+                        .set_title("选择字体文件")
+                        .add_filter("TrueType / OpenType", &["ttf", "otf"])
+                        .pick_file()
+                        .await
+                        .map(|f| f.path().to_path_buf())
+                }, Message::FontPicked);
+            }
+            Message::FontPicked(path) => {
+                let Some(path) = path else {
+                    return Task::none();
+                };
+                info!("Retrying font loading with {:?}", path);
+                self.font_config.text = Some(path);
+                self.font_error = None;
+                return Self::load_fonts_task(self.font_config.clone());
+            }
+            Message::ConvertClipboard => {
+                let Some(fonts) = self.fonts.clone() else {
+                    return Task::none();
+                };
+                let options = self.options.clone();
+                let output_dir = self.output_dir.clone();
+                self.status_message = None;
+                return Task::perform(async move {
+                    let scratch = run_blocking(|| scratch::dir("clipboard")).await?;
+                    let dir = scratch.to_path_buf();
+                    let source = run_blocking(move || clipboard::save_clipboard(&dir)).await?;
+
+                    let extension = options.output_format.extension();
+                    let mut dialog = rfd::AsyncFileDialog::new()
+                        .set_title("保存剪贴板内容")
+                        .set_file_name(format!("剪贴板.{}", extension))
+                        .add_filter(options.output_format.to_string(), &[extension]);
+                    if let Some(dir) = &output_dir {
+                        dialog = dialog.set_directory(dir);
+                    }
+                    let output = dialog.save_file().await.map(|handle| handle.path().with_extension(extension));
+
+                    let result = match output {
+                        Some(output) => {
+                            let (input, target) = (source.clone(), output.clone());
+                            run_blocking(move || {
+                                converter::convert(&input, &target, &fonts, &options).map_err(|e| anyhow::anyhow!(error_message(&e)))
+                            })
+                            .await
+                                .map(|_| Some(output))
+                        }
+                        None => Ok(None),
+                    };
+                    // The clipboard file goes with `scratch`.
+                    result
+                }, Message::ClipboardConverted);
+            }
+            Message::ClipboardConverted(result) => {
+                match result {
+                    Ok(Some(path)) => {
+                        info!("Clipboard converted to {:?}", path);
+                        self.status_message = Some(format!("剪贴板内容已保存为: {}", path.display()));
+                    }
+                    Ok(None) => info!("Clipboard conversion cancelled"),
+                    Err(e) => {
+                        log::error!("Clipboard conversion failed: {}", e);
+                        self.status_message = Some(format!("剪贴板转换失败: {}", e));
+                    }
+                }
+            }
             Message::SelectOutputDir => {
                 return Task::perform(async {
                     let dir = rfd::AsyncFileDialog::new()
@@ -138,90 +1368,435 @@ impl App {
                 info!("Output directory set to: {:?}", path);
                 self.output_dir = Some(path);
             }
-            Message::ConvertAll => {
-                if self.files.is_empty() || self.is_converting {
+            Message::MoveOutputs => {
+                return Task::perform(async {
+                    rfd::AsyncFileDialog::new()
+                        .set_title("将已生成的文件移动到")
+                        .pick_folder()
+                        .await
+                        .map(|d| d.path().to_path_buf())
+                }, |d| d.map_or(Message::None, Message::MoveOutputsTo));
+            }
+            Message::MoveOutputsTo(dir) => {
+                if self.is_converting() {
                     return Task::none();
                 }
-
-                info!("Starting batch conversion...");
-                self.is_converting = true;
-                self.completed_files = 0;
-                self.total_files = 0;
-
-                let mut tasks = Vec::new();
-                
-                let output_base = self.output_dir.clone();
-                let font_arc = self.font.clone();
-
-                // Count files to convert
-                let files_to_convert: Vec<usize> = self.files.iter().enumerate()
-                    .filter(|(_, f)| !matches!(f.status, ConversionStatus::Success))
-                    .map(|(i, _)| i)
+                let files: Vec<(PathBuf, Vec<PathBuf>)> = self.files.iter()
+                    .filter(|f| matches!(f.status, ConversionStatus::Success | ConversionStatus::Unchanged) && !f.outputs.is_empty())
+                    .map(|f| (f.path.clone(), f.outputs.clone()))
                     .collect();
-                
-                self.total_files = files_to_convert.len();
-                info!("Files scheduled for conversion: {}", self.total_files);
-
-                if self.total_files == 0 {
-                    self.is_converting = false;
-                    info!("No pending files to convert.");
-                    return Task::none();
-                }
-
-                for i in files_to_convert {
-                    if let Some(file) = self.files.get_mut(i) {
-                         file.status = ConversionStatus::Converting;
-                         
-                         let input_path = file.path.clone();
-                         let output_dir = output_base.clone().unwrap_or_else(|| input_path.parent().unwrap().to_path_buf());
-                         let file_stem = input_path.file_stem().unwrap().to_string_lossy().to_string();
-                         let output_path = output_dir.join(format!("{}.pdf", file_stem));
-                         let font_for_task = font_arc.clone();
-
-                         tasks.push(Task::perform(async move {
-                            let (tx, rx) = futures::channel::oneshot::channel();
-                            
-                            std::thread::spawn(move || {
-                                 let res = converter::convert(&input_path, &output_path, font_for_task);
-                                 let _ = tx.send(res);
-                            });
-                            
-                            match rx.await {
-                                Ok(res) => match res {
-                                    Ok(_) => Ok(()),
-                                    Err(e) => Err(e.to_string()),
-                                },
-                                Err(_) => Err("Task cancelled or panicked".to_string()),
+                info!("Moving the outputs of {} files to {:?}", files.len(), dir);
+                let target = dir.clone();
+                return Task::perform(
+                    run_blocking(move || Ok(move_outputs(files, &target))),
+                    move |result| Message::OutputsMoved(dir.clone(), result.unwrap_or_default()),
+                );
+            }
+            Message::OutputsMoved(dir, moved) => {
+                let mut count = 0;
+                let mut failures = Vec::new();
+                for result in moved {
+                    if let Some(file) = self.files.iter_mut().find(|f| f.path == result.source) {
+                        for (old, new) in file.outputs.iter().zip(&result.outputs).filter(|(old, new)| old != new) {
+                            count += 1;
+                            // The PDF being appended to has moved along.
+                            if self.append_target.as_ref() == Some(old) {
+                                self.append_target = Some(new.clone());
                             }
-                        }, move |res| Message::ConversionFinished(i, res)));
+                        }
+                        file.outputs = result.outputs;
+                    }
+                    for (output, error) in result.failures {
+                        let name = output.file_name().unwrap_or_default().to_string_lossy().to_string();
+                        failures.push(format!("{}：{}", name, error));
                     }
                 }
-                
-                return Task::batch(tasks);
+                // Later conversions go to the same place.
+                self.output_dir = Some(dir);
+                self.status_message = Some(if failures.is_empty() {
+                    format!("已移动 {} 个输出文件", count)
+                } else {
+                    format!("已移动 {} 个输出文件，{} 个移动失败：{}", count, failures.len(), failures.join("；"))
+                });
+            }
+            Message::SelectAppendTarget => {
+                return Task::perform(async {
+                    let file = rfd::AsyncFileDialog::new()
+                        .set_title("选择要追加到的 PDF")
+                        .add_filter("PDF", &["pdf"])
+                        .pick_file()
+                        .await?;
+                    let path = file.path().to_path_buf();
+                    let checked = path.clone();
+                    Some(run_blocking(move || merge::open_target(&checked).map(|_| ())).await.map(|()| path))
+                }, |result| result.map_or(Message::None, Message::AppendTargetSelected));
+            }
+            Message::AppendTargetSelected(result) => match result {
+                Ok(path) => {
+                    info!("Appending converted PDFs to {:?}", path);
+                    self.append_target = Some(path);
+                    self.status_message = None;
+                }
+                Err(e) => {
+                    warn!("Rejected append target: {}", e);
+                    self.status_message = Some(format!("无法追加到该文件: {}", e));
+                }
+            },
+            Message::ClearAppendTarget => {
+                self.append_target = None;
             }
-            Message::ConversionFinished(index, result) => {
-                self.completed_files += 1;
+            Message::ConvertAll => return self.start_batch(false, !self.force_convert),
+            Message::ReconvertAll => {
+                if self.is_converting() {
+                    return Task::none();
+                }
+                info!("Reconverting all {} files", self.files.len());
+                for file in &mut self.files {
+                    file.status = ConversionStatus::Pending;
+                }
+                return self.start_batch(false, false);
+            }
+            Message::ConvertSelected => return self.start_batch(true, false),
+            Message::ConversionFinished(task, result, hash) => {
+                let Some(batch) = &mut self.batch else {
+                    return Task::none();
+                };
+                let Some(index) = batch.outstanding.remove(&task) else {
+                    warn!("Ignoring the result of conversion task {}, which is not part of the running batch", task);
+                    return Task::none();
+                };
+                batch.completed += 1;
+                let batch_done = batch.outstanding.is_empty();
                 if let Some(file) = self.files.get_mut(index) {
-                    match &result {
-                        Ok(_) => {
+                    match result {
+                        Ok(outputs) => {
                             info!("Conversion successful for: {:?}", file.path);
+                            if let Some(journal) = &mut self.journal {
+                                journal.mark_done(&file.path, &outputs);
+                            }
+                            if let Some(hash) = hash {
+                                self.history.record(&file.path, hash);
+                            }
+                            batch.succeeded += 1;
                             file.status = ConversionStatus::Success;
+                            file.outputs = outputs;
+                            file.changed = None;
                         },
                         Err(e) => {
                             log::error!("Conversion failed for {:?}: {}", file.path, e);
-                            file.status = ConversionStatus::Error(e.clone());
+                            let name = file.path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                            batch.failures.push((name.clone(), e.clone()));
+                            file.status = ConversionStatus::Error(e);
+                            if self.options.error_policy == ErrorPolicy::Stop && !batch_done {
+                                self.stop_batch(&name);
+                                return Task::none();
+                            }
                         },
                     }
                 }
                 
-                if self.completed_files >= self.total_files {
-                    self.is_converting = false;
+                if batch_done {
                     info!("Batch conversion completed.");
+                    if self.write_report {
+                        self.export_report();
+                    }
+                    self.finish_batch(0);
                 }
             }
             Message::ToggleAbout => {
                 self.show_about = !self.show_about;
             }
+            Message::ResumeBatch => {
+                // The offer stays until a batch can actually take it over.
+                if self.is_converting() {
+                    return Task::none();
+                }
+                let Some(journal) = self.resume_offer.take() else {
+                    return Task::none();
+                };
+                info!("Resuming interrupted batch of {} files", journal.files.len());
+                self.output_dir = journal.output_dir;
+                self.options = journal.options;
+                for entry in journal.files {
+                    if !entry.source.exists() {
+                        warn!("Skipping {:?} from the journal, it no longer exists", entry.source);
+                        continue;
+                    }
+                    if self.files.iter().any(|f| f.path == entry.source) {
+                        continue;
+                    }
+                    let status = if entry.needs_conversion() { ConversionStatus::Pending } else { ConversionStatus::Success };
+                    let content_type = sniff_mismatch(&entry.source);
+                    self.files.push(FileEntry {
+                        path: entry.source,
+                        status,
+                        zip_entries: entry.zip_entries,
+                        outputs: entry.outputs,
+                        format_override: entry.format_override,
+                        selected: false,
+                        root: entry.root,
+                        hash: None,
+                        changed: None,
+                        content_type,
+                    });
+                }
+                // Replaced by the resumed batch's own journal.
+                Journal::remove();
+                return self.update(Message::ConvertAll);
+            }
+            Message::DismissResume => {
+                info!("Discarding interrupted batch journal");
+                self.resume_offer = None;
+                Journal::remove();
+            }
+            Message::ShowSummary => {
+                self.show_summary = self.summary.is_some();
+            }
+            Message::CloseSummary => {
+                self.show_summary = false;
+            }
+            Message::OpenOutputFolder => {
+                if let Some(dir) = self.output_folder() {
+                    info!("Opening output folder: {:?}", dir);
+                    if let Err(e) = opener::open(&dir) {
+                        warn!("Failed to open folder {:?}: {}", dir, e);
+                        self.status_message = Some(format!("无法打开文件夹: {}", e));
+                    }
+                }
+            }
+            Message::OpenLogsFolder => {
+                let dir = std::env::current_dir().map(|cwd| cwd.join(crate::LOG_DIR)).unwrap_or_else(|_| PathBuf::from(crate::LOG_DIR));
+                info!("Opening logs folder: {:?}", dir);
+                if let Err(e) = opener::open(&dir) {
+                    warn!("Failed to open folder {:?}: {}", dir, e);
+                    self.status_message = Some(format!("无法打开日志文件夹: {}", e));
+                }
+            }
+            Message::ExportReport => {
+                self.export_report();
+            }
+            Message::ToggleSettings => {
+                self.show_settings = !self.show_settings;
+            }
+            Message::ToggleReport(enabled) => {
+                self.write_report = enabled;
+            }
+            Message::ToggleConvertOnDrop(enabled) => {
+                self.convert_on_drop = enabled;
+            }
+            Message::ToggleForceConvert(force) => {
+                self.force_convert = force;
+            }
+            Message::FolderLayoutChanged(layout) => {
+                info!("Folder layout set to: {:?}", layout);
+                self.folder_layout = layout;
+            }
+            Message::ToggleStopOnError(stop) => {
+                self.options.error_policy = if stop { ErrorPolicy::Stop } else { ErrorPolicy::Continue };
+            }
+            Message::ApplyPreset(name) => {
+                if let Some(preset) = self.presets.iter().find(|p| p.name == name) {
+                    info!("Applying preset: {}", name);
+                    self.options = preset.options.clone();
+                }
+            }
+            Message::PageSizeChanged(size) => {
+                self.options.page_size = size;
+            }
+            Message::FontSizeChanged(size) => {
+                self.options.font_size = size;
+            }
+            Message::MarginChanged(side, value) => {
+                let value = if value.is_empty() { Some(0) } else { value.parse::<u8>().ok() };
+                if let Some(mm) = value.filter(|mm| ConversionOptions::MARGIN_RANGE.contains(mm)) {
+                    let margins = &mut self.options.margins;
+                    match side {
+                        MarginSide::Top => margins.top = mm,
+                        MarginSide::Right => margins.right = mm,
+                        MarginSide::Bottom => margins.bottom = mm,
+                        MarginSide::Left => margins.left = mm,
+                    }
+                }
+            }
+            Message::TogglePageNumbers(enabled) => {
+                self.options.page_numbers = enabled;
+            }
+            Message::ToggleAutoLandscape(enabled) => {
+                self.options.auto_landscape = enabled;
+            }
+            Message::ToggleSmartPunctuation(enabled) => {
+                self.options.smart_punctuation = enabled;
+            }
+            Message::TogglePresentation(enabled) => {
+                self.options.presentation = enabled;
+            }
+            Message::ToggleDetectLinks(enabled) => {
+                self.options.detect_links = enabled;
+            }
+            Message::TextAlignChanged(align) => {
+                info!("Text alignment set to: {:?}", align);
+                self.options.text_align = align;
+            }
+            Message::JsonLayoutChanged(layout) => {
+                info!("JSON layout set to: {:?}", layout);
+                self.options.json_layout = layout;
+            }
+            Message::DensityChanged(density) => {
+                info!("Markdown density set to: {:?}", density);
+                self.options.markdown_density = density;
+            }
+            Message::TrackedChangesChanged(mode) => {
+                info!("DOCX tracked changes mode set to: {:?}", mode);
+                self.options.tracked_changes = mode;
+            }
+            Message::TextEncodingChanged(encoding) => {
+                info!("Text encoding set to: {:?}", encoding);
+                self.options.text_encoding = encoding;
+            }
+            Message::ConformanceChanged(conformance) => {
+                info!("PDF conformance set to: {:?}", conformance);
+                self.options.conformance = conformance;
+            }
+            Message::PerformanceModeChanged(mode) => {
+                info!("Performance mode set to: {:?}", mode);
+                self.performance = mode;
+                if let Err(e) = AppConfig::save_performance(mode) {
+                    warn!("Failed to save performance mode: {:#}", e);
+                    self.status_message = Some(format!("无法保存性能模式: {}", e));
+                }
+            }
+            Message::AccentChanged(accent) => {
+                info!("Accent color set to: {:?}", accent);
+                self.accent = accent;
+                if let Err(e) = AppConfig::save_accent(accent) {
+                    warn!("Failed to save accent color: {:#}", e);
+                    self.status_message = Some(format!("无法保存主题色: {}", e));
+                }
+            }
+            Message::ThemeChanged(theme) => {
+                info!("Theme set to: {:?}", theme);
+                self.theme = theme;
+                if let Err(e) = AppConfig::save_theme(theme) {
+                    warn!("Failed to save theme: {:#}", e);
+                    self.status_message = Some(format!("无法保存界面主题: {}", e));
+                }
+            }
+            Message::OutputFormatChanged(format) => {
+                info!("Output format set to: {:?}", format);
+                self.options.output_format = format;
+            }
+            Message::ColorChanged(target, color) => {
+                info!("{:?} color set to {}", target, color.to_hex());
+                match target {
+                    ColorTarget::Heading => self.options.heading_color = color,
+                    ColorTarget::Body => self.options.body_color = color,
+                    ColorTarget::Background => self.options.page_background = color,
+                }
+            }
+            Message::SqliteRowLimitChanged(value) => {
+                if let Ok(limit) = value.parse::<u32>()
+                    && limit > 0
+                {
+                    self.options.sqlite_row_limit = limit;
+                }
+            }
+            Message::ToggleExcelSheetFiles(enabled) => {
+                self.options.excel_sheet_files = enabled;
+            }
+            Message::ToggleSplitByHeading(enabled) => {
+                self.options.split_by_heading = enabled;
+            }
+            Message::ToggleLogLineNumbers(enabled) => {
+                self.options.log_line_numbers = enabled;
+            }
+            Message::ToggleTableStripes(enabled) => {
+                self.options.table_stripes = enabled;
+            }
+            Message::ToggleStatsPage(enabled) => {
+                self.options.stats_page = enabled;
+            }
+            Message::MaxPagesChanged(value) => {
+                let value = if value.is_empty() { Some(0) } else { value.parse::<u32>().ok() };
+                if let Some(pages) = value {
+                    self.options.max_pages = pages;
+                }
+            }
+            Message::TimeoutChanged(value) => {
+                let value = if value.is_empty() { Some(0) } else { value.parse::<u32>().ok() };
+                if let Some(secs) = value {
+                    self.options.timeout_secs = secs;
+                }
+            }
+            Message::LongLineCharsChanged(value) => {
+                let value = if value.is_empty() { Some(0) } else { value.parse::<u32>().ok() };
+                if let Some(chars) = value {
+                    self.options.long_line_chars = chars;
+                }
+            }
+            Message::PartRangeChanged(value) => {
+                self.options.part_range = value;
+            }
+            Message::ToggleRedactEmails(enabled) => {
+                self.options.redaction.emails = enabled;
+            }
+            Message::ToggleRedactPhones(enabled) => {
+                self.options.redaction.phone_numbers = enabled;
+            }
+            Message::ToggleRedactCustom(enabled) => {
+                self.options.redaction.patterns = if enabled { self.redaction_patterns.clone() } else { Vec::new() };
+            }
+            Message::ToggleImageCompression(enabled) => {
+                self.options.image_compression.enabled = enabled;
+            }
+            Message::ImageQualityChanged(quality) => {
+                self.options.image_compression.quality = quality;
+            }
+            Message::ToggleKeepLossless(keep) => {
+                self.options.image_compression.keep_lossless = keep;
+            }
+            Message::FirstLineIndentChanged(value) => {
+                let value = if value.is_empty() { Some(0) } else { value.parse::<u8>().ok() };
+                if let Some(mm) = value {
+                    self.options.first_line_indent_mm = mm;
+                }
+            }
+            Message::ParagraphSpacingChanged(spacing) => {
+                info!("Paragraph spacing set to: {} lines", spacing);
+                self.options.paragraph_spacing = spacing;
+            }
+            Message::ImageDpiChanged(dpi) => {
+                info!("Image resolution set to: {} dpi", dpi);
+                self.options.image_dpi = dpi;
+            }
+            Message::ImageMaxMegapixelsChanged(value) => {
+                let value = if value.is_empty() { Some(0) } else { value.parse::<u32>().ok() };
+                if let Some(megapixels) = value {
+                    self.options.image_max_megapixels = megapixels;
+                }
+            }
+            Message::ImagePlacementChanged(placement) => {
+                info!("Image placement set to: {:?}", placement);
+                self.options.image_placement = placement;
+            }
+            Message::TabWidthChanged(width) => {
+                info!("Tab width set to: {}", width);
+                self.options.tab_width = width;
+            }
+            Message::OpenZipPicker(index) => {
+                if !self.is_converting() && index < self.files.len() {
+                    self.zip_picker = Some(index);
+                }
+            }
+            Message::ToggleZipEntry(entry, selected) => {
+                if let Some(file) = self.zip_picker.and_then(|i| self.files.get_mut(i))
+                    && let Some(e) = file.zip_entries.get_mut(entry)
+                {
+                    e.1 = selected;
+                }
+            }
+            Message::CloseZipPicker => {
+                self.zip_picker = None;
+            }
             Message::OpenLink(url) => {
                 info!("Opening URL: {}", url);
                 let _ = webbrowser::open(&url);
@@ -231,42 +1806,91 @@ impl App {
         Task::none()
     }
 
+    /// The queued files matching the filter box, with their indices in `self.files`.
+    fn visible_files(&self) -> impl Iterator<Item = (usize, &FileEntry)> {
+        let filter = self.filter.trim().to_lowercase();
+        self.files.iter().enumerate().filter(move |(_, file)| {
+            filter.is_empty()
+                || file.path.file_name().unwrap_or_default().to_string_lossy().to_lowercase().contains(&filter)
+        })
+    }
+
+    /// Whether a batch is running.
+    fn is_converting(&self) -> bool {
+        self.batch.is_some()
+    }
+
+    /// Ticks the spinner while files are converting; nothing is redrawn when none are.
+    pub fn subscription(&self) -> Subscription<Message> {
+        let drops = iced::event::listen_with(|event, _, _| match event {
+            iced::Event::Window(iced::window::Event::FileDropped(path)) => Some(Message::FileDropped(path)),
+            _ => None,
+        });
+        let spinner = if self.files.iter().any(|f| matches!(f.status, ConversionStatus::Converting)) {
+            iced::time::every(SPINNER_INTERVAL).map(|_| Message::SpinnerTick)
+        } else {
+            Subscription::none()
+        };
+        // The button may be released anywhere, not just over a row.
+        let drag = if self.dragging.is_some() {
+            iced::event::listen_with(|event, _, _| match event {
+                iced::Event::Mouse(iced::mouse::Event::ButtonReleased(iced::mouse::Button::Left)) => Some(Message::DropFile),
+                _ => None,
+            })
+        } else {
+            Subscription::none()
+        };
+        Subscription::batch([drops, spinner, drag])
+    }
+
     pub fn view(&self) -> Element<'_, Message> {
         // Colors
-        let primary_color = iced::Color::from_rgb(0.2, 0.6, 1.0); // Light blue
-        let success_color = iced::Color::from_rgb(0.2, 0.8, 0.4); // Green
-        let text_color = iced::Color::from_rgb(0.9, 0.9, 0.9);
-        let muted_color = iced::Color::from_rgb(0.6, 0.6, 0.6);
-        let panel_bg = iced::Color::from_rgb(0.12, 0.12, 0.12);
-        let card_bg = iced::Color::from_rgb(0.18, 0.18, 0.18);
+        let palette = Palette::new(self.accent, self.theme);
+
+        if let Some(error) = &self.font_error {
+            return self.font_error_view(error);
+        }
         
         if self.show_about {
             let about_content = container(
                 column![
-                    text("关于 Topdf").size(24).color(text_color),
-                    text("一个高效、跨平台的文档转PDF工具").size(16).color(muted_color),
+                    text("关于 Topdf").size(24).color(palette.text),
+                    text("一个高效、跨平台的文档转PDF工具").size(16).color(palette.muted),
                     Column::new().spacing(10).push(
                         row![
-                            text("作者: ").color(text_color),
-                            button(text("StarsUnsurpass").color(primary_color))
+                            text("作者: ").color(palette.text),
+                            button(text("StarsUnsurpass").color(palette.accent))
                                 .on_press(Message::OpenLink("https://github.com/StarsUnsurpass".to_string()))
                                 .style(|_,_| button::Style { background: None, ..button::Style::default() })
                         ]
                     ).push(
                          row![
-                            text("项目地址: ").color(text_color),
-                            button(text("GitHub/Topdf").color(primary_color))
+                            text("项目地址: ").color(palette.text),
+                            button(text("GitHub/Topdf").color(palette.accent))
                                 .on_press(Message::OpenLink("https://github.com/StarsUnsurpass/Topdf".to_string()))
                                 .style(|_,_| button::Style { background: None, ..button::Style::default() })
                         ]
+                    ).push(
+                        row![
+                            text("本次日志: ").color(palette.text),
+                            text(crate::LOG_FILE.get()
+                                .and_then(|path| path.file_name())
+                                .map_or_else(|| "未能创建日志文件".to_string(), |name| name.to_string_lossy().to_string()))
+                                .color(palette.muted),
+                            button(text("打开日志文件夹").color(palette.accent))
+                                .on_press(Message::OpenLogsFolder)
+                                .style(|_,_| button::Style { background: None, ..button::Style::default() })
+                        ]
+                        .align_y(iced::Alignment::Center)
                     ),
+                    text(self.status_message.as_deref().unwrap_or("反馈问题时请附上日志文件")).size(palette.small(12)).color(palette.muted),
                     button(text("返回").size(16))
                         .on_press(Message::ToggleAbout)
                         .padding(10)
                         .style(move |_theme, status| {
                              let mut base = button::Style::default();
                              base.background = Some(iced::Color::from_rgb(0.3, 0.3, 0.3).into());
-                             base.text_color = text_color;
+                             base.text_color = palette.text;
                              base.border = iced::Border { radius: 6.0.into(), ..iced::Border::default() };
                              match status {
                                  button::Status::Hovered => {
@@ -282,26 +1906,109 @@ impl App {
             )
             .center_x(Length::Fill)
             .center_y(Length::Fill)
-            .style(|_theme| container::Style {
-                background: Some(iced::Color::from_rgb(0.08, 0.08, 0.08).into()),
+            .style(move |_theme| container::Style {
+                background: Some(palette.background.into()),
                 ..container::Style::default()
             });
 
             return about_content.into();
         }
 
+        if self.show_settings {
+            return self.settings_view();
+        }
+
+        if self.show_summary
+            && let Some(summary) = &self.summary
+        {
+            return self.summary_view(summary);
+        }
+
+        if let Some(file) = self.zip_picker.and_then(|i| self.files.get(i)) {
+            let name = file.path.file_name().unwrap_or_default().to_string_lossy();
+            let entries: Element<Message> = if file.zip_entries.is_empty() {
+                text("压缩包中没有可转换的文件").size(14).color(palette.muted).into()
+            } else {
+                scrollable(Column::with_children(
+                    file.zip_entries.iter().enumerate().map(|(i, (entry, selected))| {
+                        checkbox(*selected)
+                            .label(entry.as_str())
+                            .on_toggle(move |checked| Message::ToggleZipEntry(i, checked))
+                            .text_size(14)
+                            .into()
+                    })
+                ).spacing(8)).height(Length::Fill).into()
+            };
+
+            return container(
+                column![
+                    text(format!("选择要转换的条目: {}", name)).size(20).color(palette.text),
+                    text("嵌套的压缩包暂不支持，已自动忽略").size(palette.small(12)).color(palette.muted),
+                    container(entries)
+                        .height(Length::Fill)
+                        .width(Length::Fill)
+                        .padding(15)
+                        .style(move |_theme| container::Style {
+                            background: Some(palette.card.into()),
+                            border: iced::Border { radius: 8.0.into(), ..iced::Border::default() },
+                            ..container::Style::default()
+                        }),
+                    button(text("完成").size(16))
+                        .on_press(Message::CloseZipPicker)
+                        .padding(10)
+                        .style(move |_theme, status| {
+                             let mut base = button::Style::default();
+                             base.background = Some(palette.accent.into());
+                             base.text_color = palette.on_accent;
+                             base.border = iced::Border { radius: 6.0.into(), ..iced::Border::default() };
+                             match status {
+                                 button::Status::Hovered => {
+                                     base.background = Some(palette.accent_hover.into());
+                                     base
+                                 },
+                                 _ => base,
+                             }
+                        })
+                ]
+                .spacing(15)
+                .align_x(iced::Alignment::Center)
+            )
+            .padding(30)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .style(move |_theme| container::Style {
+                background: Some(palette.background.into()),
+                ..container::Style::default()
+            })
+            .into();
+        }
+
         let nav_bar = row![
-            text("Topdf").size(20).color(primary_color).font(iced::font::Font::DEFAULT),
+            text("Topdf").size(20).color(palette.accent),
             iced::widget::Space::new().width(Length::Fill),
+            button(text("设置").size(14))
+                .on_press(Message::ToggleSettings)
+                .style(move |_theme, status| {
+                    let mut base = button::Style::default();
+                    base.background = None;
+                    base.text_color = palette.muted;
+                    match status {
+                        button::Status::Hovered => {
+                            base.text_color = palette.accent;
+                            base
+                        },
+                         _ => base,
+                    }
+                }),
             button(text("更多").size(14))
                 .on_press(Message::ToggleAbout)
                 .style(move |_theme, status| {
                     let mut base = button::Style::default();
                     base.background = None;
-                    base.text_color = muted_color;
+                    base.text_color = palette.muted;
                     match status {
                         button::Status::Hovered => {
-                            base.text_color = primary_color;
+                            base.text_color = palette.accent;
                             base
                         },
                          _ => base,
@@ -311,8 +2018,8 @@ impl App {
         .padding(10)
         .align_y(iced::Alignment::Center);
 
-        let title = text("Topdf 文档转换器").size(36).color(primary_color).font(iced::font::Font::DEFAULT);
-        let subtitle = text("高效 · 极简 · 多格式支持").size(16).color(muted_color);
+        let title = text("Topdf 文档转换器").size(36).color(palette.accent);
+        let subtitle = text("高效 · 极简 · 多格式支持").size(16).color(palette.muted);
         
         let header = column![title, subtitle].spacing(5).align_x(iced::Alignment::Center);
 
@@ -323,8 +2030,8 @@ impl App {
         .padding(12)
         .style(move |_theme, status| {
              let mut base = button::Style::default();
-             base.background = Some(primary_color.into());
-             base.text_color = iced::Color::WHITE;
+             base.background = Some(palette.accent.into());
+             base.text_color = palette.on_accent;
              base.border = iced::Border {
                     color: iced::Color::TRANSPARENT,
                     width: 0.0,
@@ -332,58 +2039,162 @@ impl App {
                 };
              match status {
                  button::Status::Hovered => {
-                     base.background = Some(iced::Color::from_rgb(0.3, 0.7, 1.0).into());
+                     base.background = Some(palette.accent_hover.into());
                      base
                  },
                  button::Status::Pressed => {
-                     base.background = Some(iced::Color::from_rgb(0.1, 0.5, 0.9).into());
+                     base.background = Some(palette.accent_pressed.into());
                      base
                  },
                  _ => base,
              }
         });
             
-        let file_list_content: Element<Message> = if self.files.is_empty() {
+        let add_folder_btn = button(text("添加文件夹").size(14))
+            .on_press(Message::AddFolder)
+            .padding(8)
+            .style(move |_theme, status| {
+                let mut base = button::Style::default();
+                base.background = Some(iced::Color::from_rgb(0.25, 0.25, 0.25).into());
+                base.text_color = palette.text;
+                base.border = iced::Border { radius: 6.0.into(), ..iced::Border::default() };
+                match status {
+                    button::Status::Hovered => {
+                        base.background = Some(iced::Color::from_rgb(0.35, 0.35, 0.35).into());
+                        base
+                    },
+                    _ => base,
+                }
+            });
+
+        let fonts_ready = self.fonts.is_some();
+        let selected_count = self.files.iter().filter(|f| f.selected).count();
+        let clipboard_btn = button(text("从剪贴板").size(14))
+            .on_press_maybe(fonts_ready.then_some(Message::ConvertClipboard))
+            .padding(8)
+            .style(move |_theme, status| {
+                let mut base = button::Style::default();
+                base.background = Some(iced::Color::from_rgb(0.25, 0.25, 0.25).into());
+                base.text_color = palette.text;
+                base.border = iced::Border { radius: 6.0.into(), ..iced::Border::default() };
+                match status {
+                    button::Status::Hovered => {
+                        base.background = Some(iced::Color::from_rgb(0.35, 0.35, 0.35).into());
+                        base
+                    },
+                    button::Status::Disabled => {
+                        base.text_color = palette.muted;
+                        base
+                    },
+                    _ => base,
+                }
+            });
+
+        let all_selected = self.visible_files().next().is_some() && self.visible_files().all(|(_, f)| f.selected);
+        let select_all_btn = button(text(if all_selected { "取消全选" } else { "全选" }).size(14))
+            .on_press_maybe((!self.files.is_empty() && !self.is_converting()).then_some(Message::SelectAll(!all_selected)))
+            .padding(8)
+            .style(move |_theme, status| {
+                let mut base = button::Style::default();
+                base.background = Some(iced::Color::from_rgb(0.25, 0.25, 0.25).into());
+                base.text_color = palette.text;
+                base.border = iced::Border { radius: 6.0.into(), ..iced::Border::default() };
+                match status {
+                    button::Status::Hovered => {
+                        base.background = Some(iced::Color::from_rgb(0.35, 0.35, 0.35).into());
+                        base
+                    },
+                    button::Status::Disabled => {
+                        base.text_color = palette.muted;
+                        base
+                    },
+                    _ => base,
+                }
+            });
+
+        let has_completed = self.files.iter().any(|f| matches!(f.status, ConversionStatus::Success | ConversionStatus::Unchanged));
+        let remove_completed_btn = button(text("清除已完成").size(14))
+            .on_press_maybe((has_completed && !self.is_converting()).then_some(Message::RemoveCompleted))
+            .padding(8)
+            .style(move |_theme, status| {
+                let mut base = button::Style::default();
+                base.background = Some(iced::Color::from_rgb(0.25, 0.25, 0.25).into());
+                base.text_color = palette.text;
+                base.border = iced::Border { radius: 6.0.into(), ..iced::Border::default() };
+                match status {
+                    button::Status::Hovered => {
+                        base.background = Some(iced::Color::from_rgb(0.35, 0.35, 0.35).into());
+                        base
+                    },
+                    button::Status::Disabled => {
+                        base.text_color = palette.muted;
+                        base
+                    },
+                    _ => base,
+                }
+            });
+            
+        let file_list_content: Element<Message> = if self.files.is_empty() && self.scanning > 0 {
+            container(text(format!("正在扫描… 剩余 {} 个文件", self.scanning)).size(16).color(palette.muted))
+                .center_x(Length::Fill)
+                .center_y(Length::Fill)
+                .into()
+        } else if self.files.is_empty() {
             container(
                 column![
-                    text("暂无文件").size(20).color(muted_color),
-                    text("拖拽文件到此处 或 点击上方“添加文件”按钮").size(14).color(muted_color)
+                    text("暂无文件").size(20).color(palette.muted),
+                    text("拖拽文件到此处 或 点击上方“添加文件”按钮").size(14).color(palette.muted)
                 ].spacing(10).align_x(iced::Alignment::Center)
             )
             .center_x(Length::Fill)
             .center_y(Length::Fill)
             .into()
+        } else if self.visible_files().next().is_none() {
+            container(text("没有匹配的文件").size(14).color(palette.muted))
+                .center_x(Length::Fill)
+                .center_y(Length::Fill)
+                .into()
         } else {
             let list = Column::with_children(
-                self.files.iter().enumerate().map(|(i, file)| {
+                self.visible_files().map(|(i, file)| {
                     let name = file.path.file_name().unwrap_or_default().to_string_lossy();
                     let (status_txt, status_color) = match &file.status {
-                        ConversionStatus::Pending => ("等待中", muted_color),
-                        ConversionStatus::Converting => ("转换中...", primary_color),
-                        ConversionStatus::Success => ("转换成功", success_color),
-                        ConversionStatus::Error(_e) => ("转换失败", iced::Color::from_rgb(0.9, 0.3, 0.3)),
+                        ConversionStatus::Pending if file.changed == Some(true) => ("已更新", palette.accent),
+                        ConversionStatus::Pending if file.changed == Some(false) => ("未更改", palette.muted),
+                        ConversionStatus::Pending => ("等待中", palette.muted),
+                        ConversionStatus::Converting => ("转换中...", palette.accent),
+                        ConversionStatus::Success => ("转换成功", palette.success),
+                        ConversionStatus::Unchanged => ("未更改，已跳过", palette.muted),
+                        ConversionStatus::Error(ConversionError::TimedOut(_)) => ("转换超时", palette.error),
+                        ConversionStatus::Error(_e) => ("转换失败", palette.error),
                     };
                     
                     let status_element = if let ConversionStatus::Error(e) = &file.status {
                          column![
-                             text(status_txt).size(12).color(status_color),
-                             text(e).size(10).color(status_color)
+                             text(status_txt).size(palette.small(12)).color(status_color),
+                             mouse_area(text(error_message(e)).size(palette.small(10)).color(status_color))
+                                 .on_press(Message::CopyError(i))
+                                 .interaction(iced::mouse::Interaction::Pointer)
                          ]
+                    } else if let ConversionStatus::Converting = file.status {
+                         column![row![spinner(self.spinner_frame, palette), text(status_txt).size(palette.small(12)).color(status_color)]
+                             .spacing(6)
+                             .align_y(iced::Alignment::Center)]
                     } else {
-                         column![text(status_txt).size(12).color(status_color)]
+                         column![text(status_txt).size(palette.small(12)).color(status_color)]
                     };
 
-                    let remove_btn = if !self.is_converting {
+                    let remove_btn = if !self.is_converting() {
                         button(text(" × ").size(14))
                             .on_press(Message::RemoveFile(i))
                             .padding(5)
                             .style(move |_theme, status| {
                                 let mut base = button::Style::default();
-                                base.text_color = muted_color;
+                                base.text_color = palette.muted;
                                 base.background = Some(iced::Color::TRANSPARENT.into());
                                 match status {
                                     button::Status::Hovered => {
-                                        base.text_color = iced::Color::from_rgb(0.9, 0.3, 0.3);
+                                        base.text_color = palette.error;
                                         base
                                     },
                                     _ => base,
@@ -397,53 +2208,206 @@ impl App {
                         }) 
                     };
 
-                    container(row![
+                    let zip_btn: Element<Message> = if file.is_zip() {
+                        let selected = file.zip_entries.iter().filter(|(_, s)| *s).count();
+                        button(text(format!("选择条目 ({}/{})", selected, file.zip_entries.len())).size(palette.small(12)))
+                            .on_press_maybe((!self.is_converting()).then_some(Message::OpenZipPicker(i)))
+                            .padding(5)
+                            .style(move |_theme, status| {
+                                let mut base = button::Style::default();
+                                base.text_color = palette.accent;
+                                base.background = Some(iced::Color::TRANSPARENT.into());
+                                match status {
+                                    button::Status::Hovered => {
+                                        base.text_color = palette.accent_hover;
+                                        base
+                                    },
+                                    _ => base,
+                                }
+                            })
+                            .into()
+                    } else {
+                        Column::new().into()
+                    };
+
+                    let copy_action = match &file.status {
+                        ConversionStatus::Success if !file.outputs.is_empty() => Some(("复制路径", Message::CopyOutputPath(i))),
+                        ConversionStatus::Error(_) => Some(("复制错误", Message::CopyError(i))),
+                        _ => None,
+                    };
+                    let copy_btn: Element<Message> = if let Some((label, message)) = copy_action {
+                        button(text(label).size(palette.small(12)))
+                            .on_press(message)
+                            .padding(5)
+                            .style(move |_theme, status| {
+                                let mut base = button::Style::default();
+                                base.text_color = palette.accent;
+                                base.background = Some(iced::Color::TRANSPARENT.into());
+                                match status {
+                                    button::Status::Hovered => {
+                                        base.text_color = palette.accent_hover;
+                                        base
+                                    },
+                                    _ => base,
+                                }
+                            })
+                            .into()
+                    } else {
+                        Column::new().into()
+                    };
+
+                    let format = file.format_override.unwrap_or(self.options.output_format);
+                    let flashing = self.flashing.contains(&file.path);
+                    let format_picker: Element<Message> = if self.is_converting() {
+                        text(format.to_string()).size(palette.small(12)).color(palette.muted).into()
+                    } else {
+                        let file_type = converter::FileType::from_path(&file.path);
+                        let formats: Vec<OutputFormat> = OutputFormat::ALL.into_iter().filter(|f| f.applies_to(file_type)).collect();
+                        pick_list(formats, Some(format), move |f| Message::FileFormatChanged(i, f))
+                            .text_size(12)
+                            .into()
+                    };
+
+                    // Unknown files and files whose content contradicts their extension are
+                    // likely to fail, so they stand out before anything is converted.
+                    let file_type = converter::FileType::from_path(&file.path);
+                    let detected_type = match file.content_type {
+                        Some(sniffed) => text(format!("{}（内容像{}）", type_label(file_type), type_label(sniffed)))
+                            .size(palette.small(11))
+                            .color(palette.error),
+                        None if file_type == converter::FileType::Unknown => {
+                            text(type_label(file_type)).size(palette.small(11)).color(palette.error)
+                        }
+                        None => text(type_label(file_type)).size(palette.small(11)).color(palette.muted),
+                    };
+
+                    // Dragging the handle reorders the list, which is the order files are
+                    // appended in.
+                    let drag_handle: Element<Message> = if self.is_converting() {
+                        text("≡").size(16).color(palette.muted).into()
+                    } else {
+                        mouse_area(text("≡").size(16).color(if self.dragging == Some(i) { palette.accent } else { palette.muted }))
+                            .on_press(Message::DragFile(i))
+                            .interaction(iced::mouse::Interaction::Grab)
+                            .into()
+                    };
+                    let dragged = self.dragging == Some(i);
+
+                    let row = container(row![
+                        drag_handle,
+                        checkbox(file.selected)
+                            .on_toggle_maybe((!self.is_converting()).then_some(move |_| Message::ToggleSelect(i))),
+                        file_icon(file_type, palette),
                         column![
-                            text(name).size(14).color(text_color),
+                            row![
+                                text(name).size(14).color(palette.text),
+                                detected_type,
+                            ].spacing(8).align_y(iced::Alignment::Center),
                             status_element
                         ].width(Length::Fill).spacing(4),
+                        zip_btn,
+                        copy_btn,
+                        format_picker,
                         remove_btn
                     ]
                     .align_y(iced::Alignment::Center)
                     .spacing(10))
                     .padding(12)
                     .style(move |_theme| container::Style {
-                        background: Some(card_bg.into()),
-                        border: iced::Border {
-                            color: iced::Color::from_rgb(0.25, 0.25, 0.25),
-                            width: 1.0,
-                            radius: 6.0.into(),
+                        background: Some(palette.card.into()),
+                        border: if flashing || dragged {
+                            iced::Border { color: palette.accent, width: 2.0, radius: 6.0.into() }
+                        } else {
+                            iced::Border {
+                                color: iced::Color::from_rgb(0.25, 0.25, 0.25),
+                                width: 1.0,
+                                radius: 6.0.into(),
+                            }
                         },
                         ..container::Style::default()
-                    })
-                    .into()
+                    });
+                    mouse_area(row).on_enter(Message::DragOver(i)).into()
                 })
             ).spacing(8);
             
             scrollable(list).into()
         };
 
+        let file_filter: Element<Message> = if self.files.is_empty() {
+            Column::new().into()
+        } else {
+            let shown = self.visible_files().count();
+            let mut filter_row = row![
+                text_input("按文件名筛选…", &self.filter)
+                    .on_input(Message::FilterChanged)
+                    .size(13)
+                    .width(Length::Fill),
+                text(format!("{} / {}", shown, self.files.len())).size(palette.small(12)).color(palette.muted),
+            ];
+            if self.scanning > 0 {
+                filter_row = filter_row.push(text(format!("正在扫描… 剩余 {}", self.scanning)).size(palette.small(12)).color(palette.accent));
+            }
+            filter_row
+                .spacing(10)
+                .align_y(iced::Alignment::Center)
+                .into()
+        };
+
+        let status_text: Element<Message> = match &self.status_message {
+            Some(msg) => text(msg).size(palette.small(12)).color(palette.success).into(),
+            None => Column::new().into(),
+        };
+        let status_line: Element<Message> = if self.summary.is_some() && !self.is_converting() {
+            row![
+                container(status_text).width(Length::Fill),
+                button(text("查看本次结果").size(palette.small(12)).color(palette.accent))
+                    .on_press(Message::ShowSummary)
+                    .padding(0)
+                    .style(|_, _| button::Style { background: None, ..button::Style::default() }),
+            ]
+            .align_y(iced::Alignment::Center)
+            .into()
+        } else {
+            status_text
+        };
+
         let output_text = if let Some(p) = &self.output_dir {
             format!("输出路径: {}", p.display())
         } else {
             "输出路径: 默认 (源文件所在目录)".to_string()
         };
+        let output_info: Element<Message> = match &self.append_target {
+            Some(target) => column![
+                text(output_text).size(palette.small(12)).color(palette.muted),
+                row![
+                    text(format!("PDF 追加到: {}", target.display())).size(palette.small(12)).color(palette.accent),
+                    button(text("取消追加").size(palette.small(12)).color(palette.muted))
+                        .on_press_maybe((!self.is_converting()).then_some(Message::ClearAppendTarget))
+                        .padding(0)
+                        .style(|_, _| button::Style { background: None, ..button::Style::default() }),
+                ]
+                .spacing(10),
+            ]
+            .spacing(2)
+            .into(),
+            None => text(output_text).size(palette.small(12)).color(palette.muted).into(),
+        };
         
-        let progress_section: Element<Message> = if self.is_converting || (self.completed_files > 0 && self.completed_files < self.total_files) {
-             let progress = if self.total_files > 0 {
-                 self.completed_files as f32 / self.total_files as f32 * 100.0
+        let progress_section: Element<Message> = if let Some(batch) = &self.batch {
+             let progress = if batch.total > 0 {
+                 batch.completed as f32 / batch.total as f32 * 100.0
              } else {
                  0.0
              };
              
              column![
                  row![
-                     text::<Theme, Renderer>("总体进度:").size(12).color(muted_color),
-                     text::<Theme, Renderer>(format!("{} / {}", self.completed_files, self.total_files)).size(12).color(primary_color)
+                     text::<Theme, Renderer>("总体进度:").size(palette.small(12)).color(palette.muted),
+                     text::<Theme, Renderer>(format!("{} / {}", batch.completed, batch.total)).size(palette.small(12)).color(palette.accent)
                  ].spacing(5),
                  progress_bar::<Theme>(0.0..=100.0, progress).style(move |_theme| progress_bar::Style {
                      background: iced::Color::from_rgb(0.2, 0.2, 0.2).into(),
-                     bar: primary_color.into(),
+                     bar: palette.accent.into(),
                      border: iced::Border {
                          radius: 3.0.into(),
                          ..iced::Border::default()
@@ -454,8 +2418,97 @@ impl App {
              Column::new().into()
         };
 
+        let resume_banner: Element<Message> = match &self.resume_offer {
+            Some(journal) => container(
+                row![
+                    text(format!("上次的批量转换未完成，还有 {} 个文件未转换，是否继续？", journal.unfinished()))
+                        .size(13)
+                        .color(palette.text)
+                        .width(Length::Fill),
+                    button(text("继续转换").size(13))
+                        .on_press_maybe((fonts_ready && !self.is_converting()).then_some(Message::ResumeBatch))
+                        .padding(6)
+                        .style(move |_theme, status| {
+                            let mut base = button::Style::default();
+                            base.background = Some(palette.accent.into());
+                            base.text_color = palette.on_accent;
+                            base.border = iced::Border { radius: 6.0.into(), ..iced::Border::default() };
+                            if let button::Status::Disabled = status {
+                                base.background = Some(iced::Color::from_rgb(0.3, 0.3, 0.3).into());
+                                base.text_color = palette.muted;
+                            }
+                            base
+                        }),
+                    button(text("忽略").size(13).color(palette.muted))
+                        .on_press(Message::DismissResume)
+                        .padding(6)
+                        .style(|_, _| button::Style { background: None, ..button::Style::default() }),
+                ]
+                .spacing(10)
+                .align_y(iced::Alignment::Center)
+            )
+            .padding(10)
+            .style(move |_theme| container::Style {
+                background: Some(palette.card.into()),
+                border: iced::Border { color: palette.accent, width: 1.0, radius: 6.0.into() },
+                ..container::Style::default()
+            })
+            .into(),
+            None => Column::new().into(),
+        };
+
+        let merge_banner: Element<Message> = match &self.merge_offer {
+            Some(paths) => container(
+                row![
+                    text(format!("已选择 {} 个文件，可按选择顺序合并为一个 PDF，或逐个添加到待转换列表", paths.len()))
+                        .size(13)
+                        .color(palette.text)
+                        .width(Length::Fill),
+                    button(text("合并这些文件").size(13))
+                        .on_press_maybe((fonts_ready && !self.merging).then_some(Message::MergeSelection))
+                        .padding(6)
+                        .style(move |_theme, status| {
+                            let mut base = button::Style::default();
+                            base.background = Some(palette.accent.into());
+                            base.text_color = palette.on_accent;
+                            base.border = iced::Border { radius: 6.0.into(), ..iced::Border::default() };
+                            if let button::Status::Disabled = status {
+                                base.background = Some(iced::Color::from_rgb(0.3, 0.3, 0.3).into());
+                                base.text_color = palette.muted;
+                            }
+                            base
+                        }),
+                    button(text("添加到列表").size(13).color(palette.muted))
+                        .on_press(Message::QueueSelection)
+                        .padding(6)
+                        .style(|_, _| button::Style { background: None, ..button::Style::default() }),
+                ]
+                .spacing(10)
+                .align_y(iced::Alignment::Center)
+            )
+            .padding(10)
+            .style(move |_theme| container::Style {
+                background: Some(palette.card.into()),
+                border: iced::Border { color: palette.accent, width: 1.0, radius: 6.0.into() },
+                ..container::Style::default()
+            })
+            .into(),
+            None if self.merging => text("正在合并…").size(13).color(palette.muted).into(),
+            None => Column::new().into(),
+        };
+
         let left_panel = container(column![
-            row![add_btn, text("待转换列表").size(18).color(text_color)].spacing(20).align_y(iced::Alignment::Center),
+            resume_banner,
+            merge_banner,
+            row![
+                add_btn,
+                add_folder_btn,
+                text("待转换列表").size(18).color(palette.text).width(Length::Fill),
+                clipboard_btn,
+                select_all_btn,
+                remove_completed_btn,
+            ].spacing(20).align_y(iced::Alignment::Center),
+            file_filter,
             container(file_list_content)
                 .height(Length::Fill)
                 .style(|_theme| container::Style {
@@ -469,6 +2522,7 @@ impl App {
                 })
                 .padding(15),
             progress_section,
+            status_line,
             row![
                 button(text("选择输出文件夹").size(14))
                     .on_press(Message::SelectOutputDir)
@@ -476,24 +2530,109 @@ impl App {
                     .style(move |_theme, status| {
                         let mut base = button::Style::default();
                         base.background = Some(iced::Color::from_rgb(0.25, 0.25, 0.25).into());
-                        base.text_color = text_color;
+                        base.text_color = palette.text;
+                        base.border = iced::Border { radius: 6.0.into(), ..iced::Border::default() };
+                        match status {
+                            button::Status::Hovered => {
+                                base.background = Some(iced::Color::from_rgb(0.35, 0.35, 0.35).into());
+                                base
+                            },
+                            _ => base,
+                        }
+                    }),
+                button(text("移动已生成文件").size(14))
+                    .on_press_maybe((!self.is_converting() && self.files.iter().any(|f| !f.outputs.is_empty())).then_some(Message::MoveOutputs))
+                    .padding(10)
+                    .style(move |_theme, status| {
+                        let mut base = button::Style::default();
+                        base.background = Some(iced::Color::from_rgb(0.25, 0.25, 0.25).into());
+                        base.text_color = palette.text;
+                        base.border = iced::Border { radius: 6.0.into(), ..iced::Border::default() };
+                        match status {
+                            button::Status::Hovered => {
+                                base.background = Some(iced::Color::from_rgb(0.35, 0.35, 0.35).into());
+                                base
+                            },
+                            button::Status::Disabled => {
+                                base.text_color = palette.muted;
+                                base
+                            },
+                            _ => base,
+                        }
+                    }),
+                button(text("追加到 PDF").size(14))
+                    .on_press_maybe((!self.is_converting()).then_some(Message::SelectAppendTarget))
+                    .padding(10)
+                    .style(move |_theme, status| {
+                        let mut base = button::Style::default();
+                        base.background = Some(iced::Color::from_rgb(0.25, 0.25, 0.25).into());
+                        base.text_color = palette.text;
                         base.border = iced::Border { radius: 6.0.into(), ..iced::Border::default() };
                         match status {
                             button::Status::Hovered => {
                                 base.background = Some(iced::Color::from_rgb(0.35, 0.35, 0.35).into());
                                 base
                             },
+                            button::Status::Disabled => {
+                                base.text_color = palette.muted;
+                                base
+                            },
+                            _ => base,
+                        }
+                    }),
+                container(output_info).width(Length::Fill).align_y(iced::Alignment::Center),
+                button(text(format!(" 转换所选 ({}) ", selected_count)).size(16))
+                    .on_press_maybe((fonts_ready && selected_count > 0).then_some(Message::ConvertSelected))
+                    .padding(12)
+                    .style(move |_theme, status| {
+                         let mut base = button::Style::default();
+                         base.background = Some(palette.accent.into());
+                         base.text_color = palette.on_accent;
+                         base.border = iced::Border { radius: 8.0.into(), ..iced::Border::default() };
+                         match status {
+                             button::Status::Hovered => {
+                                 base.background = Some(palette.accent_hover.into());
+                                 base
+                             },
+                             button::Status::Pressed => {
+                                 base.background = Some(palette.accent_pressed.into());
+                                 base
+                             },
+                             button::Status::Disabled => {
+                                 base.background = Some(iced::Color::from_rgb(0.3, 0.3, 0.3).into());
+                                 base.text_color = palette.muted;
+                                 base
+                             },
+                             _ => base,
+                         }
+                    }),
+                button(text(" 全部重新转换 ").size(16))
+                    .on_press_maybe((fonts_ready && !self.is_converting() && !self.files.is_empty()).then_some(Message::ReconvertAll))
+                    .padding(12)
+                    .style(move |_theme, status| {
+                        let mut base = button::Style::default();
+                        base.background = Some(iced::Color::from_rgb(0.25, 0.25, 0.25).into());
+                        base.text_color = palette.text;
+                        base.border = iced::Border { radius: 8.0.into(), ..iced::Border::default() };
+                        match status {
+                            button::Status::Hovered => {
+                                base.background = Some(iced::Color::from_rgb(0.35, 0.35, 0.35).into());
+                                base
+                            },
+                            button::Status::Disabled => {
+                                base.text_color = palette.muted;
+                                base
+                            },
                             _ => base,
                         }
                     }),
-                container(text(output_text).size(12).color(muted_color)).width(Length::Fill).align_y(iced::Alignment::Center),
-                button(text(" 开始转换 ").size(16).font(iced::font::Font::DEFAULT)) // bold if possible
-                    .on_press(Message::ConvertAll)
+                button(text(if fonts_ready { " 开始转换 " } else { " 加载字体中… " }).size(16))
+                    .on_press_maybe(fonts_ready.then_some(Message::ConvertAll))
                     .padding(12)
                     .style(move |_theme, status| {
                          let mut base = button::Style::default();
-                         base.background = Some(success_color.into());
-                         base.text_color = iced::Color::WHITE;
+                         base.background = Some(palette.success.into());
+                         base.text_color = palette.on_accent;
                          base.border = iced::Border {
                                 color: iced::Color::TRANSPARENT,
                                 width: 0.0,
@@ -501,11 +2640,16 @@ impl App {
                             };
                          match status {
                              button::Status::Hovered => {
-                                 base.background = Some(iced::Color::from_rgb(0.3, 0.9, 0.5).into());
+                                 base.background = Some(palette.success_hover.into());
                                  base
                              },
                              button::Status::Pressed => {
-                                 base.background = Some(iced::Color::from_rgb(0.1, 0.7, 0.3).into());
+                                 base.background = Some(palette.success_pressed.into());
+                                 base
+                             },
+                             button::Status::Disabled => {
+                                 base.background = Some(iced::Color::from_rgb(0.3, 0.3, 0.3).into());
+                                 base.text_color = palette.muted;
                                  base
                              },
                              _ => base,
@@ -518,25 +2662,26 @@ impl App {
 
         // Right Panel: Info and Help
         let right_panel = container(column![
-            text("支持的文件格式").size(18).color(success_color),
+            text("支持的文件格式").size(18).color(palette.accent),
             column![
-                text("• 文档: DOCX, TXT").size(14).color(text_color),
-                text("• 数据: JSON, XML, CSV, YAML, TOML, Excel").size(14).color(text_color),
-                text("• 网页: HTML, Markdown (MD)").size(14).color(text_color),
-                text("• 图片: PNG, JPG, BMP").size(14).color(text_color),
-                text("• 代码: RS, PY, JS, C, CPP").size(14).color(text_color),
+                text("• 文档: DOCX, TXT, 邮件 (EML)").size(14).color(palette.text),
+                text("• 数据: JSON, XML, CSV, YAML, TOML, Excel, ODS, SQLite").size(14).color(palette.text),
+                text("• 压缩包: ZIP (逐个转换其中的文件)").size(14).color(palette.text),
+                text("• 网页/标记: HTML, Markdown (MD), reStructuredText (RST)").size(14).color(palette.text),
+                text("• 图片: PNG, JPG, BMP").size(14).color(palette.text),
+                text("• 代码: RS, PY, JS, C, CPP").size(14).color(palette.text),
             ].spacing(8),
             
-            text("操作指南").size(18).color(iced::Color::from_rgb(1.0, 0.8, 0.4)), // Gold
+            text("操作指南").size(18).color(palette.accent),
             column![
-                text("1. 点击“添加文件”或直接将文件拖入窗口。").size(14).color(text_color),
-                text("2. (可选) 点击“选择输出文件夹”修改保存位置。").size(14).color(text_color),
-                text("3. 点击“开始转换”按钮。").size(14).color(text_color),
+                text("1. 点击“添加文件”或直接将文件拖入窗口。").size(14).color(palette.text),
+                text("2. (可选) 点击“选择输出文件夹”修改保存位置。").size(14).color(palette.text),
+                text("3. 点击“开始转换”按钮。").size(14).color(palette.text),
             ].spacing(8),
             
             container(
                 text("提示: 软件内置了中文字体支持，若仍出现乱码，请确保系统安装了微软雅黑或 SimHei 字体。"
-                ).size(12).color(muted_color)
+                ).size(palette.small(12)).color(palette.muted)
             ).padding(10).style(|_theme| container::Style {
                 background: Some(iced::Color::from_rgb(0.15, 0.15, 0.18).into()),
                 border: iced::Border { radius: 6.0.into(), ..iced::Border::default() },
@@ -547,7 +2692,7 @@ impl App {
         .padding(25)
         .width(Length::FillPortion(1))
         .style(move |_theme| container::Style {
-            background: Some(panel_bg.into()),
+            background: Some(palette.panel.into()),
             border: iced::Border {
                 color: iced::Color::TRANSPARENT,
                 width: 0.0,
@@ -558,14 +2703,974 @@ impl App {
 
         let main_content = row![left_panel, right_panel].spacing(30).height(Length::Fill);
 
-        container(column![nav_bar, header, main_content].spacing(20))
+        let window = container(column![nav_bar, header, main_content].spacing(20))
             .width(Length::Fill)
             .height(Length::Fill)
             .padding(20)
-            .style(|_theme| container::Style {
-                background: Some(iced::Color::from_rgb(0.08, 0.08, 0.08).into()), // Very dark bg
+            .style(move |_theme| container::Style {
+                background: Some(palette.background.into()),
+                ..container::Style::default()
+            });
+
+        match &self.toast {
+            Some(toast) => stack![
+                window,
+                container(
+                    container(text(toast).size(14).color(palette.on_accent))
+                        .padding([8, 16])
+                        .style(move |_theme| container::Style {
+                            background: Some(palette.accent.into()),
+                            border: iced::Border { radius: 6.0.into(), ..iced::Border::default() },
+                            ..container::Style::default()
+                        })
+                )
+                .width(Length::Fill)
+                .height(Length::Fill)
+                .align_x(iced::Alignment::Center)
+                .align_y(iced::Alignment::End)
+                .padding(40),
+            ]
+            .into(),
+            None => window.into(),
+        }
+    }
+
+    /// Highlights the rows of `paths`, which the user just added again, and says they were
+    /// skipped.
+    fn flash_duplicates(&mut self, paths: Vec<PathBuf>) -> Task<Message> {
+        let toast = if paths.len() == 1 {
+            "已跳过重复文件".to_string()
+        } else {
+            format!("已跳过 {} 个重复文件", paths.len())
+        };
+        let task = self.show_toast(toast);
+        self.flashing = paths;
+        task
+    }
+
+    /// Shows `toast` over the window for a moment; any highlighted rows fade along with it.
+    fn show_toast(&mut self, toast: String) -> Task<Message> {
+        self.toast = Some(toast);
+        self.flash_id += 1;
+        let id = self.flash_id;
+        let (tx, rx) = futures::channel::oneshot::channel();
+        std::thread::spawn(move || {
+            std::thread::sleep(FLASH_DURATION);
+            let _ = tx.send(());
+        });
+        Task::perform(rx, move |_| Message::FlashEnded(id))
+    }
+
+    /// Converts every file that has not been converted yet, or with `selected_only` every
+    /// ticked file, converted or not.  With `skip_unchanged`, files whose output is newer than
+    /// them are marked [`ConversionStatus::Unchanged`] instead.
+    fn start_batch(&mut self, selected_only: bool, skip_unchanged: bool) -> Task<Message> {
+        let Some(fonts) = self.fonts.clone() else {
+            return Task::none();
+        };
+        if self.files.is_empty() || self.is_converting() {
+            return Task::none();
+        }
+
+        info!("Starting batch conversion...");
+
+        // Count files to convert
+        let mut files_to_convert: Vec<usize> = self.files.iter().enumerate()
+            .filter(|(_, f)| if selected_only { f.selected } else { !matches!(f.status, ConversionStatus::Success) })
+            .map(|(i, _)| i)
+            .collect();
+        let mut unchanged = 0;
+        if skip_unchanged {
+            files_to_convert.retain(|&i| {
+                let Some(output) = self.single_output(&self.files[i]).filter(|o| is_up_to_date(&self.files[i].path, o)) else {
+                    return true;
+                };
+                info!("Skipping {:?}, its output {:?} is up to date", self.files[i].path, output);
+                let file = &mut self.files[i];
+                file.status = ConversionStatus::Unchanged;
+                file.outputs = vec![output];
+                unchanged += 1;
+                false
+            });
+        }
+
+        info!("Files scheduled for conversion: {}", files_to_convert.len());
+        if files_to_convert.is_empty() {
+            info!("No pending files to convert.");
+            if unchanged > 0 {
+                self.status_message = Some(format!("{} 个文件未更改，已跳过", unchanged));
+            }
+            return Task::none();
+        }
+        // A batch of selected files resumes with those files only.
+        let journaled = self.files.iter().enumerate()
+            .filter(|(_, f)| !selected_only || f.selected)
+            .map(|(i, f)| journal_entry(f, !files_to_convert.contains(&i)))
+            .collect();
+        self.begin_batch(journaled);
+        if unchanged > 0 {
+            self.status_message = Some(format!("{} 个文件未更改，已跳过", unchanged));
+        }
+
+        let tasks: Vec<Task<Message>> = files_to_convert.into_iter().map(|i| self.schedule(i, &fonts)).collect();
+        self.abortable(Task::batch(tasks))
+    }
+
+    /// Converts the file at `index` right away: as a batch of its own, or as part of the running
+    /// batch, sharing its workers.  Used for files dropped with "convert on drop" on.
+    fn convert_dropped(&mut self, index: usize) -> Task<Message> {
+        let Some(fonts) = self.fonts.clone() else {
+            // Stays pending; the next batch picks it up.
+            return Task::none();
+        };
+        let Some(file) = self.files.get(index) else {
+            return Task::none();
+        };
+        if matches!(file.status, ConversionStatus::Converting) {
+            return Task::none();
+        }
+        info!("Converting dropped file: {:?}", file.path);
+        let entry = journal_entry(file, false);
+        match &mut self.journal {
+            Some(journal) if self.batch.is_some() => {
+                journal.files.retain(|f| f.source != entry.source);
+                journal.files.push(entry);
+                journal.save();
+            }
+            _ => self.begin_batch(vec![entry]),
+        }
+        let task = self.schedule(index, &fonts);
+        self.abortable(task)
+    }
+
+    /// The one file converting `file` writes, or `None` when it writes several (ZIP archives,
+    /// Excel workbooks split into sheets, documents split at headings) or appends to another PDF.
+    fn single_output(&self, file: &FileEntry) -> Option<PathBuf> {
+        let format = file.format_override.unwrap_or(self.options.output_format);
+        let file_type = converter::FileType::from_path(&file.path);
+        let sheet_files = self.options.excel_sheet_files && file_type.is_spreadsheet();
+        let section_files = self.options.split_by_heading && file_type.splits_at_headings();
+        if file.is_zip() || sheet_files || section_files || (self.append_target.is_some() && format == OutputFormat::Pdf) {
+            return None;
+        }
+        let dir = self.output_dir_for(file)?;
+        Some(dir.join(format!("{}.{}", file.path.file_stem()?.to_string_lossy(), format.extension())))
+    }
+
+    /// The folder the outputs of `file` go to: the output folder, under the subfolder matching
+    /// the file's place in its added folder with [`FolderLayout::Mirror`]; the file's own
+    /// folder if no output folder is chosen.
+    fn output_dir_for(&self, file: &FileEntry) -> Option<PathBuf> {
+        let Some(dir) = &self.output_dir else {
+            return file.path.parent().map(Path::to_path_buf);
+        };
+        let subfolder = match (&file.root, self.folder_layout) {
+            (Some(root), FolderLayout::Mirror) => file.path.parent().and_then(|p| p.strip_prefix(root).ok()),
+            _ => None,
+        };
+        Some(subfolder.map_or_else(|| dir.clone(), |sub| dir.join(sub)))
+    }
+
+    /// Starts an empty batch and its journal; files join it through [`Self::schedule`].
+    fn begin_batch(&mut self, journaled: Vec<JournalEntry>) {
+        self.resume_offer = None;
+        self.status_message = None;
+        let journal = Journal {
+            output_dir: self.output_dir.clone(),
+            options: self.options.clone(),
+            files: journaled,
+        };
+        journal.save();
+        self.journal = Some(journal);
+        // Files appended to one PDF land in list order only if they are converted one by one.
+        let workers = if self.append_target.is_some() { 1 } else { self.performance.workers() };
+        self.batch = Some(BatchState {
+            outstanding: HashMap::new(),
+            total: 0,
+            completed: 0,
+            succeeded: 0,
+            failures: Vec::new(),
+            started: Instant::now(),
+            pool: WorkerPool::new(workers, self.performance.pause()),
+            handles: Vec::new(),
+        });
+    }
+
+    /// Queues the file at `index` on the running batch's workers and returns the task that
+    /// reports its result.
+    fn schedule(&mut self, index: usize, fonts: &Arc<converter::FontSet>) -> Task<Message> {
+        let output_dir = self.files.get(index).and_then(|f| self.output_dir_for(f)).unwrap_or_default();
+        let (Some(batch), Some(file)) = (self.batch.as_mut(), self.files.get_mut(index)) else {
+            return Task::none();
+        };
+        file.status = ConversionStatus::Converting;
+
+        let input_path = file.path.clone();
+        let file_stem = input_path.file_stem().unwrap().to_string_lossy().to_string();
+        let fonts = fonts.clone();
+        let options = match file.format_override {
+            Some(format) if format != self.options.output_format => {
+                ConversionOptions { output_format: format, ..self.options.clone() }
+            }
+            _ => self.options.clone(),
+        };
+        let output_path = output_dir.join(format!("{}.{}", file_stem, options.output_format.extension()));
+        let zip_selection: Option<Vec<String>> = file.is_zip().then(|| {
+            file.zip_entries.iter().filter(|(_, selected)| *selected).map(|(name, _)| name.clone()).collect()
+        });
+        // Only PDF output can be appended; ZIP entries and other formats are
+        // still written as separate files.
+        let append_target = self.append_target.clone()
+            .filter(|_| zip_selection.is_none() && options.output_format == OutputFormat::Pdf);
+        let sheet_files = options.excel_sheet_files
+            && converter::FileType::from_path(&input_path).is_spreadsheet();
+        let section_files = options.split_by_heading
+            && converter::FileType::from_path(&input_path).splits_at_headings();
+
+        let (tx, rx) = futures::channel::oneshot::channel();
+        // Loading runs on a pool worker; rendering is queued on the shared
+        // render thread (see `pipeline`).
+        batch.pool.execute(move || {
+            // The batch was stopped while this file waited for a worker.
+            if tx.is_canceled() {
+                return;
+            }
+            let timeout = options.timeout_secs;
+            // Taken before converting, so an edit made meanwhile still counts as a change.
+            let hash = history::content_hash(&input_path).ok();
+            let run = move || {
+                // A subfolder mirroring the added folder may not exist yet.
+                std::fs::create_dir_all(&output_dir)
+                    .map_err(|e| ConversionError::Io(format!("Failed to create {}: {}", output_dir.display(), e)))?;
+                match (zip_selection, append_target) {
+                    (Some(entries), _) => converter::convert_zip(&input_path, &output_dir, &entries, &fonts, &options),
+                    (None, Some(target)) => converter::convert_appending(&input_path, &target, &fonts, &options).map(|_| vec![target]),
+                    (None, None) if sheet_files => converter::convert_sheets(&input_path, &output_dir, &fonts, &options),
+                    (None, None) if section_files => converter::convert_sections(&input_path, &output_dir, &fonts, &options),
+                    (None, None) => converter::convert(&input_path, &output_path, &fonts, &options).map(|_| vec![output_path]),
+                }
+            };
+            let res = if timeout > 0 {
+                pipeline::with_timeout(Duration::from_secs(timeout.into()), run)
+            } else {
+                run()
+            };
+            let _ = tx.send((res, hash));
+        });
+
+        let task = self.next_task_id;
+        self.next_task_id += 1;
+        batch.outstanding.insert(task, index);
+        batch.total += 1;
+        Task::perform(async move {
+            rx.await.unwrap_or((Err(ConversionError::Cancelled), None))
+        }, move |(res, hash)| Message::ConversionFinished(task, res, hash))
+    }
+
+    /// Makes `task` abortable together with the rest of the running batch.
+    fn abortable(&mut self, task: Task<Message>) -> Task<Message> {
+        let (task, handle) = task.abortable();
+        if let Some(batch) = &mut self.batch {
+            batch.handles.push(handle);
+        }
+        task
+    }
+
+    /// Ends the running batch after `failed` failed, putting files that have not finished back to pending.
+    ///
+    /// Files already on their worker threads still finish writing, but their results are discarded.
+    fn stop_batch(&mut self, failed: &str) {
+        if let Some(batch) = &mut self.batch {
+            for handle in batch.handles.drain(..) {
+                handle.abort();
+            }
+        }
+        let mut cancelled = 0;
+        for file in &mut self.files {
+            if matches!(file.status, ConversionStatus::Converting) {
+                file.status = ConversionStatus::Pending;
+                cancelled += 1;
+            }
+        }
+        warn!("Stopping batch after {} failed, {} files cancelled", failed, cancelled);
+        let mut message = format!("{} 转换失败，已停止批量转换，{} 个文件未转换", failed, cancelled);
+        if self.write_report {
+            self.export_report();
+            if let Some(report) = self.status_message.take() {
+                message = format!("{}；{}", message, report);
+            }
+        }
+        self.status_message = Some(message);
+        self.finish_batch(cancelled);
+    }
+
+    /// Records the summary of the batch that just ended and shows it if anything failed.
+    fn finish_batch(&mut self, cancelled: usize) {
+        let Some(batch) = self.batch.take() else {
+            return;
+        };
+        if self.journal.take().is_some() {
+            Journal::remove();
+        }
+        let summary = RunSummary {
+            succeeded: batch.succeeded,
+            failures: batch.failures,
+            cancelled,
+            elapsed: batch.started.elapsed(),
+        };
+        info!(
+            "Batch summary: {} succeeded, {} failed, {} cancelled in {:.1?}",
+            summary.succeeded, summary.failures.len(), summary.cancelled, summary.elapsed
+        );
+        self.show_summary = !summary.failures.is_empty();
+        self.summary = Some(summary);
+    }
+
+    /// Where the batch's outputs and report go: the chosen output folder, else the first file's folder.
+    fn output_folder(&self) -> Option<PathBuf> {
+        self.output_dir.clone().or_else(|| {
+            self.files.first().and_then(|f| f.path.parent()).map(|p| p.to_path_buf())
+        })
+    }
+
+    /// Writes the conversion report next to the outputs and reports the result in the status line.
+    fn export_report(&mut self) {
+        let Some(dir) = self.output_folder() else {
+            return;
+        };
+        let rows: Vec<report::ReportRow> = self.files.iter().map(|f| {
+            let (status, error) = match &f.status {
+                ConversionStatus::Pending => ("pending", None),
+                ConversionStatus::Converting => ("converting", None),
+                ConversionStatus::Success => ("success", None),
+                ConversionStatus::Unchanged => ("unchanged", None),
+                ConversionStatus::Error(e) => ("failed", Some(e.to_string())),
+            };
+            report::ReportRow { source: &f.path, outputs: &f.outputs, status, error }
+        }).collect();
+        self.status_message = Some(match report::write_report(&dir, &rows) {
+            Ok(path) => format!("转换报告已保存: {}", path.display()),
+            Err(e) => {
+                log::error!("Failed to write conversion report: {}", e);
+                format!("转换报告保存失败: {}", e)
+            }
+        });
+    }
+
+    /// Loads the output fonts off the UI thread.  Large CJK fonts can take a while to read and
+    /// parse, so this runs after the window is up.
+    fn load_fonts_task(config: FontConfig) -> Task<Message> {
+        Task::perform(
+            run_blocking(move || load_fonts(&config)),
+            |result| Message::FontsLoaded(result.map(Arc::new)),
+        )
+    }
+
+    /// Explains that no font could be loaded and offers to pick one, in place of the main window.
+    fn font_error_view<'a>(&'a self, error: &'a str) -> Element<'a, Message> {
+        let palette = Palette::new(self.accent, self.theme);
+        container(
+            column![
+                text("无法加载字体").size(24).color(palette.error),
+                text("系统字体和程序自带的字体都无法使用，因此无法生成文档。程序文件可能已损坏，请重新安装，或手动选择一个 .ttf / .otf 字体文件。")
+                    .size(14)
+                    .color(palette.text),
+                text(error).size(palette.small(12)).color(palette.muted),
+                text(format!("若所选字体可用，可在 {} 的 [fonts] 中设置 text = \"字体路径\"，以后启动时直接使用。", crate::config::CONFIG_FILE))
+                    .size(palette.small(12))
+                    .color(palette.muted),
+                row![
+                    button(text("选择字体文件…").size(16))
+                        .on_press(Message::PickFont)
+                        .padding(10)
+                        .style(move |_theme, status| {
+                            let mut base = button::Style::default();
+                            base.background = Some(palette.accent.into());
+                            base.text_color = palette.on_accent;
+                            base.border = iced::Border { radius: 6.0.into(), ..iced::Border::default() };
+                            if let button::Status::Hovered = status {
+                                base.background = Some(palette.accent_hover.into());
+                            }
+                            base
+                        }),
+                    button(text("打开日志文件夹").color(palette.accent))
+                        .on_press(Message::OpenLogsFolder)
+                        .style(|_,_| button::Style { background: None, ..button::Style::default() }),
+                ]
+                .spacing(20)
+                .align_y(iced::Alignment::Center),
+            ]
+            .spacing(20)
+            .max_width(640)
+            .align_x(iced::Alignment::Center)
+        )
+        .center_x(Length::Fill)
+        .center_y(Length::Fill)
+        .style(move |_theme| container::Style {
+            background: Some(palette.background.into()),
+            ..container::Style::default()
+        })
+        .into()
+    }
+
+    fn summary_view<'a>(&'a self, summary: &'a RunSummary) -> Element<'a, Message> {
+        let palette = Palette::new(self.accent, self.theme);
+
+        let mut counts = row![
+            text(format!("成功 {}", summary.succeeded)).size(16).color(palette.success),
+            text(format!("失败 {}", summary.failures.len())).size(16).color(palette.error),
+        ]
+        .spacing(20);
+        if summary.cancelled > 0 {
+            counts = counts.push(text(format!("未转换 {}", summary.cancelled)).size(16).color(palette.muted));
+        }
+        counts = counts.push(text(format!("用时 {:.1} 秒", summary.elapsed.as_secs_f32())).size(16).color(palette.text));
+
+        let failures: Element<Message> = if summary.failures.is_empty() {
+            text("所有文件均已成功转换").size(14).color(palette.muted).into()
+        } else {
+            // Failures grouped by cause, in order of first occurrence.
+            let mut groups: Vec<(&'static str, Vec<&(String, ConversionError)>)> = Vec::new();
+            for failure in &summary.failures {
+                let cause = error_cause(&failure.1);
+                match groups.iter_mut().find(|(c, _)| *c == cause) {
+                    Some((_, members)) => members.push(failure),
+                    None => groups.push((cause, vec![failure])),
+                }
+            }
+            scrollable(Column::with_children(
+                groups.into_iter().map(|(cause, members)| {
+                    let mut group = column![text(format!("{} ({})", cause, members.len())).size(15).color(palette.error)].spacing(8);
+                    for (name, error) in members {
+                        group = group.push(
+                            column![
+                                text(name.as_str()).size(14).color(palette.text),
+                                text(error_message(error)).size(palette.small(12)).color(palette.muted),
+                            ]
+                            .spacing(2),
+                        );
+                    }
+                    group.into()
+                })
+            ).spacing(16)).height(Length::Fill).into()
+        };
+
+        let action_button = |label: &'static str, message: Message| {
+            button(text(label).size(14))
+                .on_press(message)
+                .padding(10)
+                .style(move |_theme, status| {
+                    let mut base = button::Style::default();
+                    base.background = Some(iced::Color::from_rgb(0.3, 0.3, 0.3).into());
+                    base.text_color = palette.text;
+                    base.border = iced::Border { radius: 6.0.into(), ..iced::Border::default() };
+                    match status {
+                        button::Status::Hovered => {
+                            base.background = Some(iced::Color::from_rgb(0.4, 0.4, 0.4).into());
+                            base
+                        },
+                        _ => base,
+                    }
+                })
+        };
+
+        let status_line: Element<Message> = match &self.status_message {
+            Some(msg) => text(msg).size(palette.small(12)).color(palette.muted).into(),
+            None => Column::new().into(),
+        };
+
+        container(
+            column![
+                text("本次转换结果").size(24).color(palette.text),
+                counts,
+                container(failures)
+                    .height(Length::Fill)
+                    .width(Length::Fill)
+                    .padding(15)
+                    .style(move |_theme| container::Style {
+                        background: Some(palette.card.into()),
+                        border: iced::Border { radius: 8.0.into(), ..iced::Border::default() },
+                        ..container::Style::default()
+                    }),
+                status_line,
+                row![
+                    action_button("打开输出文件夹", Message::OpenOutputFolder),
+                    action_button("导出报告", Message::ExportReport),
+                    action_button("关闭", Message::CloseSummary),
+                ]
+                .spacing(15),
+            ]
+            .spacing(20)
+            .max_width(700)
+        )
+        .padding(30)
+        .center_x(Length::Fill)
+        .height(Length::Fill)
+        .style(move |_theme| container::Style {
+            background: Some(palette.background.into()),
+            ..container::Style::default()
+        })
+        .into()
+    }
+
+    fn settings_view(&self) -> Element<'_, Message> {
+        let palette = Palette::new(self.accent, self.theme);
+
+        let setting_row = |label: &'static str, hint: &'static str, control: Element<'static, Message>| -> Element<'static, Message> {
+            container(row![
+                column![
+                    text(label).size(14).color(palette.text),
+                    text(hint).size(palette.small(11)).color(palette.muted),
+                ].spacing(4).width(Length::Fill),
+                control,
+            ].spacing(15).align_y(iced::Alignment::Center))
+            .padding(12)
+            .style(move |_theme| container::Style {
+                background: Some(palette.card.into()),
+                border: iced::Border { radius: 6.0.into(), ..iced::Border::default() },
                 ..container::Style::default()
             })
             .into()
+        };
+
+        let value_slider = |range: std::ops::RangeInclusive<u8>, value: u8, unit: &str, on_change: fn(u8) -> Message| -> Element<'static, Message> {
+            row![
+                slider(range, value, on_change).width(160),
+                text(format!("{} {}", value, unit).trim_end().to_string()).size(14).color(palette.text).width(50),
+            ]
+            .spacing(10)
+            .align_y(iced::Alignment::Center)
+            .into()
+        };
+
+        let margins = self.options.margins;
+        let margin_input = |label: &'static str, side: MarginSide, value: u8| {
+            row![
+                text(label).size(13).color(palette.muted),
+                text_input("0", &value.to_string())
+                    .on_input(move |v| Message::MarginChanged(side, v))
+                    .size(13)
+                    .width(44),
+            ]
+            .spacing(4)
+            .align_y(iced::Alignment::Center)
+        };
+        let margin_inputs: Element<'static, Message> = row![
+            margin_input("上", MarginSide::Top, margins.top),
+            margin_input("右", MarginSide::Right, margins.right),
+            margin_input("下", MarginSide::Bottom, margins.bottom),
+            margin_input("左", MarginSide::Left, margins.left),
+            text("mm").size(13).color(palette.muted),
+        ]
+        .spacing(10)
+        .align_y(iced::Alignment::Center)
+        .into();
+
+        let color_picker = |target: ColorTarget, current: RgbColor, choices: &[RgbColor]| -> Element<'static, Message> {
+            let mut swatches = row![].spacing(6).align_y(iced::Alignment::Center);
+            for &color in choices {
+                let fill = iced::Color::from_rgb8(color.r, color.g, color.b);
+                let selected = color == current;
+                swatches = swatches.push(
+                    button(text(""))
+                        .width(20)
+                        .height(20)
+                        .on_press(Message::ColorChanged(target, color))
+                        .style(move |_theme, status| button::Style {
+                            background: Some(fill.into()),
+                            border: iced::Border {
+                                color: if selected || matches!(status, button::Status::Hovered) { palette.text } else { palette.muted },
+                                width: if selected { 2.0 } else { 1.0 },
+                                radius: 4.0.into(),
+                            },
+                            ..button::Style::default()
+                        }),
+                );
+            }
+            swatches.push(text(current.to_hex()).size(palette.small(12)).color(palette.muted).width(60)).into()
+        };
+
+        let preset_names: Vec<String> = self.presets.iter().map(|p| p.name.clone()).collect();
+        let active_preset = self.presets.iter().find(|p| p.options == self.options).map(|p| p.name.clone());
+
+        let compression = self.options.image_compression;
+        let custom_redaction_hint = if self.redaction_patterns.is_empty() {
+            "在 topdf_config.toml 的 [redaction] 中添加正则表达式后可用"
+        } else {
+            "按 topdf_config.toml 中 [redaction] 的正则表达式替换为 ████"
+        };
+        let part_range_hint = if ranges::PartRange::parse(&self.options.part_range).is_ok() {
+            "只转换 Excel/ODS 中这些序号的工作表、SQLite 中这些序号的数据表，如 1-3,5 或 2-；留空则全部转换"
+        } else {
+            "格式有误，应为以逗号分隔的序号或范围，如 1-3,5；转换时将报错"
+        };
+        let background_hint = if self.options.text_contrast() < ConversionOptions::MIN_CONTRAST {
+            "当前标题或正文颜色与背景对比度过低，可能难以阅读，请选择更深或更浅的文字颜色"
+        } else {
+            "整页的背景颜色，仅用于 PDF 输出；深色背景请搭配浅色文字"
+        };
+        let settings = column![
+            setting_row(
+                "预设",
+                "一键套用常用设置，可在 topdf_config.toml 中添加自定义预设",
+                pick_list(preset_names, active_preset, Message::ApplyPreset)
+                    .placeholder("自定义")
+                    .text_size(14)
+                    .into(),
+            ),
+            setting_row(
+                "输出格式",
+                "默认的输出格式，可在文件列表中为单个文件单独选择；Markdown 仅适用于 DOCX 和 HTML 文件",
+                pick_list(OutputFormat::ALL, Some(self.options.output_format), Message::OutputFormatChanged)
+                    .text_size(14)
+                    .into(),
+            ),
+            setting_row(
+                "纸张大小",
+                "输出页面的纸张规格（纵向）",
+                pick_list(PageSize::ALL, Some(self.options.page_size), Message::PageSizeChanged)
+                    .text_size(14)
+                    .into(),
+            ),
+            setting_row(
+                "宽表格横向",
+                "CSV、Excel 或 SQLite 表格超出纵向页面宽度时，整个文档改为横向",
+                checkbox(self.options.auto_landscape).on_toggle(Message::ToggleAutoLandscape).into(),
+            ),
+            setting_row(
+                "识别网址",
+                "将纯文本中的网址（http://、https://、www. 开头）显示为可点击的蓝色链接；关闭后按原样输出",
+                checkbox(self.options.detect_links).on_toggle(Message::ToggleDetectLinks).into(),
+            ),
+            setting_row(
+                "Markdown 智能标点",
+                "将 Markdown 中的 -- 和 --- 显示为短破折号和长破折号、... 显示为省略号、直引号显示为弯引号；关闭后按原字符输出，适合代码较多的文档",
+                checkbox(self.options.smart_punctuation).on_toggle(Message::ToggleSmartPunctuation).into(),
+            ),
+            setting_row(
+                "演示模式",
+                "将 Markdown 和 Org 文件排版为幻灯片：字号加倍、页边距加宽，每个一级和二级标题另起一页，适合把提纲快速转成演示文稿",
+                checkbox(self.options.presentation).on_toggle(Message::TogglePresentation).into(),
+            ),
+            setting_row(
+                "字号",
+                "正文的基础字号，标题与代码按比例缩放",
+                value_slider(ConversionOptions::FONT_SIZE_RANGE, self.options.font_size, "pt", Message::FontSizeChanged),
+            ),
+            setting_row(
+                "制表符宽度",
+                "渲染前将 Tab 展开为空格，按此列数对齐，使代码缩进在任何阅读器中都一致",
+                pick_list(ConversionOptions::TAB_WIDTHS, Some(self.options.tab_width), Message::TabWidthChanged)
+                    .text_size(14)
+                    .into(),
+            ),
+            setting_row(
+                "页边距",
+                "上、右、下、左四边的留白，装订时可加大左侧边距",
+                margin_inputs,
+            ),
+            setting_row(
+                "标题颜色",
+                "Markdown、reStructuredText 和 DOCX 标题的文字颜色",
+                color_picker(ColorTarget::Heading, self.options.heading_color, &TEXT_COLORS),
+            ),
+            setting_row(
+                "正文颜色",
+                "正文段落的文字颜色",
+                color_picker(ColorTarget::Body, self.options.body_color, &TEXT_COLORS),
+            ),
+            setting_row(
+                "页面背景",
+                background_hint,
+                color_picker(ColorTarget::Background, self.options.page_background, &BACKGROUND_COLORS),
+            ),
+            setting_row(
+                "页码",
+                "在每页顶部居中显示页码",
+                checkbox(self.options.page_numbers).on_toggle(Message::TogglePageNumbers).into(),
+            ),
+            setting_row(
+                "正文对齐",
+                "应用于文本、Markdown 与 HTML 的正文段落，标题保持原有对齐",
+                pick_list(TextAlign::ALL, Some(self.options.text_align), Message::TextAlignChanged)
+                    .text_size(14)
+                    .into(),
+            ),
+            setting_row(
+                "Markdown 段落间距",
+                "控制 Markdown 段落、标题与代码块之间的空白",
+                pick_list(Density::ALL, Some(self.options.markdown_density), Message::DensityChanged)
+                    .text_size(14)
+                    .into(),
+            ),
+            setting_row(
+                "首行缩进（毫米）",
+                "文本文件每行与 Markdown 每个段落的首行向右缩进，适合排版书籍式文档，0 表示不缩进",
+                text_input("0", &self.options.first_line_indent_mm.to_string())
+                    .on_input(Message::FirstLineIndentChanged)
+                    .size(14)
+                    .width(80)
+                    .into(),
+            ),
+            setting_row(
+                "段后间距（行）",
+                "文本文件每行与 Markdown 每个段落之后额外留出的空白，空行不受影响",
+                pick_list(ConversionOptions::PARAGRAPH_SPACINGS, Some(self.options.paragraph_spacing), Message::ParagraphSpacingChanged)
+                    .text_size(14)
+                    .into(),
+            ),
+            setting_row(
+                "JSON 布局",
+                "展开按层级缩进显示每个值；紧凑将顶层数组的每个元素（或顶层对象的每个键）写成一行，适合记录较多的 JSON 日志",
+                pick_list(JsonLayout::ALL, Some(self.options.json_layout), Message::JsonLayoutChanged)
+                    .text_size(14)
+                    .into(),
+            ),
+            setting_row(
+                "文本编码",
+                "读取文本、代码、CSV 等文件时使用的编码；“自动”依次尝试 UTF-8、GB18030、Big5、Shift-JIS，识别错误时可手动指定",
+                pick_list(TextEncoding::ALL, Some(self.options.text_encoding), Message::TextEncodingChanged)
+                    .text_size(14)
+                    .into(),
+            ),
+            setting_row(
+                "DOCX 修订",
+                "含修订记录的 Word 文档输出修订后或修订前的内容",
+                pick_list(TrackedChanges::ALL, Some(self.options.tracked_changes), Message::TrackedChangesChanged)
+                    .text_size(14)
+                    .into(),
+            ),
+            setting_row(
+                "界面主题",
+                "高对比度主题使用黑色背景与更亮的文字，并放大说明文字；选择会保存到配置文件",
+                pick_list(UiTheme::ALL, Some(self.theme), Message::ThemeChanged)
+                    .text_size(14)
+                    .into(),
+            ),
+            setting_row(
+                "主题色",
+                "按钮、进度条与标题使用的颜色；选择会保存到配置文件",
+                pick_list(AccentColor::ALL, Some(self.accent), Message::AccentChanged)
+                    .text_size(14)
+                    .into(),
+            ),
+            setting_row(
+                "性能模式",
+                "省电模式逐个转换并在文件间稍作停顿，高性能模式使用全部 CPU 核心；选择会保存到配置文件",
+                pick_list(PerformanceMode::ALL, Some(self.performance), Message::PerformanceModeChanged)
+                    .text_size(14)
+                    .into(),
+            ),
+            setting_row(
+                "PDF 标准",
+                "PDF/A-2b 适用于长期归档，会写入 XMP 元数据与 sRGB 色彩配置",
+                pick_list(Conformance::ALL, Some(self.options.conformance), Message::ConformanceChanged)
+                    .text_size(14)
+                    .into(),
+            ),
+            setting_row(
+                "图片分辨率",
+                "按此 DPI 计算图片在页面上的实际尺寸（如 300 DPI 扫描件保持原大），超出页面时缩小至适合页面",
+                pick_list(ConversionOptions::IMAGE_DPIS, Some(self.options.image_dpi), Message::ImageDpiChanged)
+                    .text_size(14)
+                    .into(),
+            ),
+            setting_row(
+                "图片位置",
+                "缩放至页面时忽略图片分辨率，放大或缩小至填满页面宽度或高度；单独一页时每张图片前后分页，适合照片批量转换",
+                pick_list(ImagePlacement::ALL, Some(self.options.image_placement), Message::ImagePlacementChanged)
+                    .text_size(14)
+                    .into(),
+            ),
+            setting_row(
+                "图片像素上限（百万）",
+                "超过此像素数的超大图片（如全景照片）解码后立即缩小到该大小，并逐张处理，避免批量转换时内存耗尽，0 表示不限制",
+                text_input("50", &self.options.image_max_megapixels.to_string())
+                    .on_input(Message::ImageMaxMegapixelsChanged)
+                    .size(14)
+                    .width(80)
+                    .into(),
+            ),
+            setting_row(
+                "压缩图片",
+                "将图片缩放至页面宽度、150 DPI 并以 JPEG 重新编码，显著减小 PDF 体积",
+                checkbox(compression.enabled).on_toggle(Message::ToggleImageCompression).into(),
+            ),
+            setting_row(
+                "JPEG 质量",
+                "压缩图片时使用，数值越低文件越小、画质越差",
+                value_slider(ImageCompression::QUALITY_RANGE, compression.quality, "", Message::ImageQualityChanged),
+            ),
+            setting_row(
+                "保留无损图片",
+                "压缩图片时跳过 PNG 与 BMP（如图表、截图），保持原始清晰度",
+                checkbox(compression.keep_lossless).on_toggle(Message::ToggleKeepLossless).into(),
+            ),
+            setting_row(
+                "隐去邮箱地址",
+                "转换前将邮箱地址替换为 ████",
+                checkbox(self.options.redaction.emails).on_toggle(Message::ToggleRedactEmails).into(),
+            ),
+            setting_row(
+                "隐去电话号码",
+                "转换前将电话号码替换为 ████",
+                checkbox(self.options.redaction.phone_numbers).on_toggle(Message::ToggleRedactPhones).into(),
+            ),
+            setting_row(
+                "隐去自定义内容",
+                custom_redaction_hint,
+                checkbox(!self.options.redaction.patterns.is_empty())
+                    .on_toggle_maybe((!self.redaction_patterns.is_empty()).then_some(Message::ToggleRedactCustom))
+                    .into(),
+            ),
+            setting_row(
+                "SQLite 行数上限",
+                "每个数据表最多输出的行数，其余行以提示代替",
+                text_input("200", &self.options.sqlite_row_limit.to_string())
+                    .on_input(Message::SqliteRowLimitChanged)
+                    .size(14)
+                    .width(80)
+                    .into(),
+            ),
+            setting_row(
+                "每个工作表一个 PDF",
+                "Excel 和 ODS 文件的每个工作表分别输出为“文件名_工作表名”，否则只输出第一个工作表",
+                checkbox(self.options.excel_sheet_files).on_toggle(Message::ToggleExcelSheetFiles).into(),
+            ),
+            setting_row(
+                "按一级标题拆分",
+                "Markdown、Org 和 DOCX 文件在每个一级标题处拆分，各章分别输出为“文件名_标题”",
+                checkbox(self.options.split_by_heading).on_toggle(Message::ToggleSplitByHeading).into(),
+            ),
+            setting_row(
+                "日志行号",
+                "在 .log 文件每行前显示行号",
+                checkbox(self.options.log_line_numbers).on_toggle(Message::ToggleLogLineNumbers).into(),
+            ),
+            setting_row(
+                "工作表/数据表范围",
+                part_range_hint,
+                text_input("全部", &self.options.part_range)
+                    .on_input(Message::PartRangeChanged)
+                    .size(14)
+                    .width(120)
+                    .into(),
+            ),
+            setting_row(
+                "表格隔行底色",
+                "CSV 和 Excel 数据以带边框的表格输出，标题行和隔行加浅灰底色，便于阅读长表格",
+                checkbox(self.options.table_stripes).on_toggle(Message::ToggleTableStripes).into(),
+            ),
+            setting_row(
+                "附加统计页",
+                "在 PDF 末尾附加一页文档统计：字数、字符数、段落与行数、表格和图片数量以及文件大小",
+                checkbox(self.options.stats_page).on_toggle(Message::ToggleStatsPage).into(),
+            ),
+            setting_row(
+                "最大页数",
+                "内容超出约此页数时截断并注明（按行数估算），0 表示不限制",
+                text_input("0", &self.options.max_pages.to_string())
+                    .on_input(Message::MaxPagesChanged)
+                    .size(14)
+                    .width(80)
+                    .into(),
+            ),
+            setting_row(
+                "超长行拆分",
+                "文本和代码中超过此字符数的行拆成多段排版，避免压缩过的脚本等超长行使转换长时间无响应，0 表示不拆分",
+                text_input("10000", &self.options.long_line_chars.to_string())
+                    .on_input(Message::LongLineCharsChanged)
+                    .size(14)
+                    .width(80)
+                    .into(),
+            ),
+            setting_row(
+                "单个文件超时（秒）",
+                "单个文件转换超过此时间仍未完成时放弃并标记为转换超时，0 表示不限制",
+                text_input("0", &self.options.timeout_secs.to_string())
+                    .on_input(Message::TimeoutChanged)
+                    .size(14)
+                    .width(80)
+                    .into(),
+            ),
+            setting_row(
+                "遇到错误时停止",
+                "任一文件转换失败时立即停止批量转换，未完成的文件保持等待状态",
+                checkbox(self.options.error_policy == ErrorPolicy::Stop).on_toggle(Message::ToggleStopOnError).into(),
+            ),
+            setting_row(
+                "生成转换报告",
+                "批量转换完成后在输出目录写入 topdf_report.csv",
+                checkbox(self.write_report).on_toggle(Message::ToggleReport).into(),
+            ),
+            setting_row(
+                "拖放即转换",
+                "拖入窗口的文件立即转换到输出目录，无需点击“开始转换”；正在转换时加入当前批次",
+                checkbox(self.convert_on_drop).on_toggle(Message::ToggleConvertOnDrop).into(),
+            ),
+            setting_row(
+                "强制转换",
+                "关闭时“开始转换”跳过输出文件已存在且比源文件新的文件；开启后总是重新转换",
+                checkbox(self.force_convert).on_toggle(Message::ToggleForceConvert).into(),
+            ),
+            setting_row(
+                "文件夹结构",
+                "添加文件夹时，展平将所有文件输出到输出目录；保持结构则按文件在所添加文件夹中的位置建立子目录，如 src/a/x.md 输出为 输出目录/a/x.pdf",
+                pick_list(FolderLayout::ALL, Some(self.folder_layout), Message::FolderLayoutChanged)
+                    .text_size(14)
+                    .into(),
+            ),
+        ]
+        .spacing(10);
+
+        container(
+            column![
+                text("转换设置").size(24).color(palette.text),
+                scrollable(settings).height(Length::Fill),
+                button(text("返回").size(16))
+                    .on_press(Message::ToggleSettings)
+                    .padding(10)
+                    .style(move |_theme, status| {
+                         let mut base = button::Style::default();
+                         base.background = Some(iced::Color::from_rgb(0.3, 0.3, 0.3).into());
+                         base.text_color = palette.text;
+                         base.border = iced::Border { radius: 6.0.into(), ..iced::Border::default() };
+                         match status {
+                             button::Status::Hovered => {
+                                 base.background = Some(iced::Color::from_rgb(0.4, 0.4, 0.4).into());
+                                 base
+                             },
+                             _ => base,
+                         }
+                    })
+            ]
+            .spacing(20)
+            .align_x(iced::Alignment::Center)
+        )
+        .padding(30)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .style(move |_theme| container::Style {
+            background: Some(palette.background.into()),
+            ..container::Style::default()
+        })
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_interface_font_covers_the_interface_strings() {
+        let bytes = include_bytes!("../assets/LXGWWenKai-UI.ttf");
+        assert_eq!(cjk_family_name(bytes).as_deref(), Some("LXGW WenKai"));
+        let face = ttf_parser::Face::parse(bytes, 0).unwrap();
+        let missing: String = include_str!("ui.rs").chars()
+            .filter(|c| matches!(c, '\u{3000}'..='\u{303f}' | '\u{4e00}'..='\u{9fff}' | '\u{ff00}'..='\u{ffef}'))
+            .filter(|&c| face.glyph_index(c).is_none())
+            .collect();
+        assert!(missing.is_empty(), "rebuild assets/LXGWWenKai-UI.ttf to add {}", missing);
     }
 }